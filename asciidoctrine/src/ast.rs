@@ -1,13 +1,21 @@
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
+/// A document. Its elements borrow from the source of the
+/// document, `into_owned` gives an AST which doesn't
+/// (e.g. to keep it around or to send it to another thread).
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct AST<'a> {
-  pub content: &'a str,
+  #[serde(borrow)]
+  pub content: Cow<'a, str>,
   pub elements: Vec<ElementSpan<'a>>,
   pub attributes: Vec<Attribute<'a>>,
 }
 
-impl AST<'_> {
+/// An AST which doesn't borrow from its source
+pub type OwnedAST = AST<'static>;
+
+impl<'a> AST<'a> {
   pub fn get_attribute(&self, name: &str) -> Option<&str> {
     for attribute in self.attributes.iter() {
       if &attribute.key == name {
@@ -20,6 +28,35 @@ impl AST<'_> {
 
     None
   }
+
+  /// Copies everything the AST borrows from its source
+  pub fn into_owned(self) -> OwnedAST {
+    AST {
+      content: Cow::Owned(self.content.into_owned()),
+      elements: self.elements.into_iter().map(ElementSpan::into_owned).collect(),
+      attributes: self.attributes.into_iter().map(Attribute::into_owned).collect(),
+    }
+  }
+
+  /// Gets the footnotes of the document (and all included
+  /// documents) in the order of their numbers. References
+  /// to earlier footnotes are left out.
+  pub fn footnotes(&self) -> Vec<&ElementSpan<'a>> {
+    let mut footnotes = Vec::new();
+    collect_footnotes(&self.elements, &mut footnotes);
+
+    footnotes
+  }
+}
+
+fn collect_footnotes<'a, 'b>(elements: &'b [ElementSpan<'a>], out: &mut Vec<&'b ElementSpan<'a>>) {
+  for element in elements.iter() {
+    match &element.element {
+      Element::Footnote if element.get_attribute("content").is_some() => out.push(element),
+      Element::IncludeElement(include) => collect_footnotes(&include.inner.elements, out),
+      _ => collect_footnotes(&element.children, out),
+    }
+  }
 }
 
 /// The basic element of a document
@@ -34,7 +71,8 @@ pub struct ElementSpan<'a> {
   // parent
   pub source: Option<String>,
   // A string reference to the source
-  pub content: &'a str,
+  #[serde(borrow)]
+  pub content: Cow<'a, str>,
   // TODO Add start and end point
   pub start: usize,
   pub end: usize,
@@ -70,6 +108,70 @@ impl ElementSpan<'_> {
   }
 }
 
+impl<'a> ElementSpan<'a> {
+  /// Copies everything the element borrows from its source
+  pub fn into_owned(self) -> ElementSpan<'static> {
+    ElementSpan {
+      source: self.source,
+      content: Cow::Owned(self.content.into_owned()),
+      start: self.start,
+      end: self.end,
+      start_line: self.start_line,
+      start_col: self.start_col,
+      end_line: self.end_line,
+      end_col: self.end_col,
+      element: self.element.into_owned(),
+      children: self.children.into_iter().map(ElementSpan::into_owned).collect(),
+      positional_attributes: self
+        .positional_attributes
+        .into_iter()
+        .map(AttributeValue::into_owned)
+        .collect(),
+      attributes: self.attributes.into_iter().map(Attribute::into_owned).collect(),
+    }
+  }
+
+  /// Creates an error element for a problem inside of the
+  /// content of this element. The offset is relative to the
+  /// start of the content. The error spans the rest of the line.
+  pub fn error_at(&self, offset: usize, message: String) -> ElementSpan<'a> {
+    let offset = offset.min(self.content.len());
+    let before = &self.content[..offset];
+    let length = self.content[offset..].lines().next().unwrap_or("").len();
+    let content = slice(&self.content, offset..offset + length).unwrap_or_default();
+
+    let start_line = self.start_line + before.matches('\n').count();
+    let start_col = match before.rfind('\n') {
+      Some(position) => before[position + 1..].chars().count() + 1,
+      None => self.start_col + before.chars().count(),
+    };
+
+    ElementSpan {
+      element: Element::Error(message),
+      source: None,
+      children: Vec::new(),
+      attributes: Vec::new(),
+      positional_attributes: Vec::new(),
+      start: self.start + offset,
+      end: self.start + offset + length,
+      start_line,
+      start_col,
+      end_line: start_line,
+      end_col: start_col + content.chars().count(),
+      content,
+    }
+  }
+}
+
+/// Gets a part of a content. It borrows from the source as
+/// long as the content does.
+pub(crate) fn slice<'a>(content: &Cow<'a, str>, range: std::ops::Range<usize>) -> Option<Cow<'a, str>> {
+  match content {
+    Cow::Borrowed(content) => content.get(range).map(Cow::Borrowed),
+    Cow::Owned(content) => content.get(range).map(|part| Cow::Owned(part.to_string())),
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum Element<'a> {
   Attribute(#[serde(borrow)] Attribute<'a>),
@@ -81,10 +183,22 @@ pub enum Element<'a> {
   Title {
     level: u32,
   },
+  /// A section with its title as the first child and the
+  /// content up to the next title of the same or a higher
+  /// level as the other children. The level is the level of
+  /// the section (`==` is 1), not the one of the title.
+  /// Only used when the reader is asked to nest sections.
+  Section {
+    level: u32,
+  },
   Table,
   List(ListType),
   Image,
+  /// An anchor (`anchor:id[]`) with its `id` attribute
   Anchor,
+  /// A note, tip, warning and so on. The children are the
+  /// blocks inside (a single paragraph for `NOTE: text`)
+  Admonition(AdmonitionType),
   /// Holds all blocks with special content and the type
   /// TODO Could be done with ExternalContent and all known
   /// Types here direktly
@@ -103,7 +217,9 @@ pub enum Element<'a> {
   /// The following variants are inline elements nested
   /// inside a conainer element
 
-  /// Element with a special style. The attributes define the kind of style
+  /// Element with a special style. The attributes define the kind of style.
+  /// The children are the inline elements inside, the `content` attribute
+  /// holds the raw text between the marks.
   Styled,
   /// A chunk of text.
   Text,
@@ -111,8 +227,34 @@ pub enum Element<'a> {
   XRef,
   /// An external link
   Link,
+  /// A footnote. The `content` attribute holds its text and the
+  /// `number` attribute its (automatic) number. A footnote without
+  /// content refers to the earlier footnote with the same `id`.
+  Footnote,
+  /// An inline image (`image:icon.png[]`). Like an image block
+  /// it has the `path` attribute.
+  InlineImage,
+  /// A key or key combination (`kbd:[Ctrl+S]`). The `keys`
+  /// attribute holds the keys separated by `+`.
+  Keyboard,
+  /// A button (`btn:[OK]`) with its `label` attribute
+  Button,
+  /// A menu selection (`menu:File[Save]`). The `menu` attribute
+  /// holds the menu and `items` the (sub)menu items separated by `>`.
+  Menu,
+  /// Text which is written to the output as it is (`pass:[<u>text</u>]`).
+  /// The `content` attribute holds the text.
+  Passthrough,
+  /// A hard line break (` +` at the end of a line)
+  LineBreak,
+  /// Any other inline macro (`name:target[attributes]`). Its
+  /// `name` and `target` are held as attributes.
+  InlineMacro,
   /// A list item
   ListItem(u32),
+  /// A callout marker (e.g. `// <1>`) inside of a listing. The
+  /// `line` attribute holds the line of the listing it is in.
+  Callout(u32),
   /// A table row
   TableRow,
   /// A table cell
@@ -121,10 +263,52 @@ pub enum Element<'a> {
   Error(String),
 }
 
+impl Element<'_> {
+  pub fn into_owned(self) -> Element<'static> {
+    match self {
+      Element::Attribute(inner) => Element::Attribute(inner.into_owned()),
+      Element::Comment => Element::Comment,
+      Element::Paragraph => Element::Paragraph,
+      Element::Title { level } => Element::Title { level },
+      Element::Section { level } => Element::Section { level },
+      Element::Table => Element::Table,
+      Element::List(value) => Element::List(value),
+      Element::Image => Element::Image,
+      Element::Anchor => Element::Anchor,
+      Element::Admonition(value) => Element::Admonition(value),
+      Element::TypedBlock { kind } => Element::TypedBlock { kind },
+      Element::ExternalContent => Element::ExternalContent,
+      Element::IncludeElement(inner) => Element::IncludeElement(inner.into_owned()),
+      Element::Styled => Element::Styled,
+      Element::Text => Element::Text,
+      Element::XRef => Element::XRef,
+      Element::Link => Element::Link,
+      Element::Footnote => Element::Footnote,
+      Element::InlineImage => Element::InlineImage,
+      Element::Keyboard => Element::Keyboard,
+      Element::Button => Element::Button,
+      Element::Menu => Element::Menu,
+      Element::Passthrough => Element::Passthrough,
+      Element::LineBreak => Element::LineBreak,
+      Element::InlineMacro => Element::InlineMacro,
+      Element::ListItem(value) => Element::ListItem(value),
+      Element::Callout(value) => Element::Callout(value),
+      Element::TableRow => Element::TableRow,
+      Element::TableCell => Element::TableCell,
+      Element::Error(value) => Element::Error(value),
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum ListType {
   Bullet,
   Number,
+  /// A description list. Every item has its `term` as
+  /// attribute and the description as children.
+  Labeled,
+  /// The explanations of the callouts of the listing before
+  Callout,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -135,15 +319,78 @@ pub enum BlockType {
   Literal,
   Sidebar,
   Quote,
+  Verse,
+  Open,
   Example,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum AdmonitionType {
+  Note,
+  Tip,
+  Important,
+  Caution,
+  Warning,
+}
+
+impl AdmonitionType {
+  /// Gets the type from its label (e.g. `NOTE`)
+  pub fn from_label(label: &str) -> Option<Self> {
+    match label {
+      "NOTE" => Some(AdmonitionType::Note),
+      "TIP" => Some(AdmonitionType::Tip),
+      "IMPORTANT" => Some(AdmonitionType::Important),
+      "CAUTION" => Some(AdmonitionType::Caution),
+      "WARNING" => Some(AdmonitionType::Warning),
+      _ => None,
+    }
+  }
+
+  /// The caption shown for the admonition
+  pub fn caption(&self) -> &'static str {
+    match self {
+      AdmonitionType::Note => "Note",
+      AdmonitionType::Tip => "Tip",
+      AdmonitionType::Important => "Important",
+      AdmonitionType::Caution => "Caution",
+      AdmonitionType::Warning => "Warning",
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(from = "AttributeValueRepr<'a>", bound(deserialize = "'de: 'a"))]
 pub enum AttributeValue<'a> {
   String(String),
   Ref(&'a str),
 }
 
+/// Deserializes a `Ref` which can't be borrowed from the input
+/// (e.g. because of escaped characters) as a `String`
+#[derive(Deserialize)]
+enum AttributeValueRepr<'a> {
+  String(String),
+  Ref(#[serde(borrow)] Cow<'a, str>),
+}
+
+impl<'a> From<AttributeValueRepr<'a>> for AttributeValue<'a> {
+  fn from(value: AttributeValueRepr<'a>) -> Self {
+    match value {
+      AttributeValueRepr::String(value) => AttributeValue::String(value),
+      AttributeValueRepr::Ref(value) => value.into(),
+    }
+  }
+}
+
+impl<'a> From<Cow<'a, str>> for AttributeValue<'a> {
+  fn from(value: Cow<'a, str>) -> Self {
+    match value {
+      Cow::Borrowed(value) => AttributeValue::Ref(value),
+      Cow::Owned(value) => AttributeValue::String(value),
+    }
+  }
+}
+
 impl AttributeValue<'_> {
   pub fn as_str(&self) -> &str {
     match self {
@@ -151,6 +398,13 @@ impl AttributeValue<'_> {
       AttributeValue::String(value) => value.as_str(),
     }
   }
+
+  pub fn into_owned(self) -> AttributeValue<'static> {
+    match self {
+      AttributeValue::Ref(value) => AttributeValue::String(value.to_string()),
+      AttributeValue::String(value) => AttributeValue::String(value),
+    }
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -160,8 +414,25 @@ pub struct Attribute<'a> {
   pub value: AttributeValue<'a>,
 }
 
+impl Attribute<'_> {
+  pub fn into_owned(self) -> Attribute<'static> {
+    Attribute {
+      key: self.key,
+      value: self.value.into_owned(),
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct IncludeElement<'a> {
   #[serde(borrow)]
   pub inner: AST<'a>,
 }
+
+impl IncludeElement<'_> {
+  pub fn into_owned(self) -> IncludeElement<'static> {
+    IncludeElement {
+      inner: self.inner.into_owned(),
+    }
+  }
+}
@@ -0,0 +1,98 @@
+use crate::ast::*;
+use crate::options::Diagnostics;
+use crate::Result;
+use pest::error::{ErrorVariant, InputLocation};
+use serde::Serialize;
+use std::io;
+
+/// A problem found in a document (an `Element::Error`)
+/// with the file and position it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+  pub file: Option<String>,
+  pub start_line: usize,
+  pub start_col: usize,
+  pub end_line: usize,
+  pub end_col: usize,
+  pub message: String,
+}
+
+/// Collects all error elements of a document and
+/// the documents it includes
+pub fn collect(ast: &AST) -> Vec<Diagnostic> {
+  let mut diagnostics = Vec::new();
+  collect_elements(&ast.elements, ast.get_attribute("source"), &mut diagnostics);
+
+  diagnostics
+}
+
+fn collect_elements(elements: &[ElementSpan], file: Option<&str>, out: &mut Vec<Diagnostic>) {
+  for element in elements.iter() {
+    let file = element.source.as_deref().or(file);
+
+    match &element.element {
+      Element::Error(message) => out.push(Diagnostic {
+        file: file.map(|file| file.to_string()),
+        start_line: element.start_line,
+        start_col: element.start_col,
+        end_line: element.end_line,
+        end_col: element.end_col,
+        message: message.to_string(),
+      }),
+      Element::IncludeElement(include) => {
+        let file = include.inner.get_attribute("source").or(file);
+        collect_elements(&include.inner.elements, file, out);
+      }
+      _ => (),
+    }
+
+    collect_elements(&element.children, file, out);
+  }
+}
+
+/// Writes the diagnostics in the given format
+pub fn write<T: io::Write>(diagnostics: &[Diagnostic], format: Diagnostics, mut out: T) -> Result<()> {
+  match format {
+    Diagnostics::Json => {
+      serde_json::to_writer_pretty(&mut out, diagnostics)?;
+      writeln!(out)?;
+    }
+    Diagnostics::Human => {
+      for diagnostic in diagnostics.iter() {
+        writeln!(
+          out,
+          "{}:{}:{}: error: {}",
+          diagnostic.file.as_deref().unwrap_or("<stdin>"),
+          diagnostic.start_line,
+          diagnostic.start_col,
+          diagnostic.message
+        )?;
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Gets the offset (relative to the parsed input) and
+/// a description of a pest error
+pub fn describe_parse_error<R: std::fmt::Debug>(error: &pest::error::Error<R>) -> (usize, String) {
+  let offset = match error.location {
+    InputLocation::Pos(position) => position,
+    InputLocation::Span((start, _)) => start,
+  };
+  let message = match &error.variant {
+    ErrorVariant::ParsingError {
+      positives,
+      negatives,
+    } => match (positives.is_empty(), negatives.is_empty()) {
+      (false, true) => format!("expected {:?}", positives),
+      (true, false) => format!("unexpected {:?}", negatives),
+      (false, false) => format!("unexpected {:?}, expected {:?}", negatives, positives),
+      (true, true) => "unknown parsing error".to_string(),
+    },
+    ErrorVariant::CustomError { message } => message.to_string(),
+  };
+
+  (offset, message)
+}
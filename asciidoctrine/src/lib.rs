@@ -9,20 +9,30 @@ extern crate log;
 
 mod ast;
 pub use ast::*;
+pub mod diagnostics;
 pub mod options;
+pub mod query;
+pub mod subs;
 pub mod util;
+pub mod visit;
 pub mod reader;
 pub use reader::asciidoc::AsciidocReader;
 pub use reader::json::JsonReader;
+pub use reader::markdown::MarkdownReader;
 mod writer;
 pub use writer::html::HtmlWriter;
 pub use writer::docx::DocxWriter;
+pub use writer::docbook::DocbookWriter;
+pub use writer::pdf::PdfWriter;
 pub use writer::json::JsonWriter;
+pub use writer::asciidoc::AsciidocWriter;
 
 #[derive(Error, Debug)]
 pub enum AsciidoctrineError {
   #[error("could not parse input")]
   Parse(#[from] pest::error::Error<reader::asciidoc::Rule>),
+  #[error("could not parse markdown input")]
+  MarkdownParse(#[from] pest::error::Error<reader::markdown::Rule>),
   #[error(transparent)]
   Json(#[from] serde_json::Error),
   #[error(transparent)]
@@ -35,10 +45,18 @@ pub enum AsciidoctrineError {
   Utf8(#[from] std::str::Utf8Error),
   #[error(transparent)]
   Docx(#[from] docx_rs::DocxError),
+  #[error("could not read the reference docx")]
+  ReferenceDoc(#[from] docx_rs::ReaderError),
+  #[error(transparent)]
+  Zip(#[from] zip::result::ZipError),
   #[error("Child process stdin has not been captured!")]
   Childprocess,
+  #[error("invalid selector: {0}")]
+  Selector(String),
   #[error("malformed ast structure")]
   MalformedAst,
+  #[error("path is not valid unicode: {0:?}")]
+  Path(std::path::PathBuf),
 }
 
 type Result<T> = std::result::Result<T, AsciidoctrineError>;
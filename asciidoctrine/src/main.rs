@@ -1,20 +1,53 @@
 extern crate asciidoctrine;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use asciidoctrine::*;
+use clap::{Parser, Subcommand};
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+// The command line of asciidoctrine. Lisa only takes the options,
+// it has no commands.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+  #[clap(subcommand)]
+  command: Option<Command>,
+  #[clap(flatten)]
+  opts: options::Opts,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+  /// Writes the elements matching a selector (e.g.
+  /// `listing[source][lang=rust]` or `section > paragraph`)
+  /// as json. Sections are always nested.
+  Query {
+    selector: String,
+    #[clap(name = "FILE")]
+    input: Option<PathBuf>,
+  },
+}
 
 fn main() -> Result<()> {
-  let opts = options::from_args();
+  let Cli { command, mut opts } = Cli::parse();
+  let input = match &command {
+    Some(Command::Query { input, .. }) => {
+      opts.sections = true;
+      input.clone()
+    }
+    None => opts.input.clone(),
+  };
 
   let reader: Box<dyn Reader> = match opts.readerfmt {
     options::Reader::Asciidoc => Box::new(AsciidocReader::new()),
     options::Reader::Json => Box::new(JsonReader::new()),
+    options::Reader::Markdown => Box::new(MarkdownReader::new()),
   };
 
   // read the input
-  let input = match &opts.input {
+  let input = match &input {
     Some(input) => fs::read_to_string(input).context("Could not read in file")?,
     None => {
       let mut input = String::new();
@@ -37,9 +70,41 @@ fn main() -> Result<()> {
   // * Die zweiten sind (lua)-Scripte, die den AST als Struktur übergeben bekommen und wieder
   //   einen AST zurückgeben.
 
-  // TODO Das Ausgabeformat festlegen
-  //println!("{:#?}", opts);
-  //println!("\n\n{:#?}", ast);
+  if let Some(format) = opts.diagnostics {
+    diagnostics::write(&diagnostics::collect(&ast), format, io::stderr())?;
+  }
+
+  let output: Box<dyn Write> = match &opts.output {
+    Some(output) => Box::new(fs::File::create(output).context("Could not open output file")?),
+    None => Box::new(io::stdout()),
+  };
+
+  if let Some(Command::Query { selector, .. }) = &command {
+    let selector = query::Selector::parse(selector)?;
+    let matches: Vec<_> = query::select(&ast, &selector).collect();
+    let mut output = output;
+    serde_json::to_writer_pretty(&mut output, &matches)?;
+    writeln!(output)?;
+    return Ok(());
+  }
+
+  match opts.writerfmt {
+    options::Writer::Html5 => HtmlWriter::new().write(ast, &opts, output)?,
+    options::Writer::Json => JsonWriter::new().write(ast, &opts, output)?,
+    options::Writer::Docbook => DocbookWriter::new().write(ast, &opts, output)?,
+    options::Writer::Asciidoc => AsciidocWriter::new().write(ast, &opts, output)?,
+    options::Writer::Pdf => PdfWriter::new().write(ast, &opts, output)?,
+    options::Writer::Docx => match &opts.output {
+      Some(output) => {
+        DocxWriter::new().write(
+          ast,
+          &opts,
+          fs::File::create(output).context("Could not open output file")?,
+        )?;
+      }
+      None => bail!("docx cant only be written to file not to stdout"),
+    },
+  };
 
   Ok(())
 }
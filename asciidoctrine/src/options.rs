@@ -1,21 +1,20 @@
 use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
 
-/// Parse a single key-value pair
+/// Parse a single key-value pair. A key without a value
+/// (like `-a name`) gets an empty value.
 fn parse_key_val<T, U>(s: &str) -> Result<(T, U), String>
 where
   T: std::str::FromStr,
   U: std::str::FromStr,
 {
-  let pos = s
-    .find('=')
-    .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{}`", s))?;
-  let key = s[..pos]
+  let (key, value) = s.split_once('=').unwrap_or((s, ""));
+  let key = key
     .parse()
-    .or_else(|_| Err(format!("couldn't parse key in `{}`", s)))?;
-  let value = s[pos + 1..]
+    .map_err(|_| format!("couldn't parse key in `{}`", s))?;
+  let value = value
     .parse()
-    .or_else(|_| Err(format!("couldn't parse value in `{}`", s)))?;
+    .map_err(|_| format!("couldn't parse value in `{}`", s))?;
   Ok((key, value))
 }
 
@@ -23,6 +22,7 @@ where
 pub enum Reader {
   Asciidoc,
   Json,
+  Markdown,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -38,6 +38,12 @@ pub enum Writer {
   Asciidoc,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub enum Diagnostics {
+  Json,
+  Human,
+}
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Opts {
@@ -55,11 +61,33 @@ pub struct Opts {
   pub stylesheet: Option<PathBuf>,
   #[clap(short = 'a', long = "attribute")]
   #[clap(value_parser = parse_key_val::<String, String>, number_of_values = 1)]
-  defines: Vec<(String, String)>,
+  pub defines: Vec<(String, String)>,
   #[clap(name = "FILE")]
   pub input: Option<PathBuf>,
   #[clap(short = 'o')]
   pub output: Option<PathBuf>,
+  /// Nests the content following a title in a section element
+  #[clap(long)]
+  pub sections: bool,
+  /// Lists all problems found in the document on stderr
+  #[clap(long)]
+  #[clap(value_enum)]
+  pub diagnostics: Option<Diagnostics>,
+  /// Writes the content of included documents instead of the
+  /// include directives (asciidoc writer)
+  #[clap(long)]
+  pub inline_includes: bool,
+  /// Replaces the attribute references with their values
+  /// (asciidoc writer)
+  #[clap(long)]
+  pub resolve_attributes: bool,
+  /// A json file with the sizes and colors of the layout
+  /// (pdf writer)
+  #[clap(long)]
+  pub theme: Option<PathBuf>,
+  /// A docx file whose styles are used (docx writer)
+  #[clap(long)]
+  pub reference_doc: Option<PathBuf>,
 }
 
 pub fn from_args() -> Opts {
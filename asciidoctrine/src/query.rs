@@ -0,0 +1,565 @@
+use crate::ast::*;
+use crate::visit::{self, Visit, Visitor};
+use crate::{AsciidoctrineError, Result};
+use serde::Serialize;
+
+/// Selects elements like a css selector (e.g. `listing[source][lang=rust]`
+/// or `section > paragraph`). A selector is a chain of element kinds
+/// (or `*` for any element) with conditions:
+///
+/// * `[name]` a positional attribute with the value `name` or an
+///   attribute with the key `name`
+/// * `[key=value]` an attribute with the value. `1`, `2` and so on
+///   are the positional attributes, `level` is the level of a title,
+///   section or list item and `lang` the language of a source listing.
+/// * `.role` an element with the role
+/// * `#id` an element with the id
+///
+/// A space between two parts stands for any descendant, `>` for a
+/// child. Several selectors can be separated by commas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+  alternatives: Vec<Vec<(Combinator, Compound)>>,
+}
+
+/// How a part is connected to the part before
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+  Descendant,
+  Child,
+}
+
+/// A part of a selector (e.g. `listing[source]`)
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Compound {
+  kind: Option<String>,
+  conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+  Has(String),
+  Equals(String, String),
+  Role(String),
+  Id(String),
+}
+
+/// The names of the element kinds used in selectors
+const KINDS: [&str; 37] = [
+  "attribute",
+  "comment",
+  "paragraph",
+  "title",
+  "section",
+  "table",
+  "list",
+  "image",
+  "anchor",
+  "admonition",
+  "pass",
+  "listing",
+  "literal",
+  "sidebar",
+  "quote",
+  "verse",
+  "open",
+  "example",
+  "external",
+  "include",
+  "styled",
+  "text",
+  "xref",
+  "link",
+  "footnote",
+  "inline_image",
+  "keyboard",
+  "button",
+  "menu",
+  "passthrough",
+  "line_break",
+  "inline_macro",
+  "list_item",
+  "callout",
+  "table_row",
+  "table_cell",
+  "error",
+];
+
+/// Gets the name of the kind of an element
+pub fn kind_name(element: &Element) -> &'static str {
+  match element {
+    Element::Attribute(_) => "attribute",
+    Element::Comment => "comment",
+    Element::Paragraph => "paragraph",
+    Element::Title { .. } => "title",
+    Element::Section { .. } => "section",
+    Element::Table => "table",
+    Element::List(_) => "list",
+    Element::Image => "image",
+    Element::Anchor => "anchor",
+    Element::Admonition(_) => "admonition",
+    Element::TypedBlock { kind } => match kind {
+      BlockType::Comment => "comment",
+      BlockType::Passtrough => "pass",
+      BlockType::Listing => "listing",
+      BlockType::Literal => "literal",
+      BlockType::Sidebar => "sidebar",
+      BlockType::Quote => "quote",
+      BlockType::Verse => "verse",
+      BlockType::Open => "open",
+      BlockType::Example => "example",
+    },
+    Element::ExternalContent => "external",
+    Element::IncludeElement(_) => "include",
+    Element::Styled => "styled",
+    Element::Text => "text",
+    Element::XRef => "xref",
+    Element::Link => "link",
+    Element::Footnote => "footnote",
+    Element::InlineImage => "inline_image",
+    Element::Keyboard => "keyboard",
+    Element::Button => "button",
+    Element::Menu => "menu",
+    Element::Passthrough => "passthrough",
+    Element::LineBreak => "line_break",
+    Element::InlineMacro => "inline_macro",
+    Element::ListItem(_) => "list_item",
+    Element::Callout(_) => "callout",
+    Element::TableRow => "table_row",
+    Element::TableCell => "table_cell",
+    Element::Error(_) => "error",
+  }
+}
+
+impl Selector {
+  pub fn parse(input: &str) -> Result<Self> {
+    let mut parser = SelectorParser { input, position: 0 };
+    let mut alternatives = Vec::new();
+    let mut parts = Vec::new();
+    let mut combinator = Combinator::Descendant;
+
+    loop {
+      parser.skip_spaces();
+      match parser.peek() {
+        None | Some(',') => {
+          if parts.is_empty() || combinator == Combinator::Child {
+            return Err(parser.error("expected an element"));
+          }
+          alternatives.push(std::mem::take(&mut parts));
+          if parser.peek().is_none() {
+            break;
+          }
+          parser.position += 1;
+        }
+        Some('>') if !parts.is_empty() && combinator == Combinator::Descendant => {
+          combinator = Combinator::Child;
+          parser.position += 1;
+        }
+        _ => {
+          parts.push((combinator, parser.compound()?));
+          combinator = Combinator::Descendant;
+        }
+      }
+    }
+
+    Ok(Selector { alternatives })
+  }
+
+  /// Checks if an element with the given ancestors (the
+  /// parent is the last one) is selected
+  pub fn matches(&self, element: &ElementSpan, ancestors: &[&ElementSpan]) -> bool {
+    self
+      .alternatives
+      .iter()
+      .any(|parts| matches_parts(parts, element, ancestors))
+  }
+}
+
+fn matches_parts(parts: &[(Combinator, Compound)], element: &ElementSpan, ancestors: &[&ElementSpan]) -> bool {
+  let ((combinator, compound), before) = match parts.split_last() {
+    Some(part) => part,
+    None => return false,
+  };
+  if !compound.matches(element) {
+    return false;
+  }
+  if before.is_empty() {
+    return true;
+  }
+
+  match combinator {
+    Combinator::Child => match ancestors.split_last() {
+      Some((parent, ancestors)) => matches_parts(before, parent, ancestors),
+      None => false,
+    },
+    Combinator::Descendant => (0..ancestors.len())
+      .rev()
+      .any(|index| matches_parts(before, ancestors[index], &ancestors[..index])),
+  }
+}
+
+impl Compound {
+  fn matches(&self, element: &ElementSpan) -> bool {
+    if let Some(kind) = &self.kind {
+      if kind_name(&element.element) != kind {
+        return false;
+      }
+    }
+
+    self.conditions.iter().all(|condition| match condition {
+      Condition::Has(name) => {
+        positional_values(element).any(|value| value == name) || attribute_value(element, name).is_some()
+      }
+      Condition::Equals(key, value) => attribute_value(element, key).as_deref() == Some(value.as_str()),
+      Condition::Role(role) => roles(element).any(|other| other == role),
+      Condition::Id(id) => ids(element).any(|other| other == id),
+    })
+  }
+}
+
+const SHORTHAND_MARKS: [char; 3] = ['#', '.', '%'];
+
+/// The first positional attribute can hold an id, roles
+/// and options after the style (e.g. `source#id.role%option`)
+fn shorthand<'b>(element: &'b ElementSpan) -> Option<&'b str> {
+  let first = element.positional_attributes.first()?.as_str();
+  match first.contains(char::is_whitespace) {
+    true => None,
+    false => Some(first),
+  }
+}
+
+/// Gets the parts of the shorthand starting with the mark
+fn shorthand_parts<'b>(element: &'b ElementSpan, mark: char) -> impl Iterator<Item = &'b str> {
+  shorthand(element).into_iter().flat_map(move |shorthand| {
+    shorthand
+      .match_indices(SHORTHAND_MARKS)
+      .filter(move |(_, found)| found.starts_with(mark))
+      .map(move |(start, _)| {
+        let part = &shorthand[start + 1..];
+        &part[..part.find(SHORTHAND_MARKS).unwrap_or(part.len())]
+      })
+  })
+}
+
+/// Gets the positional attributes (without the shorthand parts)
+fn positional_values<'b>(element: &'b ElementSpan) -> impl Iterator<Item = &'b str> {
+  let style = shorthand(element).map(|shorthand| shorthand.split(SHORTHAND_MARKS).next().unwrap_or(""));
+  element
+    .positional_attributes
+    .iter()
+    .enumerate()
+    .map(move |(index, value)| match (index, style) {
+      (0, Some(style)) => style,
+      _ => value.as_str(),
+    })
+}
+
+fn roles<'b>(element: &'b ElementSpan) -> impl Iterator<Item = &'b str> {
+  let roles = element.get_attribute("role").unwrap_or("").split_whitespace();
+  roles.chain(shorthand_parts(element, '.'))
+}
+
+fn ids<'b>(element: &'b ElementSpan) -> impl Iterator<Item = &'b str> {
+  let ids = element.get_attribute("anchor").into_iter().chain(element.get_attribute("id"));
+  ids.chain(shorthand_parts(element, '#'))
+}
+
+/// Gets the value of a key used in a selector
+fn attribute_value(element: &ElementSpan, key: &str) -> Option<String> {
+  if let Ok(index) = key.parse::<usize>() {
+    return positional_values(element)
+      .nth(index.checked_sub(1)?)
+      .map(|value| value.to_string());
+  }
+
+  match (key, &element.element) {
+    ("level", Element::Title { level } | Element::Section { level } | Element::ListItem(level)) => {
+      Some(level.to_string())
+    }
+    ("lang" | "language", _) if element.get_attribute("language").is_none() => {
+      let mut values = positional_values(element);
+      match values.next() {
+        Some("source") => values.next().map(|value| value.to_string()),
+        _ => None,
+      }
+    }
+    ("lang", _) => element.get_attribute("language").map(|value| value.to_string()),
+    ("id", _) => ids(element).next().map(|value| value.to_string()),
+    _ => element.get_attribute(key).map(|value| value.to_string()),
+  }
+}
+
+struct SelectorParser<'s> {
+  input: &'s str,
+  position: usize,
+}
+
+impl SelectorParser<'_> {
+  fn peek(&self) -> Option<char> {
+    self.input[self.position..].chars().next()
+  }
+
+  fn skip_spaces(&mut self) {
+    let rest = &self.input[self.position..];
+    self.position += rest.len() - rest.trim_start().len();
+  }
+
+  fn error(&self, message: &str) -> AsciidoctrineError {
+    AsciidoctrineError::Selector(format!(
+      "{} at column {} of `{}`",
+      message,
+      self.input[..self.position].chars().count() + 1,
+      self.input
+    ))
+  }
+
+  /// Reads a name of a kind, a role or an id
+  fn name(&mut self) -> Result<String> {
+    let rest = &self.input[self.position..];
+    let length = rest
+      .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+      .unwrap_or(rest.len());
+    if length == 0 {
+      return Err(self.error("expected a name"));
+    }
+    self.position += length;
+
+    Ok(rest[..length].to_string())
+  }
+
+  /// Reads the content of `[key]` or `[key=value]`. The
+  /// value can be quoted.
+  fn condition(&mut self) -> Result<Condition> {
+    self.position += 1;
+    self.skip_spaces();
+    let key = self.name()?;
+    self.skip_spaces();
+
+    let condition = match self.peek() {
+      Some('=') => {
+        self.position += 1;
+        self.skip_spaces();
+        let rest = &self.input[self.position..];
+        let value = match rest.chars().next() {
+          Some(quote @ ('"' | '\'')) => {
+            let length = rest[1..]
+              .find(quote)
+              .ok_or_else(|| self.error("unclosed quote"))?;
+            self.position += length + 2;
+            rest[1..length + 1].to_string()
+          }
+          _ => {
+            let length = rest.find(']').unwrap_or(rest.len());
+            self.position += length;
+            rest[..length].trim_end().to_string()
+          }
+        };
+        self.skip_spaces();
+        Condition::Equals(key, value)
+      }
+      _ => Condition::Has(key),
+    };
+    if self.peek() != Some(']') {
+      return Err(self.error("expected `]`"));
+    }
+    self.position += 1;
+
+    Ok(condition)
+  }
+
+  fn compound(&mut self) -> Result<Compound> {
+    let mut compound = Compound::default();
+    match self.peek() {
+      Some('*') => self.position += 1,
+      Some(c) if c.is_alphanumeric() => {
+        let start = self.position;
+        let kind = self.name()?;
+        if !KINDS.contains(&kind.as_str()) {
+          self.position = start;
+          return Err(self.error(&format!("unknown element kind `{}`", kind)));
+        }
+        compound.kind = Some(kind);
+      }
+      Some('#' | '.' | '[') => (),
+      _ => return Err(self.error("expected an element")),
+    }
+
+    loop {
+      let condition = match self.peek() {
+        Some('#') => {
+          self.position += 1;
+          Condition::Id(self.name()?)
+        }
+        Some('.') => {
+          self.position += 1;
+          Condition::Role(self.name()?)
+        }
+        Some('[') => self.condition()?,
+        _ => break,
+      };
+      compound.conditions.push(condition);
+    }
+
+    Ok(compound)
+  }
+}
+
+/// An element found by a selector
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Match<'v> {
+  /// The indices of the element and its ancestors. The elements
+  /// of an included document come before the children of the
+  /// include.
+  pub path: Vec<usize>,
+  pub element: &'v ElementSpan<'v>,
+}
+
+/// Finds the elements of the document matching the selector
+pub fn select<'v>(ast: &'v AST<'v>, selector: &Selector) -> impl Iterator<Item = Match<'v>> {
+  let mut search = Search {
+    selector,
+    ancestors: Vec::new(),
+    path: Vec::new(),
+    next_index: vec![0],
+    matches: Vec::new(),
+  };
+  let _ = visit::walk_ast(&mut search, ast);
+
+  search.matches.into_iter()
+}
+
+struct Search<'s, 'v> {
+  selector: &'s Selector,
+  ancestors: Vec<&'v ElementSpan<'v>>,
+  path: Vec<usize>,
+  next_index: Vec<usize>,
+  matches: Vec<Match<'v>>,
+}
+
+impl<'v> Visitor<'v> for Search<'_, 'v> {
+  fn enter(&mut self, element: &'v ElementSpan<'v>) -> Visit {
+    if let Some(next_index) = self.next_index.last_mut() {
+      self.path.push(*next_index);
+      *next_index += 1;
+    }
+    if self.selector.matches(element, &self.ancestors) {
+      self.matches.push(Match {
+        path: self.path.clone(),
+        element,
+      });
+    }
+
+    self.ancestors.push(element);
+    self.next_index.push(0);
+    Visit::Continue
+  }
+
+  fn leave(&mut self, _element: &'v ElementSpan<'v>) {
+    self.ancestors.pop();
+    self.next_index.pop();
+    self.path.pop();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::options::Opts;
+  use crate::reader::asciidoc::AsciidocReader;
+  use crate::util::{Cache, Env};
+  use crate::Reader;
+  use clap::Parser;
+  use pretty_assertions::assert_eq;
+
+  const INPUT: &str = r#"= Document
+
+[[intro]]
+== Introduction
+
+A paragraph.
+
+[source,rust,save]
+----
+fn main() {}
+----
+
+[source#setup.important,bash]
+----
+echo setup
+----
+
+== Usage
+
+* A list item
+
+----
+plain
+----
+"#;
+
+  fn select_paths(input: &str, selector: &str) -> Vec<Vec<usize>> {
+    let reader = AsciidocReader::new();
+    let opts = Opts::parse_from(vec!["", "--sections"]);
+    let mut env = Env::Cache(Cache::new());
+    let ast = reader.parse(input, &opts, &mut env).unwrap();
+    let selector = Selector::parse(selector).unwrap();
+
+    select(&ast, &selector).map(|found| found.path).collect()
+  }
+
+  #[test]
+  fn parse_selectors() {
+    assert_eq!(
+      Selector::parse("section > listing[source][lang = \"rust\"].big, #id").unwrap(),
+      Selector {
+        alternatives: vec![
+          vec![
+            (
+              Combinator::Descendant,
+              Compound {
+                kind: Some("section".to_string()),
+                conditions: vec![],
+              }
+            ),
+            (
+              Combinator::Child,
+              Compound {
+                kind: Some("listing".to_string()),
+                conditions: vec![
+                  Condition::Has("source".to_string()),
+                  Condition::Equals("lang".to_string(), "rust".to_string()),
+                  Condition::Role("big".to_string()),
+                ],
+              }
+            ),
+          ],
+          vec![(
+            Combinator::Descendant,
+            Compound {
+              kind: None,
+              conditions: vec![Condition::Id("id".to_string())],
+            }
+          )],
+        ],
+      }
+    );
+
+    assert!(Selector::parse("").is_err());
+    assert!(Selector::parse("section >").is_err());
+    assert!(Selector::parse("listing[source").is_err());
+    assert!(Selector::parse("unknown").is_err());
+  }
+
+  #[test]
+  fn select_elements() {
+    assert_eq!(select_paths(INPUT, "listing[source][lang=rust]"), vec![vec![1, 2]]);
+    assert_eq!(select_paths(INPUT, "listing[source]"), vec![vec![1, 2], vec![1, 3]]);
+    assert_eq!(select_paths(INPUT, "listing"), vec![vec![1, 2], vec![1, 3], vec![2, 2]]);
+    assert_eq!(select_paths(INPUT, "listing#setup.important[2=bash]"), vec![vec![1, 3]]);
+    assert_eq!(select_paths(INPUT, "title[level=2]#intro"), vec![vec![1, 0]]);
+    assert_eq!(select_paths(INPUT, "section > paragraph"), vec![vec![1, 1]]);
+    assert_eq!(select_paths(INPUT, "section paragraph"), vec![vec![1, 1], vec![2, 1, 0, 0]]);
+    assert_eq!(select_paths(INPUT, "list_item text, title[level=1]"), vec![vec![0], vec![2, 1, 0, 0, 0]]);
+  }
+}
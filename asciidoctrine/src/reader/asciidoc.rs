@@ -1,10 +1,16 @@
 pub use crate::ast::*;
+use crate::diagnostics::describe_parse_error;
 use crate::options::Opts;
-use crate::util::{Env, Environment};
+use crate::util::{split_callouts, Env, Environment};
+use crate::reader::attributes::DocumentAttributes;
+use crate::reader::preprocessor::preprocess;
+use crate::subs::{self, Substitution};
 use crate::reader::*;
 use crate::Result;
 use pest::iterators::Pair;
 use pest::Parser;
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
 
 pub struct AsciidocReader {}
 
@@ -16,10 +22,17 @@ impl AsciidocReader {
 
 impl crate::Reader for AsciidocReader {
   fn parse<'a>(&self, input: &'a str, args: &Opts, env: &mut Env) -> Result<AST<'a>> {
-    let ast = AsciidocParser::parse(Rule::asciidoc, input)?;
+    let mut ctx = ParserContext {
+      env,
+      include_stack: Vec::new(),
+      attributes: DocumentAttributes::from_opts(args),
+      subs: subs::NORMAL.to_vec(),
+    };
 
     let mut attributes = Vec::new();
     if let Some(path) = &args.input {
+      ctx.include_stack.push(normalize_path(path));
+
       if let Some(path) = path.to_str() {
         attributes.push(Attribute {
           key: "source".to_string(),
@@ -28,34 +41,156 @@ impl crate::Reader for AsciidocReader {
       }
     }
 
-    let mut elements = Vec::new();
-
-    for element in ast {
-      if let Some(element) = process_element(element, env) {
-        elements.push(element);
-      }
+    let mut elements = parse_source(input, &mut ctx)?;
+    resolve_attributes(&mut elements, &mut ctx);
+    link_callouts(&mut elements, &mut 0);
+    number_footnotes(&mut elements, &mut Vec::new());
+    if args.sections {
+      elements = nest_sections(elements, &input.into());
     }
 
     Ok(AST {
-      content: input,
+      content: input.into(),
       elements,
       attributes,
     })
   }
 }
 
+/// The state we need to carry around while processing
+/// a document and all the documents it includes
+struct ParserContext<'e> {
+  env: &'e mut Env,
+  /// The files we are currently processing. The last one is
+  /// the innermost included file. Relative include paths are
+  /// resolved against it and it is used to detect include cycles.
+  include_stack: Vec<PathBuf>,
+  attributes: DocumentAttributes,
+  /// The substitutions of the block we are in
+  subs: Vec<Substitution>,
+}
+
+/// Parses a whole document (or an included file) after
+/// applying the conditional preprocessor directives. The
+/// spans of the elements point into the original input.
+/// The preprocessed text only lives while parsing, so the
+/// elements read from it own their content.
+fn parse_source<'a>(input: &'a str, ctx: &mut ParserContext) -> Result<Vec<ElementSpan<'a>>> {
+  match preprocess(input, &ctx.attributes) {
+    Some(preprocessed) => {
+      let mut elements = parse_elements(&preprocessed.content, ctx)?;
+      preprocessed.lines.remap(&mut elements);
+      Ok(elements.into_iter().map(ElementSpan::into_owned).collect())
+    }
+    None => parse_elements(input, ctx),
+  }
+}
+
+fn parse_elements<'a>(input: &'a str, ctx: &mut ParserContext) -> Result<Vec<ElementSpan<'a>>> {
+  let ast = AsciidocParser::parse(Rule::asciidoc, input)?;
+
+  let mut elements = Vec::new();
+
+  for element in ast {
+    if let Some(element) = process_element(element, ctx) {
+      elements.push(element);
+    }
+  }
+
+  Ok(elements)
+}
+
+/// Parses the content of a block. The spans of the elements are
+/// moved to the position of the content in the document. If the
+/// content can't be parsed we only get an error element.
+fn parse_inner<'a>(
+  rule: Rule,
+  inner: &Pair<'a, asciidoc::Rule>,
+  base: &ElementSpan<'a>,
+  ctx: &mut ParserContext,
+) -> Vec<ElementSpan<'a>> {
+  parse_span(rule, inner.as_span(), base, ctx)
+}
+
+/// Parses a part of the input which isn't a pair
+/// of its own (e.g. the text of a csv cell)
+fn parse_span<'a>(
+  rule: Rule,
+  inner: pest::Span<'a>,
+  base: &ElementSpan<'a>,
+  ctx: &mut ParserContext,
+) -> Vec<ElementSpan<'a>> {
+  let ast = match AsciidocParser::parse(rule, inner.as_str()) {
+    Ok(ast) => ast,
+    Err(e) => return vec![inner_error(&inner, base, &e)],
+  };
+
+  let mut elements = Vec::new();
+  for element in ast {
+    // Non silent rules (like `table_inner`) only wrap the elements
+    let subelements = if element.as_rule() == rule {
+      element.into_inner().collect()
+    } else {
+      vec![element]
+    };
+    for subelement in subelements {
+      if let Some(e) = process_element(subelement, ctx) {
+        elements.push(e);
+      }
+    }
+  }
+
+  let (line, col) = inner.start_pos().line_col();
+  move_spans(&mut elements, inner.start(), line, col);
+
+  elements
+}
+
+/// Creates the error element for content of a block
+/// which couldn't be parsed
+fn inner_error<'a>(
+  inner: &pest::Span<'a>,
+  base: &ElementSpan<'a>,
+  error: &pest::error::Error<Rule>,
+) -> ElementSpan<'a> {
+  let (offset, message) = describe_parse_error(error);
+  base.error_at(
+    inner.start() - base.start + offset,
+    format!("couldn't parse the content of the block ({})", message),
+  )
+}
+
+/// Moves the spans of elements parsed from a part of the document
+/// (starting at `start`, `line`, `col`) to their real position
+pub(crate) fn move_spans(elements: &mut [ElementSpan], start: usize, line: usize, col: usize) {
+  for element in elements.iter_mut() {
+    if element.start_line == 1 {
+      element.start_col += col - 1;
+    }
+    if element.end_line == 1 {
+      element.end_col += col - 1;
+    }
+    element.start_line += line - 1;
+    element.end_line += line - 1;
+    element.start += start;
+    element.end += start;
+
+    move_spans(&mut element.children, start, line, col);
+  }
+}
+
 #[derive(Parser, Debug, Copy, Clone)]
 #[grammar = "reader/asciidoc.pest"]
 pub struct AsciidocParser;
 
 fn process_element<'a>(
   element: Pair<'a, asciidoc::Rule>,
-  env: &mut Env,
+  ctx: &mut ParserContext,
 ) -> Option<ElementSpan<'a>> {
   let mut base = set_span(&element);
 
   let element = match element.as_rule() {
-    Rule::delimited_block => Some(process_delimited_block(element, env)),
+    Rule::delimited_block => Some(process_delimited_block(element, ctx)),
     Rule::header => {
       for subelement in element.into_inner() {
         match subelement.as_rule() {
@@ -64,11 +199,12 @@ fn process_element<'a>(
               base = e;
             }
           }
-          // We just take the attributes at the beginning
-          // of the element.
-          _ => {
-            break;
-          } // TODO improve matching
+          Rule::attribute_entry => {
+            let entry = set_span(&subelement);
+            base.children.push(process_attribute_entry(subelement, entry));
+          }
+          // TODO author and revision info
+          _ => (),
         }
       }
       // TODO
@@ -96,12 +232,66 @@ fn process_element<'a>(
       Some(base)
     }
     Rule::paragraph => Some(process_paragraph(element)),
-    Rule::table_cell => {
-      Some(process_table_cell(&element, base, env, &DEFAULT_CELL_FORMAT))
+    Rule::quoted_paragraph => {
+      for subelement in element.into_inner() {
+        match subelement.as_rule() {
+          Rule::anchor => {
+            base = process_anchor(subelement, base);
+          }
+          Rule::attribute_list => {
+            base = process_attribute_list(subelement, base);
+          }
+          Rule::blocktitle => {
+            base = process_blocktitle(subelement, base);
+          }
+          Rule::paragraph => {
+            if let Some(AttributeValue::Ref("verse")) = base.positional_attributes.first() {
+              base.element = Element::TypedBlock {
+                kind: BlockType::Verse,
+              };
+              base.attributes.push(Attribute {
+                key: "content".to_string(),
+                value: AttributeValue::Ref(subelement.as_str()),
+              });
+            } else {
+              base.element = Element::TypedBlock {
+                kind: BlockType::Quote,
+              };
+              base.children.push(process_paragraph(subelement));
+            }
+          }
+          _ => (),
+        }
+      }
+      Some(process_attribution(base))
+    }
+    Rule::markdown_quote => Some(process_markdown_quote(element, base, ctx)),
+    Rule::admonition => {
+      for subelement in element.into_inner() {
+        match subelement.as_rule() {
+          Rule::anchor => {
+            base = process_anchor(subelement, base);
+          }
+          Rule::blocktitle => {
+            base = process_blocktitle(subelement, base);
+          }
+          Rule::admonition_label | Rule::admonition_prefix => {
+            let label = subelement.as_str().trim_end().trim_end_matches(':');
+            if let Some(kind) = AdmonitionType::from_label(label) {
+              base.element = Element::Admonition(kind);
+            }
+          }
+          Rule::paragraph => {
+            base.children.push(process_paragraph(subelement));
+          }
+          _ => (),
+        }
+      }
+      Some(base)
     }
     Rule::list => {
       for subelement in element.into_inner() {
-        if let Some(e) = process_element(subelement, env) {
+        if let Some(e) = process_element(subelement, ctx) {
           base = e;
         }
       }
@@ -114,7 +304,7 @@ fn process_element<'a>(
       base.element = Element::List(ListType::Bullet);
 
       for subelement in element.into_inner() {
-        if let Some(e) = process_element(subelement, env) {
+        if let Some(e) = process_element(subelement, ctx) {
           base.children.push(e);
         }
       }
@@ -129,11 +319,12 @@ fn process_element<'a>(
           }
           Rule::list_element => {
             for subelement in subelement.into_inner() {
-              if let Some(e) = process_element(subelement, env) {
+              if let Some(e) = process_element(subelement, ctx) {
                 base.children.push(e);
               }
             }
           }
+          Rule::EOI => (),
           _ => {
             base.children.push(set_span(&subelement));
           }
@@ -146,7 +337,7 @@ fn process_element<'a>(
       base.element = Element::List(ListType::Number);
 
       for subelement in element.into_inner() {
-        if let Some(e) = process_element(subelement, env) {
+        if let Some(e) = process_element(subelement, ctx) {
           base.children.push(e);
         }
       }
@@ -161,11 +352,111 @@ fn process_element<'a>(
           }
           Rule::list_element => {
             for subelement in subelement.into_inner() {
-              if let Some(e) = process_element(subelement, env) {
+              if let Some(e) = process_element(subelement, ctx) {
+                base.children.push(e);
+              }
+            }
+          }
+          Rule::EOI => (),
+          _ => {
+            base.children.push(set_span(&subelement));
+          }
+        }
+      }
+
+      Some(base)
+    }
+    Rule::labeled_list => {
+      base.element = Element::List(ListType::Labeled);
+
+      for subelement in element.into_inner() {
+        if let Some(e) = process_element(subelement, ctx) {
+          base.children.push(e);
+        }
+      }
+
+      Some(base)
+    }
+    Rule::labeled_list_element => {
+      for subelement in element.into_inner() {
+        match subelement.as_rule() {
+          Rule::label_bullet => {
+            for subelement in subelement.into_inner() {
+              match subelement.as_rule() {
+                Rule::label => base.attributes.push(Attribute {
+                  key: "term".to_string(),
+                  value: AttributeValue::Ref(subelement.as_str().trim()),
+                }),
+                Rule::label_delimiter => {
+                  // `::` to `::::` and then `;;`
+                  let level = match subelement.as_str() {
+                    ";;" => 4,
+                    delimiter => delimiter.len() - 1,
+                  };
+                  base.element = Element::ListItem(level as u32);
+                }
+                _ => (),
+              }
+            }
+          }
+          Rule::list_element => {
+            for subelement in subelement.into_inner() {
+              if let Some(e) = process_element(subelement, ctx) {
+                base.children.push(e);
+              }
+            }
+          }
+          Rule::bullet_list | Rule::numbered_list => {
+            if let Some(e) = process_element(subelement, ctx) {
+              base.children.push(e);
+            }
+          }
+          _ => (),
+        }
+      }
+
+      Some(base)
+    }
+    Rule::callout_list => {
+      base.element = Element::List(ListType::Callout);
+
+      let mut number = 0;
+      for subelement in element.into_inner() {
+        if let Some(mut e) = process_element(subelement, ctx) {
+          // `<.>` gets the next number
+          number = match e.get_attribute("callout") {
+            Some(".") | None => number + 1,
+            Some(value) => value.parse().unwrap_or(number + 1),
+          };
+          e.attributes.retain(|attribute| attribute.key != "callout");
+          e.attributes.push(Attribute {
+            key: "callout".to_string(),
+            value: AttributeValue::String(number.to_string()),
+          });
+          base.children.push(e);
+        }
+      }
+
+      Some(base)
+    }
+    Rule::callout_list_element => {
+      base.element = Element::ListItem(1);
+      for subelement in element.into_inner() {
+        match subelement.as_rule() {
+          Rule::callout_bullet => {
+            base.attributes.push(Attribute {
+              key: "callout".to_string(),
+              value: AttributeValue::Ref(subelement.as_str().trim_matches(&['<', '>'][..])),
+            });
+          }
+          Rule::list_element => {
+            for subelement in subelement.into_inner() {
+              if let Some(e) = process_element(subelement, ctx) {
                 base.children.push(e);
               }
             }
           }
+          Rule::EOI => (),
           _ => {
             base.children.push(set_span(&subelement));
           }
@@ -174,12 +465,15 @@ fn process_element<'a>(
 
       Some(base)
     }
-    Rule::image_block => Some(process_image(element, base, env)),
-    Rule::table_row => Some(process_table_row(element, base, env, &[DEFAULT_CELL_FORMAT])),
-    Rule::table_cell => Some(process_table_cell(&element, base, env, &DEFAULT_CELL_FORMAT)),
+    Rule::image_block => Some(process_image(element, base, ctx)),
+    Rule::include_macro => Some(process_include(element, base)),
+    Rule::attribute_entry_block => element
+      .into_inner()
+      .next()
+      .map(|entry| process_attribute_entry(entry, base)),
     Rule::block => {
       for subelement in element.into_inner() {
-        if let Some(e) = process_element(subelement, env) {
+        if let Some(e) = process_element(subelement, ctx) {
           base = e;
         }
       }
@@ -251,7 +545,11 @@ fn process_inline_attribute_list<'a>(
                 match subelement.as_rule() {
                   Rule::identifier => key = Some(subelement.as_str()),
                   Rule::attribute_value => {
-                    value = Some(subelement.into_inner().concat());
+                    // Only quoted values have an inner element
+                    value = Some(match subelement.clone().into_inner().next() {
+                      Some(inner) => inner.as_str().to_string(),
+                      None => subelement.as_str().to_string(),
+                    });
                   }
                   // TODO Fehler abfangen und anzeigen
                   _ => (),
@@ -310,7 +608,7 @@ fn process_blocktitle<'a>(
 
 fn process_delimited_block<'a>(
   element: Pair<'a, asciidoc::Rule>,
-  env: &mut Env,
+  ctx: &mut ParserContext,
 ) -> ElementSpan<'a> {
   let mut base = set_span(&element);
 
@@ -327,31 +625,85 @@ fn process_delimited_block<'a>(
       }
       Rule::delimited_table => {
         base.element = Element::Table;
-        base = process_inner_table(subelement, base, env);
+        base = process_inner_table(subelement, base, ctx);
       }
       Rule::delimited_comment => {
         base.element = Element::TypedBlock {
           kind: BlockType::Comment,
         };
-        base = process_delimited_inner(subelement, base, env);
+        base = process_delimited_inner(subelement, base, ctx);
       }
       Rule::delimited_source => {
         base.element = Element::TypedBlock {
           kind: BlockType::Listing,
         };
-        base = process_delimited_inner(subelement, base, env);
+        base = process_delimited_inner(subelement, base, ctx);
       }
       Rule::delimited_literal => {
         base.element = Element::TypedBlock {
           kind: BlockType::Listing,
         };
-        base = process_delimited_inner(subelement, base, env);
+        base = process_delimited_inner(subelement, base, ctx);
+      }
+      Rule::delimited_fenced => {
+        base.element = Element::TypedBlock {
+          kind: BlockType::Listing,
+        };
+        // A fenced block is a source block. The style and the language
+        // are put in front of the other positional attributes (`[save]`
+        // before ```bash is read as `[source,bash,save]`).
+        if base.positional_attributes.first().map(|style| style.as_str()) != Some("source") {
+          let language = subelement.clone().into_inner().find(|e| e.as_rule() == Rule::fenced_language);
+          if let Some(language) = language {
+            base.positional_attributes.insert(0, AttributeValue::Ref(language.as_str()));
+          }
+          base.positional_attributes.insert(0, AttributeValue::Ref("source"));
+        }
+        base = process_delimited_inner(subelement, base, ctx);
       }
       Rule::delimited_example => {
         base.element = Element::TypedBlock {
           kind: BlockType::Example,
         };
-        base = process_delimited_inner(subelement, base, env);
+        base = process_delimited_inner(subelement, base, ctx);
+
+        // An example block with a label (e.g. `[NOTE]`) is an admonition
+        let label = base.positional_attributes.first().map(|label| label.as_str());
+        if let Some(kind) = label.and_then(AdmonitionType::from_label) {
+          base.element = Element::Admonition(kind);
+        }
+      }
+      Rule::delimited_sidebar => {
+        base.element = Element::TypedBlock {
+          kind: BlockType::Sidebar,
+        };
+        base = process_delimited_inner(subelement, base, ctx);
+      }
+      Rule::delimited_quote => {
+        let kind = match base.positional_attributes.first().map(|style| style.as_str()) {
+          Some("verse") => BlockType::Verse,
+          _ => BlockType::Quote,
+        };
+        base.element = Element::TypedBlock { kind };
+        base = process_attribution(base);
+        base = process_delimited_inner(subelement, base, ctx);
+      }
+      Rule::delimited_open => {
+        base.element = Element::TypedBlock {
+          kind: BlockType::Open,
+        };
+        base = process_delimited_inner(subelement, base, ctx);
+
+        let label = base.positional_attributes.first().map(|label| label.as_str());
+        if let Some(kind) = label.and_then(AdmonitionType::from_label) {
+          base.element = Element::Admonition(kind);
+        }
+      }
+      Rule::delimited_passthrough => {
+        base.element = Element::TypedBlock {
+          kind: BlockType::Passtrough,
+        };
+        base = process_delimited_inner(subelement, base, ctx);
       }
       // We just take the attributes at the beginning
       // of the element.
@@ -364,22 +716,212 @@ fn process_delimited_block<'a>(
   base
 }
 
+/// Finds the callout markers at the end of the lines of a listing
+fn process_callouts<'a>(element: &Pair<'a, asciidoc::Rule>) -> Vec<ElementSpan<'a>> {
+  let (start_line, start_col) = element.as_span().start_pos().line_col();
+  let mut callouts = Vec::new();
+  let mut number = 0;
+  let mut line_start = 0;
+
+  for (index, line) in element.as_str().split('\n').enumerate() {
+    if let Some((code, markers)) = split_callouts(line) {
+      let markers_text = &line[code.len()..];
+      let content = markers_text.trim();
+      let offset = code.len() + (markers_text.len() - markers_text.trim_start().len());
+      let marker_start = line_start + offset;
+      let col = line[..offset].chars().count() + if index == 0 { start_col } else { 1 };
+
+      for marker in markers {
+        number = marker.unwrap_or(number + 1);
+        callouts.push(ElementSpan {
+          element: Element::Callout(number),
+          source: None,
+          content: content.into(),
+          children: Vec::new(),
+          attributes: vec![Attribute {
+            key: "line".to_string(),
+            value: AttributeValue::String(index.to_string()),
+          }],
+          positional_attributes: Vec::new(),
+          start: element.as_span().start() + marker_start,
+          end: element.as_span().start() + marker_start + content.len(),
+          start_line: start_line + index,
+          start_col: col,
+          end_line: start_line + index,
+          end_col: col + content.chars().count(),
+        });
+      }
+    }
+    line_start += line.len() + 1;
+  }
+
+  callouts
+}
+
+/// Gives the callouts of the listings ids and links the items of
+/// the callout lists to the callouts of the listing before them
+fn link_callouts(elements: &mut [ElementSpan], counter: &mut usize) {
+  let mut ids: Vec<(u32, String)> = Vec::new();
+
+  for element in elements.iter_mut() {
+    match &mut element.element {
+      Element::TypedBlock {
+        kind: BlockType::Listing,
+      } if !element.children.is_empty() => {
+        *counter += 1;
+        ids.clear();
+        for (index, callout) in element.children.iter_mut().enumerate() {
+          if let Element::Callout(number) = callout.element {
+            let id = format!("CO{}-{}", counter, index + 1);
+            ids.push((number, id.clone()));
+            callout.attributes.push(Attribute {
+              key: "id".to_string(),
+              value: AttributeValue::String(id),
+            });
+          }
+        }
+      }
+      Element::List(ListType::Callout) => {
+        for item in element.children.iter_mut() {
+          let number = item
+            .get_attribute("callout")
+            .and_then(|number| number.parse::<u32>().ok());
+          match ids.iter().find(|(callout, _)| Some(*callout) == number) {
+            Some((_, id)) => item.attributes.push(Attribute {
+              key: "target".to_string(),
+              value: AttributeValue::String(id.clone()),
+            }),
+            None => warn!(
+              "line {}: no callout <{}> in the listing before",
+              item.start_line,
+              number.unwrap_or_default()
+            ),
+          }
+        }
+      }
+      Element::IncludeElement(include) => link_callouts(&mut include.inner.elements, counter),
+      _ => link_callouts(&mut element.children, counter),
+    }
+  }
+}
+
+/// Takes the author and the source of a quote or verse
+/// from its positional attributes (`[quote, author, source]`)
+fn process_attribution(mut base: ElementSpan) -> ElementSpan {
+  let mut values = base.positional_attributes.iter().skip(1).map(|value| {
+    value.as_str().trim().trim_matches('"').to_string()
+  });
+  let attribution = values.next();
+  let citetitle = values.next();
+
+  for (key, value) in [("attribution", attribution), ("citetitle", citetitle)] {
+    if let Some(value) = value.filter(|value| !value.is_empty()) {
+      base.attributes.push(Attribute {
+        key: key.to_string(),
+        value: AttributeValue::String(value),
+      });
+    }
+  }
+
+  base
+}
+
+/// Builds a quote from a markdown blockquote. Following lines
+/// form a paragraph, empty lines (`>`) separate them.
+fn process_markdown_quote<'a>(
+  element: Pair<'a, asciidoc::Rule>,
+  mut base: ElementSpan<'a>,
+  ctx: &mut ParserContext,
+) -> ElementSpan<'a> {
+  base.element = Element::TypedBlock {
+    kind: BlockType::Quote,
+  };
+
+  let mut lines = Vec::new();
+  for subelement in element.into_inner() {
+    match subelement.as_rule() {
+      Rule::anchor => {
+        base = process_anchor(subelement, base);
+      }
+      Rule::blocktitle => {
+        base = process_blocktitle(subelement, base);
+      }
+      Rule::markdown_quote_line => {
+        lines.push(subelement.into_inner().next().map(|text| text.as_span()));
+      }
+      _ => (),
+    }
+  }
+
+  if let Some(Some(line)) = lines.last() {
+    if let Some(attribution) = line.as_str().strip_prefix("-- ") {
+      let mut values = attribution.splitn(2, ", ");
+      for key in ["attribution", "citetitle"] {
+        if let Some(value) = values.next().map(str::trim).filter(|value| !value.is_empty()) {
+          base.attributes.push(Attribute {
+            key: key.to_string(),
+            value: AttributeValue::Ref(value),
+          });
+        }
+      }
+      lines.pop();
+    }
+  }
+
+  for paragraph in lines.split(|line| line.is_none()) {
+    let lines: Vec<_> = paragraph.iter().flatten().collect();
+    let (first, last) = match (lines.first(), lines.last()) {
+      (Some(first), Some(last)) => (first, last),
+      _ => continue,
+    };
+    let mut paragraph = from_span(first.start_pos().span(&last.end_pos()), Element::Paragraph);
+
+    for (index, line) in lines.iter().enumerate() {
+      // The line break and the marks of the next line
+      // stand for a simple line break
+      if index > 0 {
+        let mut separator = from_span(lines[index - 1].end_pos().span(&line.start_pos()), Element::Text);
+        separator.attributes.push(Attribute {
+          key: "content".to_string(),
+          value: AttributeValue::Ref("\n"),
+        });
+        paragraph.children.push(separator);
+      }
+      // Only paragraphs are supported in the lines of the quote
+      for element in parse_span(Rule::table_cell_paragraphs, **line, &base, ctx) {
+        match element.element {
+          Element::Paragraph => paragraph.children.extend(element.children),
+          _ => paragraph.children.push(element),
+        }
+      }
+    }
+    base.children.push(paragraph);
+  }
+
+  base
+}
+
 fn process_delimited_inner<'a>(
   element: Pair<'a, asciidoc::Rule>,
   mut base: ElementSpan<'a>,
-  env: &mut Env,
+  ctx: &mut ParserContext,
 ) -> ElementSpan<'a> {
   for element in element.into_inner() {
     match element.as_rule() {
       Rule::delimited_inner => {
-        if let Element::TypedBlock { kind: BlockType::Example } = base.element {
-          let ast = AsciidocParser::parse(Rule::asciidoc, element.as_str()).unwrap();
-
-          for element in ast {
-            if let Some(e) = process_element(element, env) {
-              base.children.push(e);
-            }
-          }
+        // Only compound blocks hold other blocks
+        if let Element::TypedBlock {
+          kind: BlockType::Example | BlockType::Sidebar | BlockType::Quote | BlockType::Open,
+        } = base.element
+        {
+          let children = parse_inner(Rule::asciidoc, &element, &base, ctx);
+          base.children.extend(children);
+        }
+        if let Element::TypedBlock {
+          kind: BlockType::Listing,
+        } = base.element
+        {
+          base.children.extend(process_callouts(&element));
         }
         base.attributes.push(Attribute {
           key: "content".to_string(), // TODO
@@ -471,61 +1013,54 @@ fn process_inline<'a>(
 ) -> ElementSpan<'a> {
   for element in element.into_inner() {
     match element.as_rule() {
+      Rule::comment => {
+        base.element = Element::Comment;
+      }
       Rule::link => {
         base = process_link(element, base);
       }
       Rule::xref => {
         base = process_xref(element, base);
       }
-      Rule::monospaced => {
-        base.element = Element::Styled;
-        base.attributes.push(Attribute {
-          key: "style".to_string(),
-          value: AttributeValue::Ref("monospaced"),
-        });
-
-        if let Some(content) = concat_elements(element.clone(), Rule::linechar, "") {
-          base.attributes.push(Attribute {
-            key: "content".to_string(),
-            value: AttributeValue::String(content),
-          });
+      Rule::footnote | Rule::footnoteref => {
+        base = process_footnote(element, base);
+      }
+      Rule::kbd_macro
+      | Rule::btn_macro
+      | Rule::menu_macro
+      | Rule::pass_macro
+      | Rule::anchor_macro
+      | Rule::inline_image
+      | Rule::inline_macro => {
+        base = process_inline_macro(element, base);
+      }
+      Rule::hard_break => {
+        base.element = Element::LineBreak;
+      }
+      Rule::curved_quote => {
+        let style = match element.as_str().starts_with('"') {
+          true => "doublequote",
+          false => "singlequote",
         };
-        for subelement in element.into_inner() {
-          match subelement.as_rule() {
-            Rule::inline_anchor => {
-              base = process_inline_anchor(subelement, base);
-            }
-            _ => (),
-          }
-        }
+        base = process_styled(element, base, style);
       }
       Rule::strong => {
-        base.element = Element::Styled;
-        base.attributes.push(Attribute {
-          key: "style".to_string(),
-          value: AttributeValue::Ref("strong"),
-        });
-
-        if let Some(content) = concat_elements(element, Rule::linechar, "") {
-          base.attributes.push(Attribute {
-            key: "content".to_string(),
-            value: AttributeValue::String(content),
-          });
-        };
+        base = process_styled(element, base, "strong");
       }
       Rule::emphasized => {
-        base.element = Element::Styled;
-        base.attributes.push(Attribute {
-          key: "style".to_string(),
-          value: AttributeValue::Ref("em"),
-        });
-
-        if let Some(content) = concat_elements(element, Rule::linechar, "") {
-          base.attributes.push(Attribute {
-            key: "content".to_string(),
-            value: AttributeValue::String(content),
-          });
-        };
+        base = process_styled(element, base, "em");
+      }
+      Rule::monospaced => {
+        base = process_styled(element, base, "monospaced");
+      }
+      Rule::highlighted => {
+        base = process_styled(element, base, "mark");
+      }
+      Rule::superscript => {
+        base = process_styled(element, base, "sup");
+      }
+      Rule::subscript => {
+        base = process_styled(element, base, "sub");
       }
       _ => (),
     };
@@ -533,11 +1068,113 @@ fn process_inline<'a>(
   base
 }
 
-fn process_link<'a>(
+/// Processes styled text. Its inline elements become the children
+/// and the raw text between the marks is kept as `content`.
+fn process_styled<'a>(
   element: Pair<'a, asciidoc::Rule>,
   mut base: ElementSpan<'a>,
+  style: &'static str,
 ) -> ElementSpan<'a> {
-  base.element = Element::Link;
+  base.element = Element::Styled;
+  base.attributes.push(Attribute {
+    key: "style".to_string(),
+    value: AttributeValue::Ref(style),
+  });
+
+  let content = element.clone().into_inner().find(|sub| {
+    matches!(
+      sub.as_rule(),
+      Rule::unconstrained_content
+        | Rule::constrained_content
+        | Rule::quoted_content
+        | Rule::monospaced_literal
+        | Rule::script_text
+    )
+  });
+  if let Some(content) = &content {
+    base.attributes.push(Attribute {
+      key: "content".to_string(),
+      value: AttributeValue::String(content.as_str().to_string()),
+    });
+  }
+
+  for subelement in element.into_inner() {
+    match subelement.as_rule() {
+      Rule::inline_anchor => {
+        base = process_inline_anchor(subelement, base);
+      }
+      Rule::inline_attribute_list => {
+        base = process_inline_attribute_list(subelement, base);
+      }
+      Rule::unconstrained_content | Rule::constrained_content | Rule::quoted_content => {
+        for inner in subelement.into_inner() {
+          base.children.push(match inner.as_rule() {
+            Rule::inline => process_inline(inner.clone(), set_span(&inner)),
+            _ => from_element(&inner, Element::Text),
+          });
+        }
+      }
+      Rule::monospaced_literal | Rule::script_text => {
+        base.children.push(from_element(&subelement, Element::Text));
+      }
+      _ => (),
+    }
+  }
+
+  base
+}
+
+fn process_inline_macro<'a>(
+  element: Pair<'a, asciidoc::Rule>,
+  mut base: ElementSpan<'a>,
+) -> ElementSpan<'a> {
+  let rule = element.as_rule();
+  base.element = match rule {
+    Rule::kbd_macro => Element::Keyboard,
+    Rule::btn_macro => Element::Button,
+    Rule::menu_macro => Element::Menu,
+    Rule::pass_macro => Element::Passthrough,
+    Rule::anchor_macro => Element::Anchor,
+    Rule::inline_image => Element::InlineImage,
+    _ => Element::InlineMacro,
+  };
+
+  for element in element.into_inner() {
+    let key = match (rule, element.as_rule()) {
+      (_, Rule::inline_attribute_list) => {
+        base = process_inline_attribute_list(element, base);
+        continue;
+      }
+      (Rule::kbd_macro, Rule::macro_text) => "keys",
+      (Rule::btn_macro, Rule::macro_text) => "label",
+      (Rule::menu_macro, Rule::macro_target) => "menu",
+      (Rule::menu_macro, Rule::macro_text) => "items",
+      (Rule::pass_macro, Rule::pass_subs) => "subs",
+      (Rule::anchor_macro, Rule::identifier) => "id",
+      (Rule::inline_image, Rule::macro_target) => "path",
+      (_, Rule::macro_name) => "name",
+      (_, Rule::macro_target) => "target",
+      (_, Rule::macro_text) => "content",
+      _ => continue,
+    };
+    let value = match element.as_rule() {
+      Rule::macro_text => AttributeValue::String(element.as_str().replace("\\]", "]")),
+      _ => AttributeValue::Ref(element.as_str()),
+    };
+    base.attributes.push(Attribute {
+      key: key.to_string(),
+      value,
+    });
+  }
+
+  base
+}
+
+fn process_link<'a>(
+  element: Pair<'a, asciidoc::Rule>,
+  mut base: ElementSpan<'a>,
+) -> ElementSpan<'a> {
+  base.element = Element::Link;
   for element in element.into_inner() {
     match element.as_rule() {
       Rule::url => {
@@ -590,10 +1227,74 @@ fn process_xref<'a>(
   base
 }
 
+fn process_footnote<'a>(
+  element: Pair<'a, asciidoc::Rule>,
+  mut base: ElementSpan<'a>,
+) -> ElementSpan<'a> {
+  base.element = Element::Footnote;
+  for element in element.into_inner() {
+    match element.as_rule() {
+      Rule::identifier => {
+        base.attributes.push(Attribute {
+          key: "id".to_string(),
+          value: AttributeValue::Ref(element.as_str()),
+        });
+      }
+      Rule::footnote_text => {
+        let content = element.as_str().trim().replace("\\]", "]");
+        // Without text the footnote refers to an earlier one
+        if !content.is_empty() {
+          base.attributes.push(Attribute {
+            key: "content".to_string(),
+            value: AttributeValue::String(content),
+          });
+        }
+      }
+      _ => (),
+    };
+  }
+
+  base
+}
+
+/// Numbers the footnotes in the order they appear. Footnotes
+/// referring to an earlier one (by its id) get the same number.
+fn number_footnotes(elements: &mut [ElementSpan], ids: &mut Vec<Option<String>>) {
+  for element in elements.iter_mut() {
+    match &mut element.element {
+      Element::Footnote => {
+        let id = element.get_attribute("id").map(|id| id.to_string());
+        let number = if element.get_attribute("content").is_some() {
+          ids.push(id);
+          Some(ids.len())
+        } else {
+          ids
+            .iter()
+            .position(|known| known.is_some() && *known == id)
+            .map(|index| index + 1)
+        };
+
+        match number {
+          Some(number) => element.attributes.push(Attribute {
+            key: "number".to_string(),
+            value: AttributeValue::String(number.to_string()),
+          }),
+          None => {
+            let id = element.get_attribute("id").unwrap_or("");
+            element.element = Element::Error(format!("unknown footnote `{}`", id));
+          }
+        }
+      }
+      Element::IncludeElement(include) => number_footnotes(&mut include.inner.elements, ids),
+      _ => number_footnotes(&mut element.children, ids),
+    }
+  }
+}
+
 fn process_image<'a>(
   element: Pair<'a, asciidoc::Rule>,
   mut base: ElementSpan<'a>,
-  env: &mut Env,
+  ctx: &mut ParserContext,
 ) -> ElementSpan<'a> {
   base.element = Element::Image;
   for element in element.into_inner().flatten() {
@@ -623,7 +1324,7 @@ fn process_image<'a>(
     if value == "inline" {
       // TODO Die Datei einlesen
       if let Some(path) = base.get_attribute("path") {
-        match env.read_to_string(path) {
+        match ctx.env.read_to_string(path) {
           Ok(content) => {
             base.attributes.push(Attribute {
               key: "content".to_string(),
@@ -643,101 +1344,1235 @@ fn process_image<'a>(
   base
 }
 
+fn process_include<'a>(
+  element: Pair<'a, asciidoc::Rule>,
+  mut base: ElementSpan<'a>,
+) -> ElementSpan<'a> {
+  for element in element.into_inner() {
+    match element.as_rule() {
+      Rule::include_target => {
+        base.attributes.push(Attribute {
+          key: "target".to_string(),
+          value: AttributeValue::Ref(element.as_str()),
+        });
+      }
+      Rule::inline_attribute_list => {
+        base = process_inline_attribute_list(element, base);
+      }
+      _ => (),
+    };
+  }
+
+  // The included document is read when the attributes are
+  // resolved, because the target could contain attributes
+  base.element = Element::IncludeElement(IncludeElement {
+    inner: AST {
+      content: "".into(),
+      elements: Vec::new(),
+      attributes: Vec::new(),
+    },
+  });
+
+  base
+}
+
+fn process_attribute_entry<'a>(
+  element: Pair<'a, asciidoc::Rule>,
+  mut base: ElementSpan<'a>,
+) -> ElementSpan<'a> {
+  let mut key = "";
+  let mut value = "";
+
+  for element in element.into_inner() {
+    match element.as_rule() {
+      Rule::attribute_entry_name => key = element.as_str(),
+      Rule::attribute_entry_value => value = element.as_str().trim_end(),
+      _ => (),
+    }
+  }
+
+  base.element = Element::Attribute(Attribute {
+    key: key.to_string(),
+    value: AttributeValue::Ref(value),
+  });
+
+  base
+}
+
+// Attribute resolution
+
+/// Walks through the elements in document order, keeps track
+/// of the attribute entries and replaces all attribute references.
+/// Includes are read and resolved on the way.
+fn resolve_attributes<'a>(elements: &mut [ElementSpan<'a>], ctx: &mut ParserContext) {
+  for element in elements.iter_mut() {
+    resolve_element_attributes(element, ctx);
+  }
+}
+
+fn resolve_element_attributes<'a>(element: &mut ElementSpan<'a>, ctx: &mut ParserContext) {
+  if let Element::Attribute(entry) = &element.element {
+    ctx.attributes.define(&entry.key, entry.value.as_str());
+    return;
+  }
+
+  // The attribute entries of the document header are
+  // children of the title but apply to it as well
+  let header = matches!(element.element, Element::Title { .. });
+  if header {
+    resolve_attributes(&mut element.children, ctx);
+  }
+
+  // Blocks choose the substitutions for the text inside
+  let outer_subs = match &element.element {
+    Element::TypedBlock { .. } => Some(std::mem::replace(&mut ctx.subs, subs::of(element))),
+    Element::Table | Element::TableCell if element.get_attribute("subs").is_some() => {
+      Some(std::mem::replace(&mut ctx.subs, subs::of(element)))
+    }
+    _ => None,
+  };
+  unparse_inline(element, &ctx.subs);
+  let attributes = ctx.subs.contains(&Substitution::Attributes);
+  let replacements = ctx.subs.contains(&Substitution::Replacements);
+
+  let substitute_content = match &element.element {
+    Element::Styled | Element::XRef | Element::Footnote | Element::TypedBlock { .. } => attributes,
+    _ => false,
+  };
+
+  for value in element.positional_attributes.iter_mut() {
+    substitute_value(value, ctx);
+  }
+  for attribute in element.attributes.iter_mut() {
+    if attribute.key != "content" || substitute_content {
+      substitute_value(&mut attribute.value, ctx);
+    }
+  }
+
+  match &element.element {
+    Element::Text | Element::TableCell => {
+      let mut content = match attributes {
+        true => ctx.attributes.substitute(&element.content),
+        false => None,
+      };
+      if replacements {
+        content = subs::replacements(content.as_deref().unwrap_or(&element.content)).or(content);
+      }
+      if let Some(content) = content {
+        element.attributes.push(Attribute {
+          key: "content".to_string(),
+          value: AttributeValue::String(content),
+        });
+      }
+      // Text without escaping is written to the output as it is
+      if element.element == Element::Text && !ctx.subs.contains(&Substitution::SpecialChars) {
+        element.element = Element::Passthrough;
+        if element.get_attribute("content").is_none() {
+          element.attributes.push(Attribute {
+            key: "content".to_string(),
+            value: element.content.clone().into(),
+          });
+        }
+      }
+    }
+    Element::TypedBlock { .. } if replacements => {
+      for attribute in element.attributes.iter_mut().filter(|attribute| attribute.key == "content") {
+        if let Some(content) = subs::replacements(attribute.value.as_str()) {
+          attribute.value = AttributeValue::String(content);
+        }
+      }
+    }
+    Element::IncludeElement(include) if include.inner.get_attribute("source").is_none() => {
+      resolve_include(element, ctx);
+    }
+    _ => (),
+  }
+
+  if !header {
+    resolve_attributes(&mut element.children, ctx);
+  }
+  if let Some(subs) = outer_subs {
+    ctx.subs = subs;
+  }
+}
+
+/// Turns inline elements back into text if the substitution
+/// step which would have found them is switched off
+fn unparse_inline(element: &mut ElementSpan, subs: &[Substitution]) {
+  let step = match element.element {
+    Element::Styled => Substitution::Quotes,
+    Element::Link
+    | Element::XRef
+    | Element::Footnote
+    | Element::InlineImage
+    | Element::Keyboard
+    | Element::Button
+    | Element::Menu
+    | Element::Anchor
+    | Element::InlineMacro => Substitution::Macros,
+    Element::LineBreak => Substitution::PostReplacements,
+    _ => return,
+  };
+
+  if !subs.contains(&step) {
+    element.element = Element::Text;
+    element.children.clear();
+    element.attributes.clear();
+    element.positional_attributes.clear();
+  }
+}
+
+fn substitute_value(value: &mut AttributeValue, ctx: &mut ParserContext) {
+  if let Some(substituted) = ctx.attributes.substitute(value.as_str()) {
+    *value = AttributeValue::String(substituted);
+  }
+}
+
+fn resolve_include<'a>(base: &mut ElementSpan<'a>, ctx: &mut ParserContext) {
+  let target = base.get_attribute("target").unwrap_or("");
+  let path = match ctx.include_stack.last().and_then(|file| file.parent()) {
+    Some(dir) => normalize_path(&dir.join(target)),
+    None => normalize_path(Path::new(target)),
+  };
+
+  if ctx.include_stack.contains(&path) {
+    let cycle = ctx
+      .include_stack
+      .iter()
+      .chain(std::iter::once(&path))
+      .map(|path| path.to_string_lossy())
+      .collect::<Vec<_>>()
+      .join(" -> ");
+    base.element = Element::Error(format!("include cycle detected: {}", cycle));
+    return;
+  }
+
+  let content = match ctx.env.read_to_string(&path.to_string_lossy()) {
+    Ok(content) => content,
+    Err(e) => {
+      base.element = Element::Error(format!(
+        "couldn't read include file {} ({})",
+        path.to_string_lossy(),
+        e
+      ));
+      return;
+    }
+  };
+
+  let content = if let Some(lines) = base.get_attribute("lines") {
+    select_lines(&content, lines)
+  } else if let Some(tags) = base.get_attribute("tags").or(base.get_attribute("tag")) {
+    select_tags(&content, tags)
+  } else {
+    content
+  };
+
+  let mut elements = match parse_source(&content, ctx) {
+    Ok(elements) => elements,
+    Err(e) => {
+      base.element = Element::Error(format!(
+        "couldn't parse include file {} ({})",
+        path.to_string_lossy(),
+        e
+      ));
+      return;
+    }
+  };
+
+  ctx.include_stack.push(path.clone());
+  resolve_attributes(&mut elements, ctx);
+  ctx.include_stack.pop();
+
+  if let Some(offset) = base.get_attribute("leveloffset") {
+    match offset.trim_start_matches('+').parse::<i64>() {
+      Ok(offset) => shift_title_levels(&mut elements, offset),
+      Err(_) => {
+        warn!("invalid leveloffset `{}` in include of {}", offset, target);
+      }
+    }
+  }
+
+  // The included text is owned by the include
+  let elements = elements.into_iter().map(ElementSpan::into_owned).collect();
+  base.element = Element::IncludeElement(IncludeElement {
+    inner: AST {
+      content: Cow::Owned(content),
+      elements,
+      attributes: vec![Attribute {
+        key: "source".to_string(),
+        value: AttributeValue::String(path.to_string_lossy().to_string()),
+      }],
+    },
+  });
+}
+
+/// Moves the elements following a title into a section element.
+/// Sections of included documents are nested inside of the
+/// include, they don't take the content after it.
+pub(crate) fn nest_sections<'a>(elements: Vec<ElementSpan<'a>>, source: &Cow<'a, str>) -> Vec<ElementSpan<'a>> {
+  let mut root = Vec::new();
+  let mut sections: Vec<ElementSpan<'a>> = Vec::new();
+
+  for mut element in elements {
+    if let Element::IncludeElement(include) = &mut element.element {
+      let inner = std::mem::take(&mut include.inner.elements);
+      include.inner.elements = nest_sections(inner, &include.inner.content);
+    }
+
+    let level = match element.element {
+      // The level 1 title is the document title
+      Element::Title { level } if level > 1 => level - 1,
+      _ => {
+        match sections.last_mut() {
+          Some(section) => section.children.push(element),
+          None => root.push(element),
+        }
+        continue;
+      }
+    };
+
+    while let Some(Element::Section { level: open }) = sections.last().map(|s| &s.element) {
+      if *open < level {
+        break;
+      }
+      close_section(&mut sections, &mut root, source);
+    }
+
+    let parent = match sections.last().map(|s| &s.element) {
+      Some(Element::Section { level }) => *level,
+      _ => 0,
+    };
+    if level > parent + 1 {
+      warn!(
+        "section title out of sequence: expected level {}, got level {} (line {}: {})",
+        parent + 1,
+        level,
+        element.start_line,
+        element.get_attribute("name").unwrap_or("")
+      );
+    }
+
+    sections.push(ElementSpan {
+      element: Element::Section { level },
+      source: None,
+      content: element.content.clone(),
+      children: Vec::new(),
+      attributes: Vec::new(),
+      positional_attributes: Vec::new(),
+      start: element.start,
+      end: element.end,
+      start_line: element.start_line,
+      start_col: element.start_col,
+      end_line: element.end_line,
+      end_col: element.end_col,
+    });
+    if let Some(section) = sections.last_mut() {
+      section.children.push(element);
+    }
+  }
+
+  while !sections.is_empty() {
+    close_section(&mut sections, &mut root, source);
+  }
+
+  root
+}
+
+/// Ends the innermost open section at its last element. The
+/// content of the section borrows from the source if it can.
+#[allow(clippy::ptr_arg)]
+fn close_section<'a>(
+  sections: &mut Vec<ElementSpan<'a>>,
+  root: &mut Vec<ElementSpan<'a>>,
+  source: &Cow<'a, str>,
+) {
+  let mut section = match sections.pop() {
+    Some(section) => section,
+    None => return,
+  };
+
+  if let Some(last) = section.children.last() {
+    section.end = last.end;
+    section.end_line = last.end_line;
+    section.end_col = last.end_col;
+  }
+  if let Some(content) = slice(source, section.start..section.end) {
+    section.content = content;
+  }
+
+  match sections.last_mut() {
+    Some(parent) => parent.children.push(section),
+    None => root.push(section),
+  }
+}
+
+/// Only keeps the lines of an include selected by the
+/// `lines` attribute (e.g. `1..5;10..-1` or `3,7`)
+fn select_lines(content: &str, selection: &str) -> String {
+  let ranges: Vec<(usize, usize)> = selection
+    .split([';', ','])
+    .filter_map(|range| {
+      let range = range.trim();
+      match range.split_once("..") {
+        Some((start, end)) => {
+          let start = start.trim().parse::<usize>().ok()?;
+          let end = match end.trim() {
+            "" | "-1" => usize::MAX,
+            end => end.parse::<usize>().ok()?,
+          };
+          Some((start, end))
+        }
+        None => {
+          let line = range.parse::<usize>().ok()?;
+          Some((line, line))
+        }
+      }
+    })
+    .collect();
+
+  content
+    .lines()
+    .enumerate()
+    .filter(|(number, _)| {
+      ranges
+        .iter()
+        .any(|(start, end)| (*start..=*end).contains(&(number + 1)))
+    })
+    .map(|(_, line)| line.to_string() + "\n")
+    .collect()
+}
+
+/// Only keeps the regions of an include selected by the
+/// `tag` or `tags` attribute. The regions are marked with
+/// `tag::name[]` and `end::name[]` in the included file
+/// (usually inside a comment).
+///
+/// The selection follows asciidoctor: `*` selects all tagged
+/// regions, `**` all lines and a leading `!` excludes a tag.
+fn select_tags(content: &str, selection: &str) -> String {
+  let mut selected = Vec::new();
+  let mut wildcard = None;
+  let mut all_lines = false;
+
+  for tag in selection.split([';', ',']).map(str::trim) {
+    let (name, include) = match tag.strip_prefix('!') {
+      Some(name) => (name, false),
+      None => (tag, true),
+    };
+    match name {
+      "" => (),
+      "**" => {
+        all_lines = include;
+        wildcard = Some(include);
+      }
+      "*" => wildcard = Some(include),
+      name => selected.push((name, include)),
+    }
+  }
+
+  // Untagged lines are only kept if no tag is
+  // explicitly requested
+  let untagged =
+    all_lines || (wildcard != Some(true) && selected.iter().all(|(_, include)| !include));
+
+  let mut output = String::new();
+  let mut active: Vec<&str> = Vec::new();
+
+  for line in content.lines() {
+    if let Some(name) = find_tag_directive(line, "tag::") {
+      active.push(name);
+      continue;
+    }
+    if let Some(name) = find_tag_directive(line, "end::") {
+      match active.iter().rposition(|tag| *tag == name) {
+        Some(position) => {
+          active.remove(position);
+        }
+        None => warn!("unexpected end tag `{}` in include", name),
+      }
+      continue;
+    }
+
+    let include = active.iter().fold(untagged, |include, tag| {
+      match selected.iter().find(|(name, _)| name == tag) {
+        Some((_, selected)) => *selected,
+        None => wildcard.unwrap_or(include),
+      }
+    });
+    if include {
+      output.push_str(line);
+      output.push('\n');
+    }
+  }
+
+  output
+}
+
+fn find_tag_directive<'a>(line: &'a str, directive: &str) -> Option<&'a str> {
+  let start = line.find(directive)? + directive.len();
+  let rest = &line[start..];
+  let end = rest.find("[]")?;
+  let name = &rest[..end];
+
+  if !name.is_empty()
+    && name
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+  {
+    Some(name)
+  } else {
+    None
+  }
+}
+
+fn shift_title_levels(elements: &mut [ElementSpan], offset: i64) {
+  for element in elements.iter_mut() {
+    match &mut element.element {
+      Element::Title { level } => {
+        *level = (*level as i64 + offset).max(1) as u32;
+      }
+      Element::IncludeElement(include) => {
+        shift_title_levels(&mut include.inner.elements, offset);
+      }
+      _ => (),
+    }
+    shift_title_levels(&mut element.children, offset);
+  }
+}
+
 fn process_inner_table<'a>(
   element: Pair<'a, asciidoc::Rule>,
   mut base: ElementSpan<'a>,
-  env: &mut Env,
+  ctx: &mut ParserContext,
 ) -> ElementSpan<'a> {
   let row_format = base.get_attribute("cols").unwrap_or("");
   let cell_formats = parse_row_format(row_format);
+  let options = table_options(&base);
+  let format = table_format(&base, element.as_str());
 
   for element in element.into_inner() {
     match element.as_rule() {
       Rule::delimited_inner => {
-        let ast = AsciidocParser::parse(Rule::table_inner, element.as_str()).unwrap();
-        for element in ast {
-          for subelement in element.into_inner() {
-            if let Some(e) = process_element(subelement, env) {
-              base.children.push(e);
-            }
+        let table = match format {
+          TableFormat::Psv => AsciidocParser::parse(Rule::table_inner, element.as_str()).map(|inner| {
+            let cells = inner
+              .flat_map(|inner| inner.into_inner())
+              .filter(|cell| cell.as_rule() == Rule::table_cell)
+              .collect();
+            process_table_cells(element.as_str(), cells, &cell_formats, &options, ctx)
+          }),
+          TableFormat::Csv(separator) | TableFormat::Dsv(separator) => {
+            let records = split_data_table(element.as_str(), separator, format);
+            Ok(process_data_cells(element.as_str(), records, &cell_formats, &options, ctx))
+          }
+        };
+        match table {
+          Ok((columns, mut rows)) => {
+            let (line, col) = element.as_span().start_pos().line_col();
+            move_spans(&mut rows, element.as_span().start(), line, col);
+            base.children.extend(rows);
+            base.attributes.push(Attribute {
+              key: "colwidths".to_string(),
+              value: AttributeValue::String(column_widths(&columns).join(",")),
+            });
           }
+          Err(e) => base.children.push(inner_error(&element.as_span(), &base, &e)),
         }
         base.attributes.push(Attribute {
           key: "content".to_string(),
           value: AttributeValue::Ref(element.as_str()),
         });
       }
-      Rule::table_row => {
-        let row = process_table_row(element, base.clone(), env, &cell_formats);
-        base.children.push(row);
-      }
       _ => (),
     };
   }
   base
 }
 
+/// Gets the options of a table (e.g. `[%header%footer]`
+/// or `[options="header,footer"]`)
+fn table_options(base: &ElementSpan) -> Vec<String> {
+  let mut options = Vec::new();
+  for attribute in base.attributes.iter() {
+    if attribute.key == "options" || attribute.key == "opts" {
+      options.extend(attribute.value.as_str().split(',').map(|option| option.trim().to_string()));
+    }
+  }
+  for value in base.positional_attributes.iter() {
+    let value = value.as_str().trim();
+    if value.starts_with('%') {
+      options.extend(value.split('%').skip(1).map(|option| option.to_string()));
+    }
+  }
+
+  options
+}
+
+/// Puts the cells of a table into rows. A row is complete as soon
+/// as all of its columns are covered (by its cells or by cells of
+/// the rows above spanning multiple rows). Without a `cols`
+/// attribute the cells on the first line define the columns.
+fn process_table_cells<'a>(
+  input: &'a str,
+  cells: Vec<Pair<'a, asciidoc::Rule>>,
+  cell_formats: &[CellFormat],
+  options: &[String],
+  ctx: &mut ParserContext,
+) -> (Vec<CellFormat>, Vec<ElementSpan<'a>>) {
+  let cells: Vec<_> = cells
+    .into_iter()
+    .flat_map(|cell| {
+      let spec = cell
+        .clone()
+        .into_inner()
+        .find(|sub| sub.as_rule() == Rule::table_cell_spec)
+        .map_or(DEFAULT_CELL_SPEC, |spec| parse_cell_spec(spec.as_str()));
+      std::iter::repeat_n((cell, spec), spec.duplicate)
+    })
+    .collect();
+  let line = |cell: &Pair<'a, asciidoc::Rule>| cell.as_span().start_pos().line_col().0;
+
+  let columns = if cell_formats.is_empty() {
+    let first_line = cells.first().map(|(cell, _)| line(cell));
+    let count = cells
+      .iter()
+      .filter(|(cell, _)| Some(line(cell)) == first_line)
+      .map(|(_, spec)| spec.colspan)
+      .sum::<usize>();
+    vec![DEFAULT_CELL_FORMAT; count.max(1)]
+  } else {
+    cell_formats.to_vec()
+  };
+
+  // The cells (index) of every row with the column they start in
+  let mut layout: Vec<Vec<(usize, usize)>> = Vec::new();
+  let mut row = Vec::new();
+  // The spans of the cells cut to the size of the table
+  let mut specs: Vec<CellSpec> = cells.iter().map(|(_, spec)| *spec).collect();
+  let mut spans = vec![0; columns.len()];
+  let mut owners = vec![0; columns.len()];
+  let mut column = next_free_column(&spans, 0);
+  for (index, (_, spec)) in cells.iter().enumerate() {
+    if column >= columns.len() {
+      layout.push(std::mem::take(&mut row));
+      for span in spans.iter_mut() {
+        *span = span.saturating_sub(1);
+      }
+      column = next_free_column(&spans, 0);
+      if column >= columns.len() {
+        // No column of the row is free, so the
+        // rowspans end before it
+        for (span, owner) in spans.iter_mut().zip(owners.iter()) {
+          if *span > 0 {
+            specs[*owner].rowspan = cells[*owner].1.rowspan - *span;
+            *span = 0;
+          }
+        }
+        column = 0;
+      }
+    }
+    row.push((index, column));
+    let end = (column + spec.colspan).min(columns.len());
+    specs[index].colspan = end - column;
+    for (span, owner) in spans[column..end].iter_mut().zip(owners[column..end].iter_mut()) {
+      *span = spec.rowspan;
+      *owner = index;
+    }
+    column = next_free_column(&spans, end);
+  }
+  if !row.is_empty() {
+    layout.push(row);
+  }
+  // The rowspans can't reach past the last row
+  for (span, owner) in spans.iter().zip(owners.iter()) {
+    if *span > 1 {
+      specs[*owner].rowspan = cells[*owner].1.rowspan - (*span - 1);
+    }
+  }
+
+  // The first line is the header if it's followed by an empty line
+  let implicit_header = match layout.first().and_then(|row| row.last()) {
+    Some((index, _)) if layout.len() > 1 => {
+      let (cell, _) = &cells[*index];
+      let trailing = cell
+        .clone()
+        .into_inner()
+        .find(|sub| sub.as_rule() == Rule::table_cell_content)
+        .map_or("", |content| &cell.as_str()[content.as_span().end() - cell.as_span().start()..]);
+      layout[0].iter().all(|(index, _)| line(&cells[*index].0) == line(&cells[0].0))
+        && trailing.matches('\n').count() > 1
+    }
+    _ => false,
+  };
+  let sections = table_sections(options, implicit_header, layout.len());
+
+  let rows = layout
+    .into_iter()
+    .zip(sections)
+    .map(|(row, section)| {
+      let first = &cells[row[0].0].0;
+      let last = &cells[row[row.len() - 1].0].0;
+      let mut base = table_row(input, first.as_span().start(), last.as_span().end(), section);
+      for (index, column) in row {
+        let (cell, _) = &cells[index];
+        let format = columns.get(column).unwrap_or(&DEFAULT_CELL_FORMAT);
+        let spec = &specs[index];
+        base.children.push(process_table_cell(cell, spec, format, section == "header", ctx));
+      }
+
+      base
+    })
+    .collect();
+
+  (columns, rows)
+}
+
+/// Gets the section (header, body or footer) of every row
+fn table_sections(options: &[String], implicit_header: bool, rows: usize) -> Vec<&'static str> {
+  let has_option = |name: &str| options.iter().any(|option| option == name);
+  let header = if has_option("noheader") {
+    false
+  } else {
+    has_option("header") || implicit_header
+  };
+  let footer = has_option("footer") && rows > 1;
+
+  (0..rows)
+    .map(|number| {
+      if number == 0 && header {
+        "header"
+      } else if number + 1 == rows && footer {
+        "footer"
+      } else {
+        "body"
+      }
+    })
+    .collect()
+}
+
+fn table_row<'a>(input: &'a str, start: usize, end: usize, section: &'static str) -> ElementSpan<'a> {
+  let end = start + input[start..end].trim_end().len();
+  let mut base = from_span(
+    pest::Span::new(input, start, end).expect("the row should be inside of the table"),
+    Element::TableRow,
+  );
+  base.attributes.push(Attribute {
+    key: "section".to_string(),
+    value: AttributeValue::Ref(section),
+  });
+
+  base
+}
+
+fn next_free_column(spans: &[usize], mut column: usize) -> usize {
+  while column < spans.len() && spans[column] > 0 {
+    column += 1;
+  }
+
+  column
+}
+
+/// Calculates the widths of the columns in percent
+fn column_widths(columns: &[CellFormat]) -> Vec<String> {
+  let total = columns.iter().map(|column| column.length).sum::<usize>().max(1) as f64;
+  let round = |width: f64| (width * 10000.0).round() / 10000.0;
+
+  let mut rest = 100.0;
+  columns
+    .iter()
+    .enumerate()
+    .map(|(index, column)| {
+      // The last column gets the rest so we always get 100%
+      let width = if index + 1 == columns.len() {
+        round(rest)
+      } else {
+        round(column.length as f64 * 100.0 / total)
+      };
+      rest -= width;
+      width.to_string()
+    })
+    .collect()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum CellKind {
   Default,
   Asciidoc,
+  Emphasis,
+  Header,
+  Literal,
+  Monospaced,
+  Strong,
+  Verse,
+}
+
+impl CellKind {
+  fn from_char(style: char) -> Option<Self> {
+    match style {
+      'a' => Some(CellKind::Asciidoc),
+      'd' => Some(CellKind::Default),
+      'e' => Some(CellKind::Emphasis),
+      'h' => Some(CellKind::Header),
+      'l' => Some(CellKind::Literal),
+      'm' => Some(CellKind::Monospaced),
+      's' => Some(CellKind::Strong),
+      'v' => Some(CellKind::Verse),
+      _ => None,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      CellKind::Default => "default",
+      CellKind::Asciidoc => "asciidoc",
+      CellKind::Emphasis => "emphasis",
+      CellKind::Header => "header",
+      CellKind::Literal => "literal",
+      CellKind::Monospaced => "monospaced",
+      CellKind::Strong => "strong",
+      CellKind::Verse => "verse",
+    }
+  }
 }
 
+/// The format of a column (from the `cols` attribute)
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct CellFormat {
     length: usize,
     kind: CellKind,
+    halign: Option<&'static str>,
+    valign: Option<&'static str>,
 }
 
+/// The specifier in front of a cell (e.g. `2+^a|`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CellSpec {
+  colspan: usize,
+  rowspan: usize,
+  duplicate: usize,
+  halign: Option<&'static str>,
+  valign: Option<&'static str>,
+  kind: Option<CellKind>,
+}
+
+/// Parses the `cols` attribute of a table
+/// (e.g. `3*`, `1,2a` or `2*^.>2,<3s`)
 fn parse_row_format(input: &str) -> Vec<CellFormat> {
-  input.split(',')
-       .map(|fmt| {
-           let parts: Vec<&str> = fmt.split('=').collect();
-           let kind = match parts.get(0).unwrap_or(&"default") {
-            &"a" => CellKind::Asciidoc,
-            _ => CellKind::Default,
-           };
-           let length = parts.get(1).unwrap_or(&"1").parse::<usize>().unwrap_or(1);
-           CellFormat { length, kind }
-       })
-       .collect()
+  let input = input.trim();
+  if input.is_empty() {
+    return Vec::new();
+  }
+  // A single number is just the number of columns
+  if let Ok(count) = input.parse::<usize>() {
+    return vec![DEFAULT_CELL_FORMAT; count];
+  }
+
+  input
+    .split([',', ';'])
+    .flat_map(|spec| {
+      let (count, spec) = match spec.split_once('*') {
+        Some((count, spec)) => (count.trim().parse().unwrap_or(1), spec),
+        None => (1, spec),
+      };
+      let (halign, valign, rest) = split_alignment(spec.trim());
+      let width: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+      let style = rest[width.len()..].trim_start_matches(&['%', '~'][..]);
+
+      let format = CellFormat {
+        length: width.parse().unwrap_or(1),
+        kind: style.chars().next().and_then(CellKind::from_char).unwrap_or(CellKind::Default),
+        halign,
+        valign,
+      };
+      std::iter::repeat_n(format, count)
+    })
+    .collect()
 }
 
-fn process_inner_table(input: &str, cell_format: Vec<CellFormat>) ->  {
-  
+fn parse_cell_spec(input: &str) -> CellSpec {
+  let mut spec = DEFAULT_CELL_SPEC;
+  let mut rest = input;
+
+  if let Some(position) = rest.find(['+', '*']) {
+    let factor = &rest[..position];
+    if rest[position..].starts_with('+') {
+      let (colspan, rowspan) = factor.split_once('.').unwrap_or((factor, ""));
+      spec.colspan = colspan.parse().unwrap_or(1);
+      spec.rowspan = rowspan.parse().unwrap_or(1);
+    } else {
+      spec.duplicate = factor.parse().unwrap_or(1);
+    }
+    rest = &rest[position + 1..];
+  }
+
+  let (halign, valign, rest) = split_alignment(rest);
+  spec.halign = halign;
+  spec.valign = valign;
+  spec.kind = rest.chars().next().and_then(CellKind::from_char);
+
+  spec
 }
 
-fn process_table_row<'a>(
-  element: Pair<'a, asciidoc::Rule>,
-  mut base: ElementSpan<'a>,
-  env: &mut Env,
-  cell_formats: &[CellFormat],
-) -> ElementSpan<'a> {
-  base.element = Element::TableRow;
+/// Splits the horizontal (`<`, `^`, `>`) and the vertical
+/// alignment (`.<`, `.^`, `.>`) from the start of a specifier
+fn split_alignment(input: &str) -> (Option<&'static str>, Option<&'static str>, &str) {
+  let mut rest = input;
 
-  for (cell_element, cell_format) in element.into_inner().zip(cell_formats.iter().cycle()) {
-    let cell = process_table_cell(&cell_element, base.clone(), env, cell_format);
-    base.children.push(cell);
+  let halign = match rest.chars().next() {
+    Some('<') => Some("left"),
+    Some('^') => Some("center"),
+    Some('>') => Some("right"),
+    _ => None,
+  };
+  if halign.is_some() {
+    rest = &rest[1..];
   }
 
-  base
+  let valign = match rest.strip_prefix('.').and_then(|rest| rest.chars().next()) {
+    Some('<') => Some("top"),
+    Some('^') => Some("middle"),
+    Some('>') => Some("bottom"),
+    _ => None,
+  };
+  if valign.is_some() {
+    rest = &rest[2..];
+  }
+
+  (halign, valign, rest)
 }
 
-static DEFAULT_CELL_FORMAT : CellFormat = CellFormat { length: 1, kind: CellKind::Default };
+static DEFAULT_CELL_FORMAT : CellFormat = CellFormat {
+  length: 1,
+  kind: CellKind::Default,
+  halign: None,
+  valign: None,
+};
+
+static DEFAULT_CELL_SPEC: CellSpec = CellSpec {
+  colspan: 1,
+  rowspan: 1,
+  duplicate: 1,
+  halign: None,
+  valign: None,
+  kind: None,
+};
 
 fn process_table_cell<'a>(
   element: &Pair<'a, asciidoc::Rule>,
-  mut base: ElementSpan<'a>,
-  _env: &mut Env,
+  spec: &CellSpec,
   cell_format: &CellFormat,
+  header: bool,
+  ctx: &mut ParserContext,
 ) -> ElementSpan<'a> {
-  base.element = Element::TableCell;
-
-  base.content = element.clone()
+  let content = element
+    .clone()
     .into_inner()
-    .find(|sub| sub.as_rule() == Rule::table_cell_content)
-    .map_or("", |pair| pair.as_str())
-    .trim();
+    .find(|sub| sub.as_rule() == Rule::table_cell_content);
+  let mut base = match &content {
+    Some(content) => from_element(content, Element::TableCell),
+    None => from_element(element, Element::TableCell),
+  };
+  let start = base.content.len() - base.content.trim_start().len();
+  let end = base.content.trim_end().len().max(start);
+  base.content = slice(&base.content, start..end).unwrap_or_default();
+  let kind = add_cell_attributes(&mut base, spec, cell_format, header);
+
+  if let Some(content) = content {
+    base.children = match kind {
+      CellKind::Asciidoc => parse_inner(Rule::asciidoc, &content, &base, ctx),
+      CellKind::Literal | CellKind::Verse => Vec::new(),
+      _ => parse_inner(Rule::table_cell_paragraphs, &content, &base, ctx),
+    };
+  }
+
+  base
+}
+
+/// Adds the style and alignment to a cell and returns its kind
+fn add_cell_attributes(
+  base: &mut ElementSpan,
+  spec: &CellSpec,
+  cell_format: &CellFormat,
+  header: bool,
+) -> CellKind {
+  // Header cells are never formatted
+  let kind = match header {
+    true => CellKind::Header,
+    false => spec.kind.unwrap_or(cell_format.kind),
+  };
+  let mut attributes = vec![
+    ("style", kind.name().to_string()),
+    ("halign", spec.halign.or(cell_format.halign).unwrap_or("left").to_string()),
+    ("valign", spec.valign.or(cell_format.valign).unwrap_or("top").to_string()),
+  ];
+  if spec.colspan > 1 {
+    attributes.push(("colspan", spec.colspan.to_string()));
+  }
+  if spec.rowspan > 1 {
+    attributes.push(("rowspan", spec.rowspan.to_string()));
+  }
+  for (key, value) in attributes {
+    base.attributes.push(Attribute {
+      key: key.to_string(),
+      value: AttributeValue::String(value),
+    });
+  }
+
+  kind
+}
+
+/// The way the cells of a table are written down. Csv and
+/// dsv tables hold their separator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TableFormat {
+  Psv,
+  Csv(char),
+  Dsv(char),
+}
+
+/// Gets the format of a table from its `format` attribute
+/// or else from its delimiter (`,===` or `:===`)
+fn table_format(base: &ElementSpan, table: &str) -> TableFormat {
+  let separator = base.get_attribute("separator").and_then(|separator| match separator {
+    "\\t" => Some('\t'),
+    _ => separator.chars().next(),
+  });
+
+  let format = base.get_attribute("format").unwrap_or(match table.chars().next() {
+    Some(',') => "csv",
+    Some(':') => "dsv",
+    _ => "psv",
+  });
+  match format {
+    "csv" => TableFormat::Csv(separator.unwrap_or(',')),
+    "tsv" => TableFormat::Csv(separator.unwrap_or('\t')),
+    "dsv" => TableFormat::Dsv(separator.unwrap_or(':')),
+    _ => TableFormat::Psv,
+  }
+}
+
+/// A record (line) of a csv or dsv table
+#[derive(Debug, Clone, PartialEq)]
+struct DataRecord {
+  cells: Vec<DataCell>,
+  /// Whether an empty line follows the record
+  blank_line_after: bool,
+}
+
+/// A cell of a csv or dsv table. The position is the one of the
+/// text without quotes. If the text contains escapes the
+/// unescaped text is held in `value`.
+#[derive(Debug, Clone, PartialEq)]
+struct DataCell {
+  start: usize,
+  end: usize,
+  value: Option<String>,
+}
+
+/// Splits the content of a csv or dsv table into records and cells.
+/// In csv tables a cell can be quoted with `"` (to hold separators and
+/// newlines, a `"` inside is written as `""`). In dsv tables a
+/// separator is escaped with a backslash.
+fn split_data_table(input: &str, separator: char, format: TableFormat) -> Vec<DataRecord> {
+  let mut records: Vec<DataRecord> = Vec::new();
+  let mut cells = Vec::new();
+  let mut position = 0;
+
+  loop {
+    let rest = &input[position..];
+    if cells.is_empty() {
+      // Empty lines between records are skipped
+      let line = rest.split('\n').next().unwrap_or("");
+      if line.trim().is_empty() {
+        if position + line.len() >= input.len() {
+          break;
+        }
+        if let Some(record) = records.last_mut() {
+          record.blank_line_after = true;
+        }
+        position += line.len() + 1;
+        continue;
+      }
+    }
+
+    let (cell, end) = match format {
+      TableFormat::Csv(_) => next_csv_cell(input, position, separator),
+      _ => next_dsv_cell(input, position, separator),
+    };
+    cells.push(cell);
+
+    if input[end..].starts_with(separator) {
+      position = end + separator.len_utf8();
+    } else {
+      records.push(DataRecord {
+        cells: std::mem::take(&mut cells),
+        blank_line_after: false,
+      });
+      // Skip the newline
+      position = (end + 1).min(input.len());
+      if end >= input.len() {
+        break;
+      }
+    }
+  }
+
+  records
+}
+
+/// Reads a csv cell starting at `start`. Returns the cell and the
+/// position of the following separator or newline.
+fn next_csv_cell(input: &str, start: usize, separator: char) -> (DataCell, usize) {
+  let rest = &input[start..];
+  let quoted = rest.trim_start_matches([' ', '\t']);
+  if !quoted.starts_with('"') {
+    return next_dsv_cell(input, start, separator);
+  }
+
+  let text_start = input.len() - quoted.len() + 1;
+  let mut value = String::new();
+  let mut escaped = false;
+  let mut chars = input[text_start..].char_indices().peekable();
+  let mut text_end = input.len();
+  while let Some((index, c)) = chars.next() {
+    if c == '"' {
+      if chars.peek().map(|(_, c)| *c) == Some('"') {
+        chars.next();
+        escaped = true;
+      } else {
+        text_end = text_start + index;
+        break;
+      }
+    }
+    value.push(c);
+  }
+
+  // Anything between the closing quote and the separator is ignored
+  let after = (text_end + 1).min(input.len());
+  let end = after
+    + input[after..]
+      .find([separator, '\n'])
+      .unwrap_or(input.len() - after);
+  let cell = DataCell {
+    start: text_start,
+    end: text_end,
+    value: escaped.then_some(value),
+  };
+
+  (cell, end)
+}
+
+/// Reads an unquoted cell starting at `start`. Returns the cell and
+/// the position of the following separator or newline.
+fn next_dsv_cell(input: &str, start: usize, separator: char) -> (DataCell, usize) {
+  let mut value = String::new();
+  let mut escaped = false;
+  let mut chars = input[start..].char_indices().peekable();
+  let mut end = input.len();
+  while let Some((index, c)) = chars.next() {
+    if c == separator || c == '\n' {
+      end = start + index;
+      break;
+    }
+    if c == '\\' && chars.peek().map(|(_, c)| *c) == Some(separator) {
+      chars.next();
+      value.push(separator);
+      escaped = true;
+      continue;
+    }
+    value.push(c);
+  }
+
+  let text = &input[start..end];
+  let text_start = start + (text.len() - text.trim_start().len());
+  let text_end = start + text.trim_end().len();
+  let cell = DataCell {
+    start: text_start.min(text_end),
+    end: text_end,
+    value: escaped.then(|| value.trim().to_string()),
+  };
+
+  (cell, end)
+}
+
+/// Puts the cells of a csv or dsv table into rows. Like in psv
+/// tables a row is complete as soon as all of its columns are
+/// filled. Without a `cols` attribute the first record defines
+/// the columns.
+fn process_data_cells<'a>(
+  input: &'a str,
+  records: Vec<DataRecord>,
+  cell_formats: &[CellFormat],
+  options: &[String],
+  ctx: &mut ParserContext,
+) -> (Vec<CellFormat>, Vec<ElementSpan<'a>>) {
+  let columns = if cell_formats.is_empty() {
+    let count = records.first().map_or(1, |record| record.cells.len());
+    vec![DEFAULT_CELL_FORMAT; count.max(1)]
+  } else {
+    cell_formats.to_vec()
+  };
+
+  let implicit_header = match records.first() {
+    Some(record) => record.blank_line_after && record.cells.len() == columns.len(),
+    None => false,
+  };
+  let cells: Vec<_> = records.into_iter().flat_map(|record| record.cells).collect();
+  let layout: Vec<_> = cells.chunks(columns.len()).collect();
+  let sections = table_sections(options, implicit_header, layout.len());
+
+  let rows = layout
+    .into_iter()
+    .zip(sections)
+    .map(|(row, section)| {
+      let mut base = table_row(input, row[0].start, row[row.len() - 1].end, section);
+      for (cell, format) in row.iter().zip(columns.iter()) {
+        base.children.push(process_data_cell(input, cell, format, section == "header", ctx));
+      }
+
+      base
+    })
+    .collect();
+
+  (columns, rows)
+}
+
+fn process_data_cell<'a>(
+  input: &'a str,
+  cell: &DataCell,
+  cell_format: &CellFormat,
+  header: bool,
+  ctx: &mut ParserContext,
+) -> ElementSpan<'a> {
+  let span = pest::Span::new(input, cell.start, cell.end).expect("the cell should be inside of the table");
+  let mut base = from_span(span, Element::TableCell);
+  let kind = add_cell_attributes(&mut base, &DEFAULT_CELL_SPEC, cell_format, header);
+
+  match &cell.value {
+    // Escaped text can't be parsed in place, so it's used as is
+    Some(value) => {
+      base.attributes.push(Attribute {
+        key: "content".to_string(),
+        value: AttributeValue::String(value.to_string()),
+      });
+      if !matches!(kind, CellKind::Literal | CellKind::Verse) {
+        let mut paragraph = from_span(span, Element::Paragraph);
+        let mut text = from_span(span, Element::Text);
+        text.attributes.push(Attribute {
+          key: "content".to_string(),
+          value: AttributeValue::String(value.to_string()),
+        });
+        paragraph.children.push(text);
+        base.children.push(paragraph);
+      }
+    }
+    None => {
+      base.children = match kind {
+        CellKind::Asciidoc => parse_span(Rule::asciidoc, span, &base, ctx),
+        CellKind::Literal | CellKind::Verse => Vec::new(),
+        _ => parse_span(Rule::table_cell_paragraphs, span, &base, ctx),
+      };
+    }
+  }
 
   base
 }
@@ -762,6 +2597,26 @@ fn concat_elements<'a>(
   }
 }
 
+/// Removes `.` and `..` from a path without touching the
+/// file system (the files could come from a cache)
+fn normalize_path(path: &Path) -> PathBuf {
+  let mut normalized = PathBuf::new();
+
+  for component in path.components() {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir => {
+        if !normalized.pop() {
+          normalized.push("..");
+        }
+      }
+      component => normalized.push(component),
+    }
+  }
+
+  normalized
+}
+
 fn set_span<'a>(element: &Pair<'a, asciidoc::Rule>) -> ElementSpan<'a> {
   from_element(
     element,
@@ -770,18 +2625,22 @@ fn set_span<'a>(element: &Pair<'a, asciidoc::Rule>) -> ElementSpan<'a> {
 }
 
 fn from_element<'a>(rule: &Pair<'a, asciidoc::Rule>, element: Element<'a>) -> ElementSpan<'a> {
-  let (start_line, start_col) = rule.as_span().start_pos().line_col();
-  let (end_line, end_col) = rule.as_span().end_pos().line_col();
+  from_span(rule.as_span(), element)
+}
+
+pub(crate) fn from_span<'a>(span: pest::Span<'a>, element: Element<'a>) -> ElementSpan<'a> {
+  let (start_line, start_col) = span.start_pos().line_col();
+  let (end_line, end_col) = span.end_pos().line_col();
 
   ElementSpan {
     element,
     source: None, // TODO
-    content: rule.as_str(),
+    content: span.as_str().into(),
     children: Vec::new(),
     attributes: Vec::new(),
     positional_attributes: Vec::new(),
-    start: rule.as_span().start(),
-    end: rule.as_span().end(),
+    start: span.start(),
+    end: span.end(),
     start_line,
     start_col,
     end_line,
@@ -798,14 +2657,65 @@ mod test {
   #[test]
   fn test_table() {
     let out = parse_row_format(r#"1,a"#);
-    assert_eq!(out, vec![CellFormat{length:1,kind:CellKind::Default}, CellFormat{length:1,kind:CellKind::Asciidoc}]);
+    assert_eq!(out, vec![CellFormat{length:1,kind:CellKind::Default,..DEFAULT_CELL_FORMAT}, CellFormat{length:1,kind:CellKind::Asciidoc,..DEFAULT_CELL_FORMAT}]);
   }
 
   #[test]
   fn test_inner_table() {
     let out = parse_row_format(r#"1,a"#);
-    assert_eq!(out, vec![CellFormat{length:1,kind:CellKind::Default}, CellFormat{length:1,kind:CellKind::Asciidoc}]);
+    assert_eq!(out, vec![CellFormat{length:1,kind:CellKind::Default,..DEFAULT_CELL_FORMAT}, CellFormat{length:1,kind:CellKind::Asciidoc,..DEFAULT_CELL_FORMAT}]);
   }
-  
 
+  #[test]
+  fn column_specs() {
+    let out = parse_row_format("2*^.>2,<3s");
+    let centered = CellFormat {
+      length: 2,
+      kind: CellKind::Default,
+      halign: Some("center"),
+      valign: Some("bottom"),
+    };
+    let strong = CellFormat {
+      length: 3,
+      kind: CellKind::Strong,
+      halign: Some("left"),
+      valign: None,
+    };
+    assert_eq!(out, vec![centered, centered, strong]);
+    assert_eq!(parse_row_format("3"), vec![DEFAULT_CELL_FORMAT; 3]);
+    assert_eq!(column_widths(&parse_row_format("1,2")), vec!["33.3333", "66.6667"]);
+  }
+
+  #[test]
+  fn cell_specs() {
+    let spec = parse_cell_spec("2.3+^.^a");
+    assert_eq!((spec.colspan, spec.rowspan, spec.duplicate), (2, 3, 1));
+    assert_eq!((spec.halign, spec.valign), (Some("center"), Some("middle")));
+    assert_eq!(spec.kind, Some(CellKind::Asciidoc));
+
+    let spec = parse_cell_spec("3*");
+    assert_eq!((spec.colspan, spec.duplicate, spec.kind), (1, 3, None));
+  }
+
+  #[test]
+  fn data_cells() {
+    let input = "a, \"b, \"\"c\"\"\"\n\n\"d\ne\",f";
+    let records = split_data_table(input, ',', TableFormat::Csv(','));
+    let cells: Vec<Vec<_>> = records
+      .iter()
+      .map(|record| {
+        record
+          .cells
+          .iter()
+          .map(|cell| cell.value.clone().unwrap_or_else(|| input[cell.start..cell.end].to_string()))
+          .collect()
+      })
+      .collect();
+    assert_eq!(cells, vec![vec!["a", "b, \"c\""], vec!["d\ne", "f"]]);
+    assert_eq!(records[0].blank_line_after, true);
+    assert_eq!(records[1].blank_line_after, false);
+
+    let records = split_data_table("x:y\\:z", ':', TableFormat::Dsv(':'));
+    assert_eq!(records[0].cells[1].value, Some("y:z".to_string()));
+  }
 }
\ No newline at end of file
@@ -0,0 +1,306 @@
+use crate::options::Opts;
+use std::collections::{HashMap, HashSet};
+
+/// The document attributes known at a point of the document
+///
+/// Attributes come from the command line (`-a key=value`)
+/// and from attribute entries (`:key: value`) inside the
+/// document. Like in asciidoctor the command line wins,
+/// unless the value ends with an `@` (soft set). Then a
+/// document entry may override it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentAttributes {
+  values: HashMap<String, String>,
+  /// Attributes set on the command line which can't
+  /// be changed by the document
+  locked: HashSet<String>,
+}
+
+impl DocumentAttributes {
+  pub fn new() -> Self {
+    let mut values = HashMap::new();
+
+    // The intrinsic attributes for characters which
+    // would otherwise be interpreted as markup
+    for (key, value) in [
+      ("empty", ""),
+      ("sp", " "),
+      ("nbsp", "\u{a0}"),
+      ("zwsp", "\u{200b}"),
+      ("wj", "\u{2060}"),
+      ("apos", "'"),
+      ("quot", "\""),
+      ("lsquo", "\u{2018}"),
+      ("rsquo", "\u{2019}"),
+      ("ldquo", "\u{201c}"),
+      ("rdquo", "\u{201d}"),
+      ("deg", "\u{b0}"),
+      ("plus", "+"),
+      ("brvbar", "\u{a6}"),
+      ("vbar", "|"),
+      ("amp", "&"),
+      ("lt", "<"),
+      ("gt", ">"),
+      ("startsb", "["),
+      ("endsb", "]"),
+      ("caret", "^"),
+      ("asterisk", "*"),
+      ("tilde", "~"),
+      ("backslash", "\\"),
+      ("backtick", "`"),
+      ("two-colons", "::"),
+      ("two-semicolons", ";;"),
+      ("cpp", "C++"),
+    ] {
+      values.insert(key.to_string(), value.to_string());
+    }
+
+    DocumentAttributes {
+      values,
+      locked: HashSet::new(),
+    }
+  }
+
+  /// Takes the attributes defined on the command line
+  pub fn from_opts(opts: &Opts) -> Self {
+    let mut attributes = DocumentAttributes::new();
+
+    if let Some(path) = &opts.input {
+      if let Some(file) = path.to_str() {
+        attributes.set("docfile", file);
+      }
+      if let Some(dir) = path.parent().and_then(|dir| dir.to_str()) {
+        attributes.set("docdir", dir);
+      }
+    }
+
+    for (key, value) in opts.defines.iter() {
+      // `-a name!` (or `-a !name`) unsets the attribute
+      let (key, unset) = match key.strip_suffix('!').or(key.strip_prefix('!')) {
+        Some(key) => (key, true),
+        None => (key.as_str(), false),
+      };
+      let (value, soft) = match value.strip_suffix('@') {
+        Some(value) => (value, true),
+        None => (value.as_str(), false),
+      };
+
+      if unset {
+        attributes.values.remove(key);
+      } else {
+        attributes.set(key, value);
+      }
+      if !soft {
+        attributes.locked.insert(key.to_string());
+      }
+    }
+
+    attributes
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.values.get(key).map(|value| value.as_str())
+  }
+
+  pub fn is_set(&self, key: &str) -> bool {
+    self.values.contains_key(key)
+  }
+
+  /// Applies an attribute entry of the document (`:key: value`).
+  /// A key ending with `!` unsets the attribute. Values are
+  /// substituted before they are stored.
+  pub fn define(&mut self, key: &str, value: &str) {
+    let (key, unset) = match key.strip_suffix('!').or(key.strip_prefix('!')) {
+      Some(key) => (key, true),
+      None => (key, false),
+    };
+
+    if self.locked.contains(key) {
+      return;
+    }
+
+    if unset {
+      self.values.remove(key);
+    } else {
+      let value = self.substitute(value).unwrap_or_else(|| value.to_string());
+      self.set(key, &value);
+    }
+  }
+
+  fn set(&mut self, key: &str, value: &str) {
+    self.values.insert(key.to_string(), value.to_string());
+  }
+
+  /// Replaces all attribute references (`{name}`) in a text.
+  ///
+  /// Besides plain references `{set:name:value}`, `{set:name!}`,
+  /// `{counter:name[:start]}` and `{counter2:name[:start]}` are
+  /// supported. References to missing attributes are left as they
+  /// are and a reference can be escaped with a backslash.
+  ///
+  /// Returns `None` if there is nothing to replace, so callers can
+  /// keep their borrowed value.
+  pub fn substitute(&mut self, input: &str) -> Option<String> {
+    if !input.contains('{') {
+      return None;
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut changed = false;
+    let mut rest = input;
+
+    while let Some(start) = rest.find('{') {
+      let (before, reference) = rest.split_at(start);
+      let end = match reference.find('}') {
+        Some(end) => end,
+        None => break,
+      };
+      let name = &reference[1..end];
+
+      if !is_reference(name) {
+        output.push_str(before);
+        output.push('{');
+        rest = &reference[1..];
+        continue;
+      }
+
+      if let Some(before) = before.strip_suffix('\\') {
+        output.push_str(before);
+        output.push_str(&reference[..=end]);
+        changed = true;
+      } else {
+        output.push_str(before);
+        match self.resolve(name) {
+          Some(value) => {
+            output.push_str(&value);
+            changed = true;
+          }
+          None => output.push_str(&reference[..=end]),
+        }
+      }
+      rest = &reference[end + 1..];
+    }
+    output.push_str(rest);
+
+    if changed {
+      Some(output)
+    } else {
+      None
+    }
+  }
+
+  fn resolve(&mut self, reference: &str) -> Option<String> {
+    let mut parts = reference.splitn(3, ':');
+
+    match (parts.next(), parts.next(), parts.next()) {
+      (Some("set"), Some(name), value) => {
+        self.define(name, value.unwrap_or(""));
+        Some(String::new())
+      }
+      (Some("counter"), Some(name), start) => Some(self.increment(name, start)),
+      (Some("counter2"), Some(name), start) => {
+        self.increment(name, start);
+        Some(String::new())
+      }
+      (Some(name), None, None) => self.get(name).map(|value| value.to_string()),
+      _ => None,
+    }
+  }
+
+  fn increment(&mut self, name: &str, start: Option<&str>) -> String {
+    let value = match self.get(name) {
+      Some(value) => match value.parse::<i64>() {
+        Ok(number) => (number + 1).to_string(),
+        Err(_) => {
+          let mut chars = value.chars();
+          match (chars.next(), chars.next()) {
+            (Some(c), None) => std::char::from_u32(c as u32 + 1)
+              .unwrap_or(c)
+              .to_string(),
+            _ => "1".to_string(),
+          }
+        }
+      },
+      None => start.unwrap_or("1").to_string(),
+    };
+
+    if !self.locked.contains(name) {
+      self.set(name, &value);
+    }
+    value
+  }
+}
+
+impl Default for DocumentAttributes {
+  fn default() -> Self {
+    DocumentAttributes::new()
+  }
+}
+
+/// Checks if the text between braces looks like an attribute
+/// reference. Otherwise it's just text (like in `{}` or a
+/// json snippet).
+fn is_reference(name: &str) -> bool {
+  let name = name
+    .strip_prefix("set:")
+    .or(name.strip_prefix("counter:"))
+    .or(name.strip_prefix("counter2:"))
+    .map(|name| name.split(':').next().unwrap_or(""))
+    .map(|name| name.trim_matches('!'))
+    .unwrap_or(name);
+
+  let mut chars = name.chars();
+  match chars.next() {
+    Some(c) if c.is_alphanumeric() || c == '_' => {
+      chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    }
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn substitute_references() {
+    let mut attributes = DocumentAttributes::new();
+    attributes.define("version", "1.2");
+    attributes.define("name", "lisa {version}");
+
+    assert_eq!(attributes.substitute("no references"), None);
+    assert_eq!(attributes.substitute("fn main() {}"), None);
+    assert_eq!(
+      attributes.substitute("This is {name}, not {unknown}"),
+      Some("This is lisa 1.2, not {unknown}".to_string())
+    );
+    assert_eq!(
+      attributes.substitute("Escaped \\{version}"),
+      Some("Escaped {version}".to_string())
+    );
+  }
+
+  #[test]
+  fn set_and_counter() {
+    let mut attributes = DocumentAttributes::new();
+
+    assert_eq!(
+      attributes.substitute("{set:os:linux}{os}"),
+      Some("linux".to_string())
+    );
+    assert_eq!(
+      attributes.substitute("{counter:step} {counter:step} {counter2:step}{step}"),
+      Some("1 2 3".to_string())
+    );
+    assert_eq!(
+      attributes.substitute("{counter:letter:A}{counter:letter}"),
+      Some("AB".to_string())
+    );
+    assert_eq!(
+      attributes.substitute("{set:os!}{os}"),
+      Some("{os}".to_string())
+    );
+  }
+}
@@ -2,6 +2,7 @@ pub use crate::ast::*;
 use crate::options::Opts;
 use crate::util::{Env};
 use crate::Result;
+use serde::Deserialize;
 
 pub struct JsonReader {}
 
@@ -9,6 +10,15 @@ impl JsonReader {
   pub fn new() -> Self {
     JsonReader {}
   }
+
+  /// Reads an AST which doesn't borrow from the input
+  pub fn parse_owned(&self, input: &str) -> Result<OwnedAST> {
+    let mut deserializer = serde_json::Deserializer::from_reader(input.as_bytes());
+    let ast = AST::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+
+    Ok(ast)
+  }
 }
 
 impl crate::Reader for JsonReader {
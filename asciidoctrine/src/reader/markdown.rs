@@ -0,0 +1,603 @@
+pub use crate::ast::*;
+use crate::options::Opts;
+use crate::reader::asciidoc::{from_span, move_spans, nest_sections};
+use crate::reader::preprocessor::LineMap;
+use crate::util::Env;
+use crate::Result;
+use pest::iterators::Pair;
+use pest::Parser;
+
+/// Reads CommonMark documents (with the tables of GitHub
+/// flavored markdown) into the same AST as asciidoc documents
+pub struct MarkdownReader {}
+
+impl MarkdownReader {
+  pub fn new() -> Self {
+    MarkdownReader {}
+  }
+}
+
+impl Default for MarkdownReader {
+  fn default() -> Self {
+    MarkdownReader::new()
+  }
+}
+
+impl crate::Reader for MarkdownReader {
+  fn parse<'a>(&self, input: &'a str, args: &Opts, _env: &mut Env) -> Result<AST<'a>> {
+    let mut attributes = Vec::new();
+    if let Some(path) = args.input.as_ref().and_then(|path| path.to_str()) {
+      attributes.push(Attribute {
+        key: "source".to_string(),
+        value: AttributeValue::String(path.to_string()),
+      });
+    }
+
+    let mut elements = parse_blocks(input)?;
+    if args.sections {
+      elements = nest_sections(elements, &input.into());
+    }
+
+    Ok(AST {
+      content: input.into(),
+      elements,
+      attributes,
+    })
+  }
+}
+
+#[derive(Parser, Debug, Copy, Clone)]
+#[grammar = "reader/markdown.pest"]
+pub struct MarkdownParser;
+
+fn parse_blocks<'a>(input: &'a str) -> Result<Vec<ElementSpan<'a>>> {
+  let ast = MarkdownParser::parse(Rule::markdown, input)?;
+
+  let mut elements = Vec::new();
+  for element in ast {
+    elements.extend(process_block(element)?);
+  }
+
+  Ok(elements)
+}
+
+fn process_block<'a>(element: Pair<'a, Rule>) -> Result<Vec<ElementSpan<'a>>> {
+  let mut base = from_span(element.as_span(), Element::Paragraph);
+
+  match element.as_rule() {
+    Rule::atx_heading => {
+      // An empty heading (`#`) has an empty name
+      let mut name = "";
+      for subelement in element.into_inner() {
+        match subelement.as_rule() {
+          Rule::atx_marks => {
+            base.element = Element::Title {
+              level: subelement.as_str().len() as u32,
+            };
+          }
+          Rule::heading_text => name = subelement.as_str(),
+          _ => (),
+        }
+      }
+      base.attributes.push(Attribute {
+        key: "name".to_string(),
+        value: AttributeValue::Ref(name),
+      });
+    }
+    Rule::setext_heading => {
+      for subelement in element.into_inner() {
+        match subelement.as_rule() {
+          Rule::setext_text => base.attributes.push(Attribute {
+            key: "name".to_string(),
+            value: AttributeValue::Ref(subelement.as_str().trim()),
+          }),
+          Rule::setext_underline => {
+            let level = match subelement.as_str().starts_with('=') {
+              true => 1,
+              false => 2,
+            };
+            base.element = Element::Title { level };
+          }
+          _ => (),
+        }
+      }
+    }
+    // TODO Trennlinien im AST abbilden
+    Rule::thematic_break => return Ok(Vec::new()),
+    Rule::fenced_code => base = process_fenced_code(element, base),
+    Rule::indented_code => {
+      base.element = Element::TypedBlock {
+        kind: BlockType::Listing,
+      };
+      let lines: Vec<_> = element
+        .as_str()
+        .lines()
+        .map(|line| strip_indentation(line, 4))
+        .collect();
+      base.attributes.push(Attribute {
+        key: "content".to_string(),
+        value: AttributeValue::String(lines.join("\n").trim_end().to_string()),
+      });
+    }
+    Rule::block_quote => {
+      base.element = Element::TypedBlock {
+        kind: BlockType::Quote,
+      };
+      let lines = split_lines(&element)
+        .into_iter()
+        .map(|(start, line)| (start, line, quote_mark_length(line)))
+        .collect::<Vec<_>>();
+      base.children = parse_container(&element, &lines)?;
+    }
+    Rule::list => return process_list(element),
+    Rule::table => base = process_table(element, base)?,
+    Rule::paragraph => {
+      if let Some(text) = element.into_inner().next() {
+        base = from_span(text.as_span(), Element::Paragraph);
+        base.children = parse_inlines(text.as_span())?;
+      }
+    }
+    _ => return Ok(Vec::new()),
+  }
+
+  Ok(vec![base])
+}
+
+/// A fenced code block is a source block. The words of its info
+/// string are its positional attributes (```rust save` gives
+/// `[source,rust,save]`). Words like `key=value` are named
+/// attributes and `#name` is the anchor of the block.
+fn process_fenced_code<'a>(element: Pair<'a, Rule>, mut base: ElementSpan<'a>) -> ElementSpan<'a> {
+  base.element = Element::TypedBlock {
+    kind: BlockType::Listing,
+  };
+  base.positional_attributes.push(AttributeValue::Ref("source"));
+  let content = element.clone().into_inner().find(|sub| sub.as_rule() == Rule::code_content);
+  base.attributes.push(Attribute {
+    key: "content".to_string(),
+    value: AttributeValue::Ref(content.map_or("", |content| content.as_str())),
+  });
+
+  if let Some(info) = element.into_inner().find(|sub| sub.as_rule() == Rule::info_string) {
+    for word in info.as_str().split_whitespace() {
+      if let Some(anchor) = word.strip_prefix('#') {
+        base.attributes.push(Attribute {
+          key: "anchor".to_string(),
+          value: AttributeValue::Ref(anchor),
+        });
+      } else if let Some((key, value)) = word.split_once('=') {
+        base.attributes.push(Attribute {
+          key: key.to_string(),
+          value: AttributeValue::Ref(value.trim_matches('"')),
+        });
+      } else {
+        base.positional_attributes.push(AttributeValue::Ref(word));
+      }
+    }
+  }
+
+  base
+}
+
+/// The kind of the marks of a list. A list ends where
+/// the items start to use other marks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ListMark {
+  Bullet(char),
+  Number(char),
+}
+
+struct ListItem<'a> {
+  mark: ListMark,
+  /// The lines with their start in the list and the
+  /// number of bytes in front of the content
+  lines: Vec<(usize, &'a str, usize)>,
+  /// The column where the content of the item starts
+  indent: usize,
+}
+
+/// Finds the mark of a list item at the start of the line. Returns
+/// the mark and the number of bytes in front of the content.
+fn list_mark(line: &str) -> Option<(ListMark, usize)> {
+  let indent = line.len() - line.trim_start_matches(' ').len();
+  if indent > 3 {
+    return None;
+  }
+  let rest = &line[indent..];
+  let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+  let (mark, length) = match rest.chars().next()? {
+    c @ ('-' | '*' | '+') => (ListMark::Bullet(c), 1),
+    _ if (1..=9).contains(&digits) => match rest[digits..].chars().next()? {
+      c @ ('.' | ')') => (ListMark::Number(c), digits + 1),
+      _ => return None,
+    },
+    _ => return None,
+  };
+
+  let after = &rest[length..];
+  let spaces = after.len() - after.trim_start_matches(' ').len();
+  let spaces = match spaces {
+    // The content starts on the next line or it
+    // is indented code (which keeps all but a space)
+    _ if after.trim().is_empty() => spaces.min(1),
+    0 if !after.is_empty() => return None,
+    5.. => 1,
+    spaces => spaces,
+  };
+
+  Some((mark, indent + length + spaces))
+}
+
+/// Splits a list into its items and the items into lists
+/// with the same kind of marks. Lines indented up to the
+/// content of the item belong to it (like nested lists).
+fn process_list<'a>(element: Pair<'a, Rule>) -> Result<Vec<ElementSpan<'a>>> {
+  let mut items: Vec<ListItem> = Vec::new();
+
+  for (start, line) in split_lines(&element) {
+    // Only spaces and tabs indent a line, other whitespace is
+    // content (and could be cut in half by counting bytes)
+    let indentation = line.len() - line.trim_start_matches([' ', '\t']).len();
+    match items.last_mut() {
+      Some(item) if indentation == line.len() => {
+        item.lines.push((start, line, line.len().min(item.indent)));
+      }
+      Some(item) if indentation >= item.indent => {
+        item.lines.push((start, line, item.indent));
+      }
+      _ => match list_mark(line) {
+        Some((mark, indent)) => items.push(ListItem {
+          mark,
+          lines: vec![(start, line, indent.min(line.len()))],
+          indent,
+        }),
+        // A lazy line continues the paragraph of the item
+        None => {
+          if let Some(item) = items.last_mut() {
+            item.lines.push((start, line, indentation));
+          }
+        }
+      },
+    }
+  }
+
+  let mut lists: Vec<ElementSpan> = Vec::new();
+  let mut mark = None;
+  for item in items {
+    // Blank lines at the end belong to the list
+    let content_end = item
+      .lines
+      .iter()
+      .rev()
+      .find(|(_, line, _)| !line.trim().is_empty())
+      .map(|(start, line, _)| start + line.len())
+      .unwrap_or_default();
+    let mut base = from_part(&element, item.lines[0].0, content_end, Element::ListItem(1));
+    base.children = parse_container(&element, &item.lines)?;
+
+    match lists.last_mut() {
+      Some(list) if mark == Some(item.mark) => {
+        list.end = base.end;
+        list.end_line = base.end_line;
+        list.end_col = base.end_col;
+        list.content = element.as_str()[list.start - element.as_span().start()..content_end].into();
+        list.children.push(base);
+      }
+      _ => {
+        let kind = match item.mark {
+          ListMark::Bullet(_) => ListType::Bullet,
+          ListMark::Number(_) => ListType::Number,
+        };
+        let mut list = from_part(&element, item.lines[0].0, content_end, Element::List(kind));
+        list.children.push(base);
+        lists.push(list);
+      }
+    }
+    mark = Some(item.mark);
+  }
+
+  Ok(lists)
+}
+
+/// Reads a table with its header, the alignment of its columns
+/// and the rows of its body
+fn process_table<'a>(element: Pair<'a, Rule>, mut base: ElementSpan<'a>) -> Result<ElementSpan<'a>> {
+  base.element = Element::Table;
+
+  let lines = split_lines(&element);
+  let alignments: Vec<_> = match lines.get(1) {
+    Some((start, line)) => split_cells(line, *start)
+      .into_iter()
+      .map(|(start, end)| {
+        let cell = &element.as_str()[start..end];
+        match (cell.starts_with(':'), cell.ends_with(':')) {
+          (true, true) => "center",
+          (false, true) => "right",
+          _ => "left",
+        }
+      })
+      .collect(),
+    None => Vec::new(),
+  };
+
+  let rows = lines.iter().enumerate().filter(|(number, _)| *number != 1);
+  for (number, (start, line)) in rows {
+    let mut row = from_part(&element, *start, start + line.trim_end().len(), Element::TableRow);
+    let section = match number {
+      0 => "header",
+      _ => "body",
+    };
+    row.attributes.push(Attribute {
+      key: "section".to_string(),
+      value: AttributeValue::Ref(section),
+    });
+
+    // Missing cells are left empty and cells which don't fit
+    // into the columns of the header are left out
+    let row_end = start + line.trim_end().len();
+    let mut cells = split_cells(line, *start).into_iter();
+    for halign in alignments.iter() {
+      let (start, end) = cells.next().unwrap_or((row_end, row_end));
+      let mut cell = from_part(&element, start, end, Element::TableCell);
+      let style = match number {
+        0 => "header",
+        _ => "default",
+      };
+      for (key, value) in [("style", style), ("halign", *halign), ("valign", "top")] {
+        cell.attributes.push(Attribute {
+          key: key.to_string(),
+          value: AttributeValue::Ref(value),
+        });
+      }
+      if start < end {
+        let text = pest::Span::new(element.as_str(), start, end).expect("the cell should be inside of the table");
+        let mut paragraph = from_part(&element, start, end, Element::Paragraph);
+        let mut children = parse_inlines(text)?;
+        let (line, col) = element.as_span().start_pos().line_col();
+        move_spans(&mut children, element.as_span().start(), line, col);
+        paragraph.children = children;
+        cell.children.push(paragraph);
+      }
+      row.children.push(cell);
+    }
+    base.children.push(row);
+  }
+
+  let columns = alignments.len().max(1);
+  let width = ((100.0 / columns as f64) * 10000.0).round() / 10000.0;
+  let mut widths = vec![width.to_string(); columns - 1];
+  widths.push((((100.0 - width * (columns - 1) as f64) * 10000.0).round() / 10000.0).to_string());
+  base.attributes.push(Attribute {
+    key: "colwidths".to_string(),
+    value: AttributeValue::String(widths.join(",")),
+  });
+
+  Ok(base)
+}
+
+/// Finds the cells of a table row (at `start` of the table) by
+/// its unescaped pipes. Returns the trimmed cells in the table.
+fn split_cells(line: &str, start: usize) -> Vec<(usize, usize)> {
+  let mut cells = Vec::new();
+  let mut cell_start = 0;
+  let mut escaped = false;
+  for (position, c) in line.char_indices() {
+    match c {
+      '|' if !escaped => {
+        cells.push((cell_start, position));
+        cell_start = position + 1;
+      }
+      _ => (),
+    }
+    escaped = c == '\\' && !escaped;
+  }
+  cells.push((cell_start, line.len()));
+
+  // The pipes at the start and the end of the row are optional
+  if line[cells[0].0..cells[0].1].trim().is_empty() && cells.len() > 1 {
+    cells.remove(0);
+  }
+  if let Some((cell_start, cell_end)) = cells.last() {
+    if line[*cell_start..*cell_end].trim().is_empty() && cells.len() > 1 {
+      cells.pop();
+    }
+  }
+
+  cells
+    .into_iter()
+    .map(|(cell_start, cell_end)| {
+      let cell = &line[cell_start..cell_end];
+      let cell_start = cell_start + (cell.len() - cell.trim_start().len());
+      (start + cell_start, start + cell_start + cell.trim().len())
+    })
+    .collect()
+}
+
+/// Parses the inline elements of a text (e.g. a paragraph)
+fn parse_inlines(text: pest::Span) -> Result<Vec<ElementSpan>> {
+  let ast = MarkdownParser::parse(Rule::inline_content, text.as_str())?;
+
+  let mut elements: Vec<_> = ast
+    .filter(|element| element.as_rule() != Rule::EOI)
+    .map(process_inline)
+    .collect();
+  let (line, col) = text.start_pos().line_col();
+  move_spans(&mut elements, text.start(), line, col);
+
+  Ok(elements)
+}
+
+fn process_inline(element: Pair<Rule>) -> ElementSpan {
+  let mut base = from_span(element.as_span(), Element::Text);
+
+  let element = match element.as_rule() {
+    Rule::inline => match element.into_inner().next() {
+      Some(element) => element,
+      None => return base,
+    },
+    _ => return base,
+  };
+
+  match element.as_rule() {
+    Rule::hard_break => base.element = Element::LineBreak,
+    Rule::escaped => base.attributes.push(Attribute {
+      key: "content".to_string(),
+      value: AttributeValue::Ref(&element.as_str()[1..]),
+    }),
+    Rule::code_span => {
+      base.element = Element::Styled;
+      base.attributes.push(Attribute {
+        key: "style".to_string(),
+        value: AttributeValue::Ref("monospaced"),
+      });
+      if let Some(code) = element.into_inner().find(|sub| sub.as_rule() == Rule::code_text) {
+        // A space on both sides is left out
+        let mut text = from_span(code.as_span(), Element::Text);
+        let content = code.as_str();
+        if content.len() > 2 && content.starts_with(' ') && content.ends_with(' ') {
+          text.attributes.push(Attribute {
+            key: "content".to_string(),
+            value: AttributeValue::Ref(&content[1..content.len() - 1]),
+          });
+        }
+        base.attributes.push(Attribute {
+          key: "content".to_string(),
+          value: AttributeValue::Ref(content),
+        });
+        base.children.push(text);
+      }
+    }
+    Rule::strong | Rule::emphasis => {
+      let style = match element.as_rule() {
+        Rule::strong => "strong",
+        _ => "em",
+      };
+      base.element = Element::Styled;
+      base.attributes.push(Attribute {
+        key: "style".to_string(),
+        value: AttributeValue::Ref(style),
+      });
+      if let Some(content) = element.into_inner().find(|sub| sub.as_rule() == Rule::styled_content) {
+        base.attributes.push(Attribute {
+          key: "content".to_string(),
+          value: AttributeValue::Ref(content.as_str()),
+        });
+        base.children = content.into_inner().map(process_inline).collect();
+      }
+    }
+    Rule::link | Rule::image => {
+      base.element = match element.as_rule() {
+        Rule::link => Element::Link,
+        _ => Element::InlineImage,
+      };
+      let target = match base.element {
+        Element::Link => "url",
+        _ => "path",
+      };
+      for subelement in element.into_inner() {
+        match subelement.as_rule() {
+          Rule::link_text if !subelement.as_str().is_empty() => {
+            base.positional_attributes.push(AttributeValue::Ref(subelement.as_str()));
+          }
+          Rule::link_destination => base.attributes.push(Attribute {
+            key: target.to_string(),
+            value: AttributeValue::Ref(subelement.as_str().trim_start_matches('<').trim_end_matches('>')),
+          }),
+          Rule::link_title => {
+            let title = subelement.as_str();
+            base.attributes.push(Attribute {
+              key: "title".to_string(),
+              value: AttributeValue::Ref(&title[1..title.len() - 1]),
+            });
+          }
+          _ => (),
+        }
+      }
+    }
+    Rule::autolink => {
+      base.element = Element::Link;
+      if let Some(url) = element.into_inner().next() {
+        base.attributes.push(Attribute {
+          key: "url".to_string(),
+          value: AttributeValue::Ref(url.as_str()),
+        });
+        base.positional_attributes.push(AttributeValue::Ref(url.as_str()));
+      }
+    }
+    _ => (),
+  }
+
+  base
+}
+
+/// Parses the blocks of a container (a quote or a list item)
+/// from its lines without their marks. The lines are given with
+/// their start in the element and the number of bytes to leave out.
+fn parse_container<'a>(element: &Pair<'a, Rule>, lines: &[(usize, &str, usize)]) -> Result<Vec<ElementSpan<'a>>> {
+  let span = element.as_span();
+  let (start_line, start_col) = span.start_pos().line_col();
+  let (end_line, end_col) = span.end_pos().line_col();
+
+  let mut content = String::new();
+  let mut lines_map = LineMap::new((span.end(), end_line, end_col));
+  for (start, line, skip) in lines.iter() {
+    let number = start_line + element.as_str()[..*start].matches('\n').count();
+    let col_shift = line[..*skip].chars().count() + if *start == 0 { start_col - 1 } else { 0 };
+    lines_map.push_line(content.len(), span.start() + start + skip, number, col_shift);
+    content.push_str(&line[*skip..]);
+    content.push('\n');
+  }
+
+  let mut elements = parse_blocks(&content)?;
+  lines_map.remap(&mut elements);
+
+  // The text without the marks only lives here
+  Ok(elements.into_iter().map(ElementSpan::into_owned).collect())
+}
+
+/// Gets the lines of an element with their start in it
+fn split_lines<'a>(element: &Pair<'a, Rule>) -> Vec<(usize, &'a str)> {
+  let mut start = 0;
+  element
+    .as_str()
+    .split('\n')
+    .map(|line| {
+      let line_start = start;
+      start += line.len() + 1;
+      (line_start, line.trim_end_matches('\r'))
+    })
+    .collect()
+}
+
+/// The length of the mark of a quote (`> `) at the start of a line
+fn quote_mark_length(line: &str) -> usize {
+  let indent = line.len() - line.trim_start_matches(' ').len();
+  match line[indent..].strip_prefix('>') {
+    Some(rest) if rest.starts_with(' ') => indent + 2,
+    Some(_) => indent + 1,
+    // A line continuing the paragraph
+    None => 0,
+  }
+}
+
+fn strip_indentation(line: &str, width: usize) -> &str {
+  match line.strip_prefix('\t') {
+    Some(line) => line,
+    None => {
+      let spaces = line.len() - line.trim_start_matches(' ').len();
+      &line[spaces.min(width)..]
+    }
+  }
+}
+
+/// Creates an element for a part (`start..end`) of another one
+fn from_part<'a>(element: &Pair<'a, Rule>, start: usize, end: usize, kind: Element<'a>) -> ElementSpan<'a> {
+  let span = element.as_span();
+  let part = pest::Span::new(element.as_str(), start, end).expect("the part should be inside of the element");
+  let mut base = from_span(part, kind);
+  let (line, col) = span.start_pos().line_col();
+  move_spans(std::slice::from_mut(&mut base), span.start(), line, col);
+
+  base
+}
@@ -1,2 +1,5 @@
 pub mod asciidoc;
+pub mod attributes;
 pub mod json;
+pub mod markdown;
+pub mod preprocessor;
@@ -0,0 +1,378 @@
+use crate::ast::*;
+use crate::reader::attributes::DocumentAttributes;
+use std::cmp::Ordering;
+
+/// A document after the conditional preprocessor directives
+/// (`ifdef`, `ifndef`, `ifeval` and `endif`) were applied
+pub struct Preprocessed {
+  pub content: String,
+  pub lines: LineMap,
+}
+
+/// Maps the positions in a preprocessed text back to the
+/// positions in its source
+pub struct LineMap {
+  lines: Vec<MappedLine>,
+  /// The position behind the last character of the source
+  /// (offset, line, column)
+  end: (usize, usize, usize),
+}
+
+struct MappedLine {
+  /// The start of the line in the preprocessed text
+  start: usize,
+  /// The start of the line in the source
+  source_start: usize,
+  source_line: usize,
+  /// The number of characters removed at the start of the
+  /// line (e.g. by a single line `ifdef::name[text]`)
+  col_shift: usize,
+}
+
+struct Condition<'i> {
+  name: &'i str,
+  active: bool,
+}
+
+/// Applies all conditional directives of the input
+///
+/// The attributes are evaluated as they are known at the
+/// position of the directive, so attribute entries above it
+/// are taken into account. Returns `None` if the input has no
+/// directives so it can be parsed as it is.
+pub fn preprocess(input: &str, attributes: &DocumentAttributes) -> Option<Preprocessed> {
+  if !input.lines().any(|line| parse_directive(line).is_some()) {
+    return None;
+  }
+
+  let mut attributes = attributes.clone();
+  let mut content = String::with_capacity(input.len());
+  let mut lines = Vec::new();
+  let mut conditions: Vec<Condition> = Vec::new();
+  let mut source_start = 0;
+
+  for (number, raw_line) in input.split_inclusive('\n').enumerate() {
+    let line = raw_line.trim_end_matches(&['\n', '\r'][..]);
+    let ending = &raw_line[line.len()..];
+    let skipping = conditions.iter().any(|condition| !condition.active);
+
+    // Keeps the part of the line starting at byte `from`
+    let mut keep = |from: usize, to: usize| {
+      lines.push(MappedLine {
+        start: content.len(),
+        source_start: source_start + from,
+        source_line: number + 1,
+        col_shift: line[..from].chars().count(),
+      });
+      content.push_str(&line[from..to]);
+      content.push_str(ending);
+    };
+
+    match parse_directive(line) {
+      Some(directive) if directive.escaped => {
+        if !skipping {
+          keep(1, line.len());
+        }
+      }
+      Some(directive) if directive.kind == "endif" => match conditions.pop() {
+        Some(condition) => {
+          if !directive.target.is_empty() && directive.target != condition.name {
+            warn!(
+              "line {}: endif::{}[] closes ifdef::{}[]",
+              number + 1,
+              directive.target,
+              condition.name
+            );
+          }
+        }
+        None => warn!("line {}: endif::{}[] without if", number + 1, directive.target),
+      },
+      Some(directive) => {
+        let active = !skipping && evaluate(&directive, &mut attributes);
+
+        if directive.kind != "ifeval" && !directive.text.is_empty() {
+          // The single line form doesn't need an endif
+          if active {
+            let end = line.trim_end().len() - 1;
+            keep(end - directive.text.len(), end);
+          }
+        } else {
+          conditions.push(Condition {
+            name: directive.target,
+            active,
+          });
+        }
+      }
+      None => {
+        if !skipping {
+          if let Some((key, value)) = parse_attribute_entry(line) {
+            attributes.define(key, value);
+          }
+          keep(0, line.len());
+        }
+      }
+    }
+
+    source_start += raw_line.len();
+  }
+
+  if let Some(condition) = conditions.last() {
+    warn!("unterminated conditional ifdef::{}[]", condition.name);
+  }
+
+  Some(Preprocessed {
+    content,
+    lines: LineMap {
+      lines,
+      end: end_position(input),
+    },
+  })
+}
+
+impl LineMap {
+  /// Creates an empty map for a text which ends at the
+  /// given position of the source (offset, line, column)
+  pub fn new(end: (usize, usize, usize)) -> Self {
+    LineMap {
+      lines: Vec::new(),
+      end,
+    }
+  }
+
+  /// Adds the next line of the text. It starts at `start` in the
+  /// text and at `source_start` in the source. `col_shift` is the
+  /// number of characters of the source line in front of it.
+  pub fn push_line(&mut self, start: usize, source_start: usize, source_line: usize, col_shift: usize) {
+    self.lines.push(MappedLine {
+      start,
+      source_start,
+      source_line,
+      col_shift,
+    });
+  }
+
+  /// Moves the spans of the elements (parsed from the preprocessed
+  /// text) to their positions in the source
+  pub fn remap(&self, elements: &mut [ElementSpan]) {
+    for element in elements.iter_mut() {
+      let (start, start_line, start_col) =
+        self.position(element.start, element.start_line, element.start_col);
+      let (end, end_line, end_col) = self.position(element.end, element.end_line, element.end_col);
+
+      element.start = start;
+      element.start_line = start_line;
+      element.start_col = start_col;
+      element.end = end;
+      element.end_line = end_line;
+      element.end_col = end_col;
+
+      self.remap(&mut element.children);
+    }
+  }
+
+  fn position(&self, offset: usize, line: usize, col: usize) -> (usize, usize, usize) {
+    match self.lines.get(line.saturating_sub(1)) {
+      Some(mapped) => (
+        mapped.source_start + offset.saturating_sub(mapped.start),
+        mapped.source_line,
+        col + mapped.col_shift,
+      ),
+      None => self.end,
+    }
+  }
+}
+
+struct Directive<'i> {
+  escaped: bool,
+  kind: &'i str,
+  target: &'i str,
+  text: &'i str,
+}
+
+fn parse_directive(line: &str) -> Option<Directive<'_>> {
+  let line = line.trim_end();
+  let (escaped, directive) = match line.strip_prefix('\\') {
+    Some(directive) => (true, directive),
+    None => (false, line),
+  };
+
+  let (kind, rest) = directive.split_once("::")?;
+  if !matches!(kind, "ifdef" | "ifndef" | "ifeval" | "endif") {
+    return None;
+  }
+  let (target, text) = rest.split_once('[')?;
+  let text = text.strip_suffix(']')?;
+
+  if target.contains(char::is_whitespace) || (kind == "ifeval" && !target.is_empty()) {
+    return None;
+  }
+
+  Some(Directive {
+    escaped,
+    kind,
+    target,
+    text,
+  })
+}
+
+fn parse_attribute_entry(line: &str) -> Option<(&str, &str)> {
+  let line = line.strip_prefix(':')?;
+  let (key, value) = line.split_once(':')?;
+
+  if key.is_empty() || key.contains(char::is_whitespace) {
+    return None;
+  }
+
+  Some((key, value.trim()))
+}
+
+fn evaluate(directive: &Directive, attributes: &mut DocumentAttributes) -> bool {
+  match directive.kind {
+    "ifdef" | "ifndef" => {
+      let target = directive.target;
+      let defined = if target.contains(',') {
+        target.split(',').any(|name| attributes.is_set(name))
+      } else {
+        target.split('+').all(|name| attributes.is_set(name))
+      };
+
+      (directive.kind == "ifdef") == defined
+    }
+    "ifeval" => match evaluate_expression(directive.text, attributes) {
+      Some(result) => result,
+      None => {
+        warn!("malformed ifeval expression `{}`", directive.text);
+        false
+      }
+    },
+    _ => false,
+  }
+}
+
+#[derive(Debug)]
+enum Value {
+  Number(f64),
+  Bool(bool),
+  Text(String),
+}
+
+fn evaluate_expression(expression: &str, attributes: &mut DocumentAttributes) -> Option<bool> {
+  let (position, operator) = ["==", "!=", "<=", ">=", "<", ">"]
+    .iter()
+    .filter_map(|operator| expression.find(operator).map(|position| (position, *operator)))
+    .min_by_key(|(position, operator)| (*position, usize::MAX - operator.len()))?;
+
+  let lhs = evaluate_value(&expression[..position], attributes);
+  let rhs = evaluate_value(&expression[position + operator.len()..], attributes);
+
+  let ordering = match (&lhs, &rhs) {
+    (Value::Number(lhs), Value::Number(rhs)) => lhs.partial_cmp(rhs),
+    (Value::Bool(lhs), Value::Bool(rhs)) => lhs.partial_cmp(rhs),
+    (lhs, rhs) => value_to_string(lhs).partial_cmp(&value_to_string(rhs)),
+  }?;
+
+  Some(match operator {
+    "==" => ordering == Ordering::Equal,
+    "!=" => ordering != Ordering::Equal,
+    "<=" => ordering != Ordering::Greater,
+    ">=" => ordering != Ordering::Less,
+    "<" => ordering == Ordering::Less,
+    _ => ordering == Ordering::Greater,
+  })
+}
+
+fn evaluate_value(input: &str, attributes: &mut DocumentAttributes) -> Value {
+  let input = input.trim();
+  let value = attributes
+    .substitute(input)
+    .unwrap_or_else(|| input.to_string());
+
+  for quote in ['"', '\''] {
+    if let Some(text) = value
+      .strip_prefix(quote)
+      .and_then(|value| value.strip_suffix(quote))
+    {
+      return Value::Text(text.to_string());
+    }
+  }
+
+  match value.as_str() {
+    "true" => Value::Bool(true),
+    "false" => Value::Bool(false),
+    // Missing attributes are treated as empty
+    value if value.starts_with('{') && value.ends_with('}') => Value::Text(String::new()),
+    value => match value.parse::<f64>() {
+      Ok(number) => Value::Number(number),
+      Err(_) => Value::Text(value.to_string()),
+    },
+  }
+}
+
+fn value_to_string(value: &Value) -> String {
+  match value {
+    Value::Number(number) => number.to_string(),
+    Value::Bool(value) => value.to_string(),
+    Value::Text(text) => text.clone(),
+  }
+}
+
+fn end_position(input: &str) -> (usize, usize, usize) {
+  let line = input.matches('\n').count() + 1;
+  let col = match input.rfind('\n') {
+    Some(position) => input[position + 1..].chars().count() + 1,
+    None => input.chars().count() + 1,
+  };
+
+  (input.len(), line, col)
+}
+
+#[cfg(test)]
+mod test {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  fn attributes(defines: &[(&str, &str)]) -> DocumentAttributes {
+    let mut attributes = DocumentAttributes::new();
+    for (key, value) in defines {
+      attributes.define(key, value);
+    }
+    attributes
+  }
+
+  #[test]
+  fn no_directives() {
+    assert!(preprocess("= Title\n\nSome text\n", &attributes(&[])).is_none());
+  }
+
+  #[test]
+  fn conditional_blocks() {
+    let input = "start\nifdef::linux[]\nlinux\nifndef::windows[]\nnot windows\nendif::windows[]\nendif::linux[]\nifdef::windows[]\nwindows\nendif::[]\nifdef::linux,windows[any]\nifdef::linux+windows[all]\nend\n";
+
+    let output = preprocess(input, &attributes(&[("linux", "")])).unwrap();
+    assert_eq!(output.content, "start\nlinux\nnot windows\nany\nend\n");
+
+    let output = preprocess(input, &attributes(&[("windows", "")])).unwrap();
+    assert_eq!(output.content, "start\nwindows\nany\nend\n");
+  }
+
+  #[test]
+  fn evaluated_conditions() {
+    let input = "ifeval::[{level} > 2]\nhigh\nendif::[]\nifeval::[\"{target}\" == \"linux\"]\nlinux\nendif::[]\n:level: 1\nifeval::[{level} < 2]\nlow\nendif::[]\n\\ifeval::[escaped]\n";
+
+    let output = preprocess(input, &attributes(&[("level", "3"), ("target", "linux")])).unwrap();
+    assert_eq!(output.content, "high\nlinux\n:level: 1\nlow\nifeval::[escaped]\n");
+  }
+
+  #[test]
+  fn map_positions_to_source() {
+    let input = "ifdef::linux[]\nfirst\nendif::[]\nifdef::linux[second]\n";
+    let output = preprocess(input, &attributes(&[("linux", "")])).unwrap();
+    assert_eq!(output.content, "first\nsecond\n");
+
+    // "second" starts at offset 6 in the preprocessed text
+    assert_eq!(output.lines.position(6, 2, 1), (44, 4, 14));
+    assert_eq!(output.lines.position(0, 1, 1), (15, 2, 1));
+    assert_eq!(output.lines.position(13, 3, 1), (input.len(), 5, 1));
+  }
+}
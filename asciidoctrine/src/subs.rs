@@ -0,0 +1,241 @@
+use crate::ast::*;
+
+/// A step of the substitutions applied to the text of a block
+///
+/// Like in asciidoctor the steps run in the order `SpecialChars`,
+/// `Quotes`, `Attributes`, `Replacements`, `Macros` and
+/// `PostReplacements`. Quotes, macros and hard line breaks are
+/// found by the parser and are only turned back into text if
+/// their step is switched off. Special characters are escaped by
+/// the writers (text without this step is a `Passthrough`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Substitution {
+  SpecialChars,
+  Quotes,
+  Attributes,
+  Replacements,
+  Macros,
+  PostReplacements,
+}
+
+pub const NORMAL: [Substitution; 6] = [
+  Substitution::SpecialChars,
+  Substitution::Quotes,
+  Substitution::Attributes,
+  Substitution::Replacements,
+  Substitution::Macros,
+  Substitution::PostReplacements,
+];
+
+pub const VERBATIM: [Substitution; 1] = [Substitution::SpecialChars];
+
+impl Substitution {
+  /// Gets the steps of a name used in a `subs` attribute
+  /// (a single step or a group like `normal`)
+  pub fn from_name(name: &str) -> Option<Vec<Self>> {
+    let steps = match name {
+      "none" => vec![],
+      "normal" | "n" => NORMAL.to_vec(),
+      "verbatim" | "v" => VERBATIM.to_vec(),
+      "specialchars" | "specialcharacters" | "c" => vec![Substitution::SpecialChars],
+      "quotes" | "q" => vec![Substitution::Quotes],
+      "attributes" | "a" => vec![Substitution::Attributes],
+      "replacements" | "r" => vec![Substitution::Replacements],
+      "macros" | "m" => vec![Substitution::Macros],
+      "post_replacements" | "p" => vec![Substitution::PostReplacements],
+      _ => return None,
+    };
+
+    Some(steps)
+  }
+}
+
+/// The substitutions of an element without a `subs` attribute
+pub fn defaults(element: &Element) -> &'static [Substitution] {
+  match element {
+    Element::TypedBlock {
+      kind: BlockType::Listing,
+    } => &VERBATIM,
+    Element::TypedBlock {
+      kind: BlockType::Passtrough | BlockType::Comment,
+    } => &[],
+    _ => &NORMAL,
+  }
+}
+
+/// Gets the substitutions of an element from its `subs`
+/// attribute (e.g. `subs="attributes+"`, `subs="-replacements"`
+/// or `subs="quotes,macros"`) and the given defaults.
+pub fn resolve(subs: Option<&str>, defaults: &[Substitution]) -> Vec<Substitution> {
+  let subs = match subs {
+    Some(subs) => subs,
+    None => return defaults.to_vec(),
+  };
+  let entries: Vec<_> = subs.split(',').map(str::trim).filter(|entry| !entry.is_empty()).collect();
+  let incremental = entries
+    .iter()
+    .any(|entry| entry.starts_with('+') || entry.starts_with('-') || entry.ends_with('+'));
+
+  let mut result = match incremental {
+    true => defaults.to_vec(),
+    false => Vec::new(),
+  };
+  for entry in entries {
+    if let Some(name) = entry.strip_prefix('-') {
+      let steps = Substitution::from_name(name).unwrap_or_default();
+      result.retain(|step| !steps.contains(step));
+    } else if let Some(name) = entry.strip_suffix('+') {
+      // `name+` puts the steps in front
+      let steps = Substitution::from_name(name).unwrap_or_default();
+      result.retain(|step| !steps.contains(step));
+      result.splice(0..0, steps);
+    } else {
+      let name = entry.strip_prefix('+').unwrap_or(entry);
+      for step in Substitution::from_name(name).unwrap_or_default() {
+        if !result.contains(&step) {
+          result.push(step);
+        }
+      }
+    }
+  }
+
+  result
+}
+
+/// Gets the substitutions of an element
+pub fn of(element: &ElementSpan) -> Vec<Substitution> {
+  resolve(element.get_attribute("subs"), defaults(&element.element))
+}
+
+/// Replaces the typographic character sequences (e.g. `(C)`, `--`
+/// or `...`) with the characters they stand for. A sequence can be
+/// escaped with a backslash. Returns `None` if nothing was replaced.
+pub fn replacements(input: &str) -> Option<String> {
+  let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+  let is_space = |c: Option<char>| c.is_none_or(|c| c == ' ' || c == '\n');
+
+  let mut out = String::with_capacity(input.len());
+  let mut changed = false;
+  let mut position = 0;
+  while position < input.len() {
+    let rest = &input[position..];
+    let before = out.chars().last();
+
+    if let Some(rest) = rest.strip_prefix('\\') {
+      if let Some((sequence, _)) = replacement(rest, before, is_word, is_space) {
+        out.push_str(sequence);
+        position += 1 + sequence.len();
+        changed = true;
+        continue;
+      }
+    }
+    if let Some((sequence, replaced)) = replacement(rest, before, is_word, is_space) {
+      // An em dash between spaces is surrounded by thin spaces
+      if sequence == "--" && !is_word(before) {
+        if before == Some(' ') {
+          out.pop();
+          out.push('\u{2009}');
+        }
+        out.push('\u{2014}');
+        if rest[2..].starts_with(' ') {
+          out.push('\u{2009}');
+          position += 1;
+        }
+      } else {
+        out.push_str(replaced);
+      }
+      position += sequence.len();
+      changed = true;
+      continue;
+    }
+
+    let c = rest.chars().next().unwrap_or_default();
+    out.push(c);
+    position += c.len_utf8();
+  }
+
+  match changed {
+    true => Some(out),
+    false => None,
+  }
+}
+
+/// Finds the replacement at the start of the input
+fn replacement(
+  input: &str,
+  before: Option<char>,
+  is_word: impl Fn(Option<char>) -> bool,
+  is_space: impl Fn(Option<char>) -> bool,
+) -> Option<(&'static str, &'static str)> {
+  let after = |sequence: &str| input[sequence.len()..].chars().next();
+
+  let replacements = [
+    ("(C)", "\u{a9}"),
+    ("(R)", "\u{ae}"),
+    ("(TM)", "\u{2122}"),
+    ("...", "\u{2026}\u{200b}"),
+    ("->", "\u{2192}"),
+    ("=>", "\u{21d2}"),
+    ("<-", "\u{2190}"),
+    ("<=", "\u{21d0}"),
+  ];
+  for (sequence, replaced) in replacements {
+    if input.starts_with(sequence) {
+      return Some((sequence, replaced));
+    }
+  }
+
+  // Dashes between words or standing alone
+  if input.starts_with("--") && !input.starts_with("---") {
+    if is_word(before) && is_word(after("--")) {
+      return Some(("--", "\u{2014}\u{200b}"));
+    }
+    if is_space(before) && is_space(after("--")) {
+      return Some(("--", "\u{2014}"));
+    }
+  }
+  // An apostrophe inside a word
+  if input.starts_with('\'') && is_word(before) && is_word(after("'")) {
+    return Some(("'", "\u{2019}"));
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn resolve_subs() {
+    assert_eq!(resolve(None, &VERBATIM), VERBATIM.to_vec());
+    assert_eq!(resolve(Some("none"), &NORMAL), vec![]);
+    assert_eq!(
+      resolve(Some("attributes+"), &VERBATIM),
+      vec![Substitution::Attributes, Substitution::SpecialChars]
+    );
+    assert_eq!(
+      resolve(Some("+quotes, -specialchars"), &VERBATIM),
+      vec![Substitution::Quotes]
+    );
+    assert_eq!(
+      resolve(Some("quotes,macros"), &VERBATIM),
+      vec![Substitution::Quotes, Substitution::Macros]
+    );
+  }
+
+  #[test]
+  fn typographic_replacements() {
+    assert_eq!(
+      replacements("(C) 2024 and so on... it's a -> b"),
+      Some("\u{a9} 2024 and so on\u{2026}\u{200b} it\u{2019}s a \u{2192} b".to_string())
+    );
+    assert_eq!(
+      replacements("word--word and this -- that"),
+      Some("word\u{2014}\u{200b}word and this\u{2009}\u{2014}\u{2009}that".to_string())
+    );
+    assert_eq!(replacements("\\(C) stays"), Some("(C) stays".to_string()));
+    assert_eq!(replacements("nothing to do"), None);
+  }
+}
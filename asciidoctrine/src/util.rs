@@ -6,6 +6,10 @@ use std::process::{Command, Stdio};
 
 pub trait Environment {
   fn read_to_string(&mut self, path: &str) -> crate::Result<String>;
+  /// Reads a binary file (e.g. an image)
+  fn read(&mut self, path: &str) -> crate::Result<Vec<u8>> {
+    Ok(fs::read(path)?)
+  }
   fn write(&mut self, path: &str, content: &str) -> crate::Result<()>;
   fn eval(&mut self, interpreter: &str, content: &str) -> crate::Result<(bool, String, String)>; // success, Stdout, Stderr
 }
@@ -103,6 +107,10 @@ impl Environment for Cache {
     )
   }
 
+  fn read(&mut self, path: &str) -> crate::Result<Vec<u8>> {
+    Ok(self.read_to_string(path)?.into_bytes())
+  }
+
   fn write(&mut self, path: &str, content: &str) -> crate::Result<()> {
     self.files.insert(path.to_string(), content.to_string());
 
@@ -149,6 +157,13 @@ impl Environment for Env {
     }
   }
 
+  fn read(&mut self, path: &str) -> crate::Result<Vec<u8>> {
+    match self {
+      Env::Io(env) => env.read(path),
+      Env::Cache(env) => env.read(path),
+    }
+  }
+
   fn write(&mut self, path: &str, content: &str) -> crate::Result<()> {
     match self {
       Env::Io(env) => env.write(path, content),
@@ -163,3 +178,69 @@ impl Environment for Env {
     }
   }
 }
+
+/// Splits the callout markers (like `// <1>`, `# <2> <3>`,
+/// `<.>` or `<!--1-->`) from the end of a line of a listing.
+/// Returns the code in front of the markers and the numbers
+/// of the markers (`None` for automatically numbered ones).
+pub fn split_callouts(line: &str) -> Option<(&str, Vec<Option<u32>>)> {
+  let mut code = line.trim_end();
+  let mut markers = Vec::new();
+
+  while let Some((rest, marker)) = strip_callout(code) {
+    markers.push(marker);
+    code = rest.trim_end();
+  }
+  if markers.is_empty() {
+    return None;
+  }
+  markers.reverse();
+
+  // The markers are usually hidden in a comment
+  for prefix in ["//", "#", ";;", "--"] {
+    if let Some(rest) = code.strip_suffix(prefix) {
+      code = rest.trim_end();
+      break;
+    }
+  }
+
+  Some((code, markers))
+}
+
+/// Splits a key combination (e.g. `Ctrl+S` or `Ctrl++`)
+/// into its keys
+pub fn split_keys(keys: &str) -> Vec<&str> {
+  let mut result = Vec::new();
+  let mut start = 0;
+  for (index, c) in keys.char_indices() {
+    // A `+` at the beginning of a key is the key itself
+    if c == '+' && index > start {
+      result.push(keys[start..index].trim());
+      start = index + 1;
+    }
+  }
+  if start < keys.len() {
+    result.push(keys[start..].trim());
+  }
+
+  result
+}
+
+fn strip_callout(input: &str) -> Option<(&str, Option<u32>)> {
+  let body = input.strip_suffix('>')?;
+  let (rest, number) = match body.strip_suffix("--") {
+    Some(body) => {
+      let start = body.rfind("<!--")?;
+      (&body[..start], &body[start + 4..])
+    }
+    None => {
+      let start = body.rfind('<')?;
+      (&body[..start], &body[start + 1..])
+    }
+  };
+
+  match number {
+    "." => Some((rest, None)),
+    number => number.parse().ok().map(|number| (rest, Some(number))),
+  }
+}
@@ -0,0 +1,239 @@
+use crate::ast::*;
+use std::ops::ControlFlow;
+
+/// Tells a walk what to do after entering an element
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visit {
+  /// Go on with the children of the element
+  Continue,
+  /// Don't walk into the children (or the included document)
+  /// of the element. It is left right away.
+  SkipChildren,
+  /// End the walk. The element isn't left.
+  Stop,
+}
+
+/// Gets called for every element of a walk through the AST
+/// (see `walk_ast`). The children of an element are walked
+/// between `enter` and `leave`. The elements of an included
+/// document count as children of the include.
+///
+/// The elements live as long as the walk borrows them, so a
+/// visitor can keep references to them.
+pub trait Visitor<'v> {
+  fn enter(&mut self, _element: &'v ElementSpan<'v>) -> Visit {
+    Visit::Continue
+  }
+
+  fn leave(&mut self, _element: &'v ElementSpan<'v>) {}
+}
+
+/// Like `Visitor` but can change the elements. Changes in
+/// `enter` are made before the children are walked, so new
+/// children are walked too.
+pub trait VisitorMut<'a> {
+  fn enter(&mut self, _element: &mut ElementSpan<'a>) -> Visit {
+    Visit::Continue
+  }
+
+  fn leave(&mut self, _element: &mut ElementSpan<'a>) {}
+}
+
+pub fn walk_ast<'v, V: Visitor<'v> + ?Sized>(visitor: &mut V, ast: &'v AST<'v>) -> ControlFlow<()> {
+  walk_elements(visitor, &ast.elements)
+}
+
+pub fn walk_elements<'v, V: Visitor<'v> + ?Sized>(
+  visitor: &mut V,
+  elements: &'v [ElementSpan<'v>],
+) -> ControlFlow<()> {
+  for element in elements {
+    walk_element(visitor, element)?;
+  }
+
+  ControlFlow::Continue(())
+}
+
+/// Walks through an element and everything inside of it
+pub fn walk_element<'v, V: Visitor<'v> + ?Sized>(
+  visitor: &mut V,
+  element: &'v ElementSpan<'v>,
+) -> ControlFlow<()> {
+  match visitor.enter(element) {
+    Visit::Continue => walk_children(visitor, element)?,
+    Visit::SkipChildren => (),
+    Visit::Stop => return ControlFlow::Break(()),
+  }
+  visitor.leave(element);
+
+  ControlFlow::Continue(())
+}
+
+/// Walks through the children of an element (the list items
+/// of a list, the rows of a table and so on) and the elements
+/// of an included document
+pub fn walk_children<'v, V: Visitor<'v> + ?Sized>(
+  visitor: &mut V,
+  element: &'v ElementSpan<'v>,
+) -> ControlFlow<()> {
+  if let Element::IncludeElement(include) = &element.element {
+    walk_ast(visitor, &include.inner)?;
+  }
+
+  walk_elements(visitor, &element.children)
+}
+
+pub fn walk_ast_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, ast: &mut AST<'a>) -> ControlFlow<()> {
+  walk_elements_mut(visitor, &mut ast.elements)
+}
+
+pub fn walk_elements_mut<'a, V: VisitorMut<'a> + ?Sized>(
+  visitor: &mut V,
+  elements: &mut [ElementSpan<'a>],
+) -> ControlFlow<()> {
+  for element in elements {
+    walk_element_mut(visitor, element)?;
+  }
+
+  ControlFlow::Continue(())
+}
+
+pub fn walk_element_mut<'a, V: VisitorMut<'a> + ?Sized>(
+  visitor: &mut V,
+  element: &mut ElementSpan<'a>,
+) -> ControlFlow<()> {
+  match visitor.enter(element) {
+    Visit::Continue => walk_children_mut(visitor, element)?,
+    Visit::SkipChildren => (),
+    Visit::Stop => return ControlFlow::Break(()),
+  }
+  visitor.leave(element);
+
+  ControlFlow::Continue(())
+}
+
+pub fn walk_children_mut<'a, V: VisitorMut<'a> + ?Sized>(
+  visitor: &mut V,
+  element: &mut ElementSpan<'a>,
+) -> ControlFlow<()> {
+  if let Element::IncludeElement(include) = &mut element.element {
+    walk_ast_mut(visitor, &mut include.inner)?;
+  }
+
+  walk_elements_mut(visitor, &mut element.children)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::options::Opts;
+  use crate::reader::asciidoc::AsciidocReader;
+  use crate::util::{Cache, Env, Environment};
+  use crate::Reader;
+  use clap::Parser;
+  use pretty_assertions::assert_eq;
+
+  /// Remembers the order of the walk
+  struct Trace(Vec<String>);
+
+  impl<'v> Visitor<'v> for Trace {
+    fn enter(&mut self, element: &'v ElementSpan<'v>) -> Visit {
+      self.0.push(format!("enter {:?}", element.element));
+      match element.element {
+        Element::Table => Visit::SkipChildren,
+        Element::Title { .. } => Visit::Stop,
+        _ => Visit::Continue,
+      }
+    }
+
+    fn leave(&mut self, element: &'v ElementSpan<'v>) {
+      self.0.push(format!("leave {:?}", element.element));
+    }
+  }
+
+  /// Gets the listings
+  struct Listings<'v>(Vec<&'v ElementSpan<'v>>);
+
+  impl<'v> Visitor<'v> for Listings<'v> {
+    fn enter(&mut self, element: &'v ElementSpan<'v>) -> Visit {
+      if let Element::TypedBlock {
+        kind: BlockType::Listing,
+      } = element.element
+      {
+        self.0.push(element);
+      }
+      Visit::Continue
+    }
+  }
+
+  /// Gives every list item a role
+  struct Roles;
+
+  impl<'a> VisitorMut<'a> for Roles {
+    fn enter(&mut self, element: &mut ElementSpan<'a>) -> Visit {
+      if let Element::ListItem(_) = element.element {
+        element.attributes.push(Attribute {
+          key: "role".to_string(),
+          value: AttributeValue::Ref("item"),
+        });
+      }
+      Visit::Continue
+    }
+  }
+
+  fn parse(input: &str) -> AST<'_> {
+    let reader = AsciidocReader::new();
+    let opts = Opts::parse_from(vec![""]);
+    let mut env = Env::Cache(Cache::new());
+    reader.parse(input, &opts, &mut env).unwrap()
+  }
+
+  #[test]
+  fn walk_order() {
+    let ast = parse("* item\n\n|===\n|cell\n|===\n\n== Title\n\nnot visited\n");
+
+    let mut trace = Trace(Vec::new());
+    assert_eq!(walk_ast(&mut trace, &ast), ControlFlow::Break(()));
+    assert_eq!(
+      trace.0,
+      vec![
+        "enter List(Bullet)",
+        "enter ListItem(1)",
+        "enter Paragraph",
+        "enter Text",
+        "leave Text",
+        "leave Paragraph",
+        "leave ListItem(1)",
+        "leave List(Bullet)",
+        "enter Table",
+        "leave Table",
+        "enter Title { level: 2 }",
+      ]
+    );
+  }
+
+  #[test]
+  fn walk_included_documents() {
+    let mut env = Env::Cache(Cache::new());
+    env.write("inner.adoc", "----\ninner\n----\n").unwrap();
+    let opts = Opts::parse_from(vec![""]);
+    let input = "----\nouter\n----\n\ninclude::inner.adoc[]\n";
+    let ast = AsciidocReader::new().parse(input, &opts, &mut env).unwrap();
+
+    let mut listings = Listings(Vec::new());
+    let _ = walk_ast(&mut listings, &ast);
+    let contents: Vec<_> = listings.0.iter().map(|listing| listing.content.as_ref()).collect();
+    assert_eq!(contents, vec!["----\nouter\n----", "----\ninner\n----"]);
+  }
+
+  #[test]
+  fn change_elements() {
+    let mut ast = parse("* one\n** two\n");
+
+    let _ = walk_ast_mut(&mut Roles, &mut ast);
+    let list = &ast.elements[0];
+    assert_eq!(list.children[0].get_attribute("role"), Some("item"));
+    assert_eq!(list.children[1].get_attribute("role"), Some("item"));
+    assert_eq!(list.get_attribute("role"), None);
+  }
+}
@@ -0,0 +1,501 @@
+pub use crate::ast::*;
+use crate::reader::attributes::DocumentAttributes;
+use crate::{options, Result};
+use std::io;
+
+/// Writes an AST (which could come from any reader or could be
+/// changed by extensions) as asciidoc. The blocks and inline
+/// elements are written in a canonical form. Texts keep their
+/// source, so escapes and replacements stay as they are. The
+/// content of verbatim blocks (e.g. listings) is written byte
+/// by byte.
+pub struct AsciidocWriter {}
+
+impl AsciidocWriter {
+  pub fn new() -> Self {
+    AsciidocWriter {}
+  }
+}
+
+impl Default for AsciidocWriter {
+  fn default() -> Self {
+    AsciidocWriter::new()
+  }
+}
+
+impl<T: io::Write> crate::Writer<T> for AsciidocWriter {
+  fn write<'a>(&mut self, ast: AST, args: &options::Opts, mut out: T) -> Result<()> {
+    let mut ctx = Context {
+      attributes: match args.resolve_attributes {
+        true => Some(DocumentAttributes::from_opts(args)),
+        false => None,
+      },
+      inline_includes: args.inline_includes,
+    };
+
+    let document = write_blocks(&ast.elements, &mut ctx);
+    out.write_all(document.as_bytes())?;
+    if !document.is_empty() {
+      out.write_all(b"\n")?;
+    }
+    out.flush()?;
+
+    Ok(())
+  }
+}
+
+struct Context {
+  /// The attributes references are resolved with (only if
+  /// they should be resolved)
+  attributes: Option<DocumentAttributes>,
+  /// Write the content of included documents instead
+  /// of the include directives
+  inline_includes: bool,
+}
+
+/// Writes blocks separated by empty lines
+fn write_blocks(elements: &[ElementSpan], ctx: &mut Context) -> String {
+  let blocks: Vec<_> = elements
+    .iter()
+    .map(|element| write_block(element, ctx))
+    .filter(|block| !block.is_empty())
+    .collect();
+
+  blocks.join("\n\n")
+}
+
+fn write_block(input: &ElementSpan, ctx: &mut Context) -> String {
+  match &input.element {
+    Element::Attribute(attribute) => {
+      if let Some(attributes) = &mut ctx.attributes {
+        attributes.define(&attribute.key, attribute.value.as_str());
+      }
+      match attribute.value.as_str() {
+        "" => format!(":{}:", attribute.key),
+        value => format!(":{}: {}", attribute.key, value),
+      }
+    }
+    Element::Title { level } => {
+      let name = input.get_attribute("name").unwrap_or(&input.content);
+      let mut out = metadata(input, &input.positional_attributes, &["name"]);
+      out.push_str(&format!("{} {}", "=".repeat(*level as usize), name));
+      // The attribute entries of the document header
+      for element in input.children.iter() {
+        out.push('\n');
+        out.push_str(&write_block(element, ctx));
+      }
+      out
+    }
+    Element::Section { .. } => write_blocks(&input.children, ctx),
+    Element::Paragraph => {
+      let mut out = metadata(input, &input.positional_attributes, &[]);
+      match input.children.is_empty() {
+        true => out.push_str(input.get_attribute("content").unwrap_or(&input.content)),
+        false => out.push_str(&write_inlines(&input.children, ctx)),
+      }
+      out
+    }
+    Element::List(_) => {
+      let mut out = metadata(input, &input.positional_attributes, &[]);
+      out.push_str(&write_list(input, 0, ctx));
+      out
+    }
+    Element::Admonition(kind) => write_admonition(input, kind, ctx),
+    Element::TypedBlock { kind } => write_typed_block(input, kind, ctx),
+    Element::Image => {
+      let path = input.get_attribute("path").unwrap_or("");
+      let mut out = metadata(input, &[], &["path"]);
+      let attributes = attribute_list(&input.positional_attributes, &named_attributes(input, &["path"]));
+      out.push_str(&format!("image::{}[{}]", path, attributes));
+      out
+    }
+    Element::Table => write_table(input, ctx),
+    Element::IncludeElement(include) => match ctx.inline_includes {
+      true => write_blocks(&include.inner.elements, ctx),
+      false if input.content.starts_with("include::") => input.content.to_string(),
+      false => {
+        let target = input.get_attribute("target").unwrap_or("");
+        let attributes = attribute_list(&[], &named_attributes(input, &["target"]));
+        format!("include::{}[{}]", target, attributes)
+      }
+    },
+    Element::Anchor => format!("[[{}]]", input.get_attribute("id").unwrap_or("")),
+    Element::Text
+    | Element::Styled
+    | Element::XRef
+    | Element::Link
+    | Element::Footnote
+    | Element::InlineImage
+    | Element::Keyboard
+    | Element::Button
+    | Element::Menu
+    | Element::Passthrough
+    | Element::LineBreak
+    | Element::InlineMacro => write_inline(input, ctx),
+    // Comments, external content, wrong formatted text
+    // and so on are written as they are
+    _ => input.content.to_string(),
+  }
+}
+
+/// Writes the items of a list. The markers of a nested list of
+/// the same kind continue the ones of the list it is in.
+fn write_list(input: &ElementSpan, depth: usize, ctx: &mut Context) -> String {
+  let kind = match &input.element {
+    Element::List(kind) => kind,
+    _ => return input.content.to_string(),
+  };
+
+  let mut items = Vec::new();
+  for item in input.children.iter() {
+    let level = match item.element {
+      Element::ListItem(level) => level as usize,
+      _ => 1,
+    };
+    let marker = match kind {
+      ListType::Bullet => "*".repeat(depth + level),
+      ListType::Number => ".".repeat(depth + level),
+      ListType::Labeled => format!("{}{}", item.get_attribute("term").unwrap_or(""), ":".repeat(level + 1)),
+      ListType::Callout => format!("<{}>", item.get_attribute("callout").unwrap_or("")),
+    };
+
+    let mut out = marker;
+    let mut children = item.children.iter().peekable();
+    if let Some(paragraph) = children.next_if(|child| child.element == Element::Paragraph) {
+      out.push(' ');
+      out.push_str(&write_block(paragraph, ctx));
+    }
+    for child in children {
+      match &child.element {
+        Element::List(nested) => {
+          let depth = match nested == kind {
+            true => depth + level,
+            false => 0,
+          };
+          out.push('\n');
+          out.push_str(&write_list(child, depth, ctx));
+        }
+        _ => {
+          out.push_str("\n+\n");
+          out.push_str(&write_block(child, ctx));
+        }
+      }
+    }
+    items.push(out);
+  }
+
+  items.join("\n")
+}
+
+fn write_admonition(input: &ElementSpan, kind: &AdmonitionType, ctx: &mut Context) -> String {
+  let label = kind.caption().to_uppercase();
+  let simple = input.positional_attributes.is_empty()
+    && input.get_attribute("title").is_none()
+    && input.get_attribute("anchor").is_none()
+    && input.children.len() == 1
+    && input.children[0].element == Element::Paragraph;
+  if simple {
+    return format!("{}: {}", label, write_block(&input.children[0], ctx));
+  }
+
+  let mut positional = input.positional_attributes.clone();
+  if positional.first().map(|style| style.as_str()) != Some(label.as_str()) {
+    positional.insert(0, AttributeValue::String(label));
+  }
+  let mut out = metadata(input, &positional, &[]);
+  out.push_str(&delimited("====", &write_blocks(&input.children, ctx)));
+  out
+}
+
+fn write_typed_block(input: &ElementSpan, kind: &BlockType, ctx: &mut Context) -> String {
+  let (delimiter, verbatim) = match kind {
+    // Literal blocks are read as listings without a style
+    BlockType::Listing if input.content.ends_with("\n....") => ("....", true),
+    BlockType::Listing => ("----", true),
+    BlockType::Literal => ("....", true),
+    BlockType::Sidebar => ("****", false),
+    BlockType::Quote => ("____", false),
+    BlockType::Verse => ("____", true),
+    BlockType::Open => ("--", false),
+    BlockType::Example => ("====", false),
+    BlockType::Passtrough => ("++++", true),
+    BlockType::Comment => ("////", true),
+  };
+
+  // The attribution of quotes is written in the style
+  let mut positional = input.positional_attributes.clone();
+  if let BlockType::Quote | BlockType::Verse = kind {
+    let style = match kind {
+      BlockType::Verse => "verse",
+      _ => "quote",
+    };
+    let attribution = input.get_attribute("attribution");
+    let citetitle = input.get_attribute("citetitle");
+    if positional.is_empty() && (attribution.is_some() || citetitle.is_some() || style == "verse") {
+      positional.push(AttributeValue::Ref(style));
+      for value in [attribution, citetitle].iter().flatten() {
+        positional.push(AttributeValue::String(value.to_string()));
+      }
+    }
+  }
+
+  let body = match verbatim {
+    true => input.get_attribute("content").unwrap_or("").to_string(),
+    false => write_blocks(&input.children, ctx),
+  };
+  let mut out = metadata(input, &positional, &["attribution", "citetitle"]);
+  out.push_str(&delimited(delimiter, &body));
+  out
+}
+
+/// Writes a table with a header row followed by an empty line.
+/// Rows with cells spanning several lines are written cell by
+/// cell.
+fn write_table(input: &ElementSpan, ctx: &mut Context) -> String {
+  let mut out = metadata(input, &input.positional_attributes, &["colwidths"]);
+
+  let mut body = String::new();
+  let mut rows = input.children.iter().peekable();
+  while let Some(row) = rows.next() {
+    let header = row.get_attribute("section") == Some("header");
+    let cells: Vec<_> = row.children.iter().map(|cell| write_cell(cell, header, ctx)).collect();
+    match !header && cells.iter().any(|cell| cell.contains('\n')) {
+      true => body.push_str(&cells.join("\n")),
+      false => body.push_str(&cells.join(" ")),
+    }
+    body.push('\n');
+    let header_ends = rows.peek().and_then(|row| row.get_attribute("section")) != Some("header");
+    if header && header_ends {
+      body.push('\n');
+    }
+  }
+  out.push_str(&delimited("|===", body.trim_end()));
+  out
+}
+
+fn write_cell(input: &ElementSpan, header: bool, ctx: &mut Context) -> String {
+  let mut spec = match (input.get_attribute("colspan"), input.get_attribute("rowspan")) {
+    (Some(colspan), Some(rowspan)) => format!("{}.{}+", colspan, rowspan),
+    (Some(colspan), None) => format!("{}+", colspan),
+    (None, Some(rowspan)) => format!(".{}+", rowspan),
+    (None, None) => String::new(),
+  };
+  spec.push_str(match input.get_attribute("halign") {
+    Some("center") => "^",
+    Some("right") => ">",
+    _ => "",
+  });
+  spec.push_str(match input.get_attribute("valign") {
+    Some("middle") => ".^",
+    Some("bottom") => ".>",
+    _ => "",
+  });
+  let style = input.get_attribute("style").unwrap_or("default");
+  spec.push_str(match style {
+    "asciidoc" => "a",
+    "emphasis" => "e",
+    "header" if !header => "h",
+    "literal" => "l",
+    "monospaced" => "m",
+    "strong" => "s",
+    "verse" => "v",
+    _ => "",
+  });
+
+  let content = match style {
+    "asciidoc" => format!("\n{}", write_blocks(&input.children, ctx)),
+    "literal" | "verse" => input.get_attribute("content").unwrap_or(&input.content).replace('|', "\\|"),
+    _ if input.children.is_empty() => input.get_attribute("content").unwrap_or(&input.content).replace('|', "\\|"),
+    _ => {
+      let paragraphs: Vec<_> = input.children.iter().map(|child| write_block(child, ctx)).collect();
+      paragraphs.join("\n\n")
+    }
+  };
+
+  format!("{}|{}", spec, content)
+}
+
+fn write_inlines(elements: &[ElementSpan], ctx: &mut Context) -> String {
+  elements.iter().map(|element| write_inline(element, ctx)).collect()
+}
+
+fn write_inline(input: &ElementSpan, ctx: &mut Context) -> String {
+  match &input.element {
+    Element::Text => {
+      // Texts made by extensions don't have a source
+      if input.content.is_empty() {
+        return input.get_attribute("content").unwrap_or("").to_string();
+      }
+      match &mut ctx.attributes {
+        Some(attributes) => attributes
+          .substitute(&input.content)
+          .unwrap_or_else(|| input.content.to_string()),
+        None => input.content.to_string(),
+      }
+    }
+    Element::Styled => {
+      let (open, close) = match input.get_attribute("style").unwrap_or("") {
+        "strong" => ("*", "*"),
+        "em" | "emphasis" => ("_", "_"),
+        "monospaced" => ("`", "`"),
+        "mark" => ("#", "#"),
+        "sup" => ("^", "^"),
+        "sub" => ("~", "~"),
+        "doublequote" => ("\"`", "`\""),
+        "singlequote" => ("'`", "`'"),
+        _ => return input.content.to_string(),
+      };
+      // Marks inside of a word have to be doubled
+      let (open, close) = match input.content.starts_with(&open.repeat(2)) && open.len() == 1 {
+        true => (open.repeat(2), close.repeat(2)),
+        false => (open.to_string(), close.to_string()),
+      };
+      let roles = match input.positional_attributes.is_empty() {
+        true => String::new(),
+        false => format!("[{}]", attribute_list(&input.positional_attributes, &[])),
+      };
+      let text = match input.children.is_empty() {
+        true => input.get_attribute("content").unwrap_or("").to_string(),
+        false => write_inlines(&input.children, ctx),
+      };
+      format!("{}{}{}{}", roles, open, text, close)
+    }
+    Element::LineBreak => " +".to_string(),
+    Element::XRef => {
+      let id = input.get_attribute("id").unwrap_or("");
+      match input.get_attribute("content") {
+        Some(text) => format!("<<{},{}>>", id, text),
+        None => format!("<<{}>>", id),
+      }
+    }
+    Element::Link => {
+      let url = input.get_attribute("url").unwrap_or("");
+      format!("link:{}[{}]", url, macro_text(input, &["url"]))
+    }
+    Element::Footnote => {
+      let id = input.get_attribute("id").unwrap_or("");
+      let text = input.get_attribute("content").unwrap_or("");
+      format!("footnote:{}[{}]", id, text.replace(']', "\\]"))
+    }
+    Element::Keyboard => format!("kbd:[{}]", input.get_attribute("keys").unwrap_or("").replace(']', "\\]")),
+    Element::Button => format!("btn:[{}]", input.get_attribute("label").unwrap_or("").replace(']', "\\]")),
+    Element::Menu => format!(
+      "menu:{}[{}]",
+      input.get_attribute("menu").unwrap_or(""),
+      input.get_attribute("items").unwrap_or("").replace(']', "\\]")
+    ),
+    Element::Passthrough if input.content.is_empty() => {
+      format!("pass:[{}]", input.get_attribute("content").unwrap_or("").replace(']', "\\]"))
+    }
+    Element::Anchor => format!(
+      "anchor:{}[{}]",
+      input.get_attribute("id").unwrap_or(""),
+      input.get_attribute("content").unwrap_or("").replace(']', "\\]")
+    ),
+    Element::InlineImage => {
+      let path = input.get_attribute("path").unwrap_or("");
+      let attributes = attribute_list(&input.positional_attributes, &named_attributes(input, &["path"]));
+      format!("image:{}[{}]", path, attributes)
+    }
+    Element::InlineMacro if input.content.is_empty() => {
+      let name = input.get_attribute("name").unwrap_or("");
+      let target = input.get_attribute("target").unwrap_or("");
+      format!("{}:{}[{}]", name, target, macro_text(input, &["name", "target"]))
+    }
+    // The markers are part of the listing
+    Element::Callout(_) => String::new(),
+    Element::Paragraph => write_inlines(&input.children, ctx),
+    // Macros, passthroughs, wrong formatted text and so
+    // on are written as they are
+    _ => input.content.to_string(),
+  }
+}
+
+// Helper Functions
+//----------------------------------------------------
+
+/// Writes the content between delimiter lines. The delimiter
+/// gets longer if the content has a line like it.
+fn delimited(delimiter: &str, content: &str) -> String {
+  let mut delimiter = delimiter.to_string();
+  // The delimiter of open blocks can't be changed
+  if delimiter != "--" {
+    while content.lines().any(|line| line == delimiter) {
+      let mark = delimiter[..1].to_string();
+      delimiter.push_str(&mark);
+    }
+  }
+
+  match content.is_empty() {
+    true => format!("{}\n{}", delimiter, delimiter),
+    false => format!("{}\n{}\n{}", delimiter, content, delimiter),
+  }
+}
+
+/// The lines in front of a block with its id, title and attributes
+fn metadata(input: &ElementSpan, positional: &[AttributeValue], derived: &[&str]) -> String {
+  let mut out = String::new();
+  if let Some(id) = input.get_attribute("anchor") {
+    out.push_str(&format!("[[{}]]\n", id));
+  }
+  if let Some(title) = input.get_attribute("title") {
+    if !matches!(input.element, Element::Title { .. }) {
+      out.push_str(&format!(".{}\n", title));
+    }
+  }
+  let named = named_attributes(input, derived);
+  if !positional.is_empty() || !named.is_empty() {
+    out.push_str(&format!("[{}]\n", attribute_list(positional, &named)));
+  }
+
+  out
+}
+
+/// Gets the attributes which aren't made by the reader from the
+/// source (e.g. the content or the title of a block)
+fn named_attributes<'b>(input: &'b ElementSpan, derived: &[&str]) -> Vec<&'b Attribute<'b>> {
+  input
+    .attributes
+    .iter()
+    .filter(|attribute| !["anchor", "title", "content"].contains(&attribute.key.as_str()))
+    .filter(|attribute| !derived.contains(&attribute.key.as_str()))
+    .collect()
+}
+
+fn attribute_list(positional: &[AttributeValue], named: &[&Attribute]) -> String {
+  let mut entries: Vec<_> = positional.iter().map(|value| quote(value.as_str(), false)).collect();
+  for attribute in named {
+    entries.push(format!("{}={}", attribute.key, quote(attribute.value.as_str(), true)));
+  }
+
+  entries.join(",")
+}
+
+/// The text of a macro like `link:url[text]`. It is only read
+/// as an attribute list if there are named attributes.
+fn macro_text(input: &ElementSpan, derived: &[&str]) -> String {
+  let named: Vec<_> = input
+    .attributes
+    .iter()
+    .filter(|attribute| attribute.key != "content" && !derived.contains(&attribute.key.as_str()))
+    .collect();
+  match named.is_empty() {
+    true => input
+      .positional_attributes
+      .iter()
+      .map(|value| value.as_str().replace(']', "\\]"))
+      .collect::<Vec<_>>()
+      .join(","),
+    false => attribute_list(&input.positional_attributes, &named),
+  }
+}
+
+/// Quotes a value of an attribute list if it would
+/// be read in another way
+fn quote(value: &str, named: bool) -> String {
+  let special = value.contains([',', '"', '=', ']']) || value.trim() != value;
+  match special || (named && value.is_empty()) {
+    true => format!("\"{}\"", value.replace('"', "\\\"")),
+    false => value.to_string(),
+  }
+}
@@ -0,0 +1,826 @@
+pub use crate::ast::*;
+use crate::reader::asciidoc::nest_sections;
+use crate::subs::{self, Substitution};
+use crate::{options, Result};
+use std::io;
+
+/// Writes an AST as DocBook 5 article. The content following a
+/// title is nested in a section, even if the reader didn't do it.
+pub struct DocbookWriter {}
+
+impl DocbookWriter {
+  pub fn new() -> Self {
+    DocbookWriter {}
+  }
+}
+
+impl Default for DocbookWriter {
+  fn default() -> Self {
+    DocbookWriter::new()
+  }
+}
+
+impl<T: io::Write> crate::Writer<T> for DocbookWriter {
+  fn write<'a>(&mut self, ast: AST, _args: &options::Opts, mut out: T) -> Result<()> {
+    let elements = nest_sections(ast.elements, &ast.content);
+
+    out.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")?;
+    out.write_all(
+      b"<article xmlns=\"http://docbook.org/ns/docbook\" xmlns:xl=\"http://www.w3.org/1999/xlink\" version=\"5.0\" xml:lang=\"en\">\n",
+    )?;
+    let mut elements = elements.iter().peekable();
+    if let Some(title) = elements.next_if(|element| element.element == Element::Title { level: 1 }) {
+      write_open_tag_ln("info", 1, out.by_ref())?;
+      write_text_tag("title", title.get_attribute("name").unwrap_or(""), 2, out.by_ref())?;
+      if let Some(author) = title.children.iter().find_map(|element| match &element.element {
+        Element::Attribute(attribute) if attribute.key == "author" => Some(attribute.value.as_str()),
+        _ => None,
+      }) {
+        write_open_tag_ln("author", 2, out.by_ref())?;
+        write_text_tag("personname", author, 3, out.by_ref())?;
+        write_close_tag_ln("author", 2, out.by_ref())?;
+      }
+      write_close_tag_ln("info", 1, out.by_ref())?;
+    }
+    for element in elements {
+      write_docbook(element, 1, &mut out)?;
+    }
+    out.write_all(b"</article>\n")?;
+    out.flush()?;
+
+    Ok(())
+  }
+}
+
+fn write_docbook<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) -> Result<()> {
+  match &input.element {
+    Element::Section { .. } => {
+      let mut children = input.children.iter();
+      let title = children.next();
+      let id = title.map(section_id).unwrap_or_default();
+      write_open_attribute_tag_ln("section", &format!("xml:id=\"{}\"", escape_attribute(&id)), indent, out)?;
+      if let Some(title) = title {
+        write_text_tag("title", title.get_attribute("name").unwrap_or(""), indent + 1, out)?;
+      }
+      for element in children {
+        write_docbook(element, indent + 1, out)?;
+      }
+      write_close_tag_ln("section", indent, out)?;
+    }
+    // Titles which don't start a section (e.g. in an
+    // open block) aren't part of the outline
+    Element::Title { level } => {
+      let attrs = format!("renderas=\"sect{}\"", level.saturating_sub(1).clamp(1, 5));
+      write_open_attribute_tag("bridgehead", &with_id(&attrs, input), indent, out)?;
+      out.write_all(escape_text(input.get_attribute("name").unwrap_or("")).as_bytes())?;
+      write_close_tag_ln("bridgehead", 0, out)?;
+    }
+    Element::Paragraph => match input.get_attribute("title") {
+      Some(title) => {
+        write_open_attribute_tag_ln("formalpara", &with_id("", input), indent, out)?;
+        write_text_tag("title", title, indent + 1, out)?;
+        write_open_tag("para", indent + 1, out)?;
+        write_inlines(&input.children, out)?;
+        write_close_tag_ln("para", 0, out)?;
+        write_close_tag_ln("formalpara", indent, out)?;
+      }
+      None => {
+        write_open_attribute_tag("simpara", &with_id("", input), indent, out)?;
+        write_inlines(&input.children, out)?;
+        write_close_tag_ln("simpara", 0, out)?;
+      }
+    },
+    Element::List(ListType::Callout) => {
+      write_open_attribute_tag_ln("calloutlist", &with_id("", input), indent, out)?;
+      write_block_title(input, indent + 1, out)?;
+      for item in input.children.iter() {
+        let attrs = format!("arearefs=\"{}\"", escape_attribute(item.get_attribute("target").unwrap_or("")));
+        write_open_attribute_tag_ln("callout", &attrs, indent + 1, out)?;
+        for element in item.children.iter() {
+          write_docbook(element, indent + 2, out)?;
+        }
+        write_close_tag_ln("callout", indent + 1, out)?;
+      }
+      write_close_tag_ln("calloutlist", indent, out)?;
+    }
+    Element::List(kind) => write_list(input, kind, &input.children, indent, out)?,
+    Element::Admonition(kind) => {
+      let tag = kind.caption().to_lowercase();
+      write_open_attribute_tag_ln(&tag, &with_id("", input), indent, out)?;
+      write_block_title(input, indent + 1, out)?;
+      for element in input.children.iter() {
+        write_docbook(element, indent + 1, out)?;
+      }
+      write_close_tag_ln(&tag, indent, out)?;
+    }
+    Element::TypedBlock { kind } => write_typed_block(input, kind, indent, out)?,
+    Element::Image => {
+      let tag = match input.get_attribute("title") {
+        Some(_) => "figure",
+        None => "informalfigure",
+      };
+      write_open_attribute_tag_ln(tag, &with_id("", input), indent, out)?;
+      write_block_title(input, indent + 1, out)?;
+      write_open_tag_ln("mediaobject", indent + 1, out)?;
+      out.write_all(&b"  ".repeat(indent + 2))?;
+      out.write_all(image_object(input).as_bytes())?;
+      out.write_all(b"\n")?;
+      write_close_tag_ln("mediaobject", indent + 1, out)?;
+      write_close_tag_ln(tag, indent, out)?;
+    }
+    Element::Table => write_table(input, indent, out)?,
+    Element::Anchor => {
+      write_open_tag("simpara", indent, out)?;
+      write_inline(input, out)?;
+      write_close_tag_ln("simpara", 0, out)?;
+    }
+    Element::IncludeElement(include) => {
+      for element in include.inner.elements.iter() {
+        write_docbook(element, indent, out)?;
+      }
+    }
+    // Attribute entries are already applied by the reader
+    Element::Attribute(_) | Element::Comment => (),
+    Element::Text
+    | Element::Styled
+    | Element::XRef
+    | Element::Link
+    | Element::Footnote
+    | Element::InlineImage
+    | Element::Keyboard
+    | Element::Button
+    | Element::Menu
+    | Element::Passthrough
+    | Element::LineBreak
+    | Element::InlineMacro => {
+      write_open_tag("simpara", indent, out)?;
+      write_inline(input, out)?;
+      write_close_tag_ln("simpara", 0, out)?;
+    }
+    // Wrong formatted text and so on is kept as text
+    _ => {
+      write_open_tag("simpara", indent, out)?;
+      out.write_all(escape_text(&input.content).as_bytes())?;
+      write_close_tag_ln("simpara", 0, out)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Writes the items of a list. The items following an item with
+/// a higher level form a nested list inside of it.
+fn write_list<T: io::Write>(
+  input: &ElementSpan,
+  kind: &ListType,
+  items: &[ElementSpan],
+  indent: usize,
+  out: &mut T,
+) -> Result<()> {
+  let level = |item: &ElementSpan| match item.element {
+    Element::ListItem(level) => level,
+    _ => 1,
+  };
+  let (tag, attrs) = match kind {
+    ListType::Bullet => ("itemizedlist", String::new()),
+    ListType::Number => match items.first().map(level).unwrap_or(1) % 2 {
+      0 => ("orderedlist", "numeration=\"loweralpha\"".to_string()),
+      _ => ("orderedlist", "numeration=\"arabic\"".to_string()),
+    },
+    ListType::Labeled | ListType::Callout => ("variablelist", String::new()),
+  };
+  // Only the outer list gets the id and title
+  let outer = items.first().map(level) == input.children.first().map(level);
+  match outer {
+    true => write_open_attribute_tag_ln(tag, &with_id(&attrs, input), indent, out)?,
+    false => write_open_attribute_tag_ln(tag, &attrs, indent, out)?,
+  }
+  if outer {
+    write_block_title(input, indent + 1, out)?;
+  }
+
+  let mut index = 0;
+  while index < items.len() {
+    let item = &items[index];
+    let nested = items[index + 1..]
+      .iter()
+      .take_while(|nested| level(nested) > level(item))
+      .count();
+
+    let item_indent = match kind {
+      ListType::Labeled | ListType::Callout => {
+        write_open_tag_ln("varlistentry", indent + 1, out)?;
+        write_text_tag("term", item.get_attribute("term").unwrap_or(""), indent + 2, out)?;
+        indent + 2
+      }
+      _ => indent + 1,
+    };
+    write_open_tag_ln("listitem", item_indent, out)?;
+    for element in item.children.iter() {
+      write_docbook(element, item_indent + 1, out)?;
+    }
+    if nested > 0 {
+      write_list(input, kind, &items[index + 1..index + 1 + nested], item_indent + 1, out)?;
+    }
+    // A list item needs some content
+    if item.children.is_empty() && nested == 0 {
+      write_open_tag("simpara", item_indent + 1, out)?;
+      write_close_tag_ln("simpara", 0, out)?;
+    }
+    write_close_tag_ln("listitem", item_indent, out)?;
+    if let ListType::Labeled | ListType::Callout = kind {
+      write_close_tag_ln("varlistentry", indent + 1, out)?;
+    }
+
+    index += 1 + nested;
+  }
+  write_close_tag_ln(tag, indent, out)?;
+
+  Ok(())
+}
+
+fn write_typed_block<T: io::Write>(input: &ElementSpan, kind: &BlockType, indent: usize, out: &mut T) -> Result<()> {
+  let content = input.get_attribute("content").unwrap_or(&input.content);
+  match kind {
+    BlockType::Listing | BlockType::Literal => {
+      // A title is given to listings by a formal paragraph
+      let title = input.get_attribute("title");
+      let indent = match title {
+        Some(title) => {
+          write_open_attribute_tag_ln("formalpara", &with_id("", input), indent, out)?;
+          write_text_tag("title", title, indent + 1, out)?;
+          write_open_tag_ln("para", indent + 1, out)?;
+          indent + 2
+        }
+        None => indent,
+      };
+      let id = match title {
+        Some(_) => String::new(),
+        None => with_id("", input),
+      };
+
+      let style = input.positional_attributes.first().map(|style| style.as_str());
+      let (tag, attrs) = match style {
+        Some("source") => {
+          let mut attrs = Vec::new();
+          if let Some(language) = input.positional_attributes.get(1) {
+            attrs.push(format!("language=\"{}\"", escape_attribute(language.as_str())));
+          }
+          attrs.push("linenumbering=\"unnumbered\"".to_string());
+          ("programlisting", attrs.join(" "))
+        }
+        // Literal blocks are read as listings without a style
+        None if kind == &BlockType::Literal || input.content.ends_with("\n....") => {
+          ("literallayout", "class=\"monospaced\"".to_string())
+        }
+        _ => ("screen", String::new()),
+      };
+      let attrs = [id.as_str(), attrs.as_str()]
+        .iter()
+        .filter(|attrs| !attrs.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+      write_open_attribute_tag(tag, &attrs, indent, out)?;
+      write_listing_content(input, content, out)?;
+      write_close_tag_ln(tag, 0, out)?;
+
+      if title.is_some() {
+        write_close_tag_ln("para", indent - 1, out)?;
+        write_close_tag_ln("formalpara", indent - 2, out)?;
+      }
+    }
+    BlockType::Sidebar | BlockType::Example | BlockType::Open => {
+      let tag = match kind {
+        BlockType::Sidebar => "sidebar",
+        BlockType::Example if input.get_attribute("title").is_some() => "example",
+        BlockType::Example => "informalexample",
+        _ => {
+          // The content of open blocks belongs to the surrounding block
+          for element in input.children.iter() {
+            write_docbook(element, indent, out)?;
+          }
+          return Ok(());
+        }
+      };
+      write_open_attribute_tag_ln(tag, &with_id("", input), indent, out)?;
+      write_block_title(input, indent + 1, out)?;
+      for element in input.children.iter() {
+        write_docbook(element, indent + 1, out)?;
+      }
+      write_close_tag_ln(tag, indent, out)?;
+    }
+    BlockType::Quote | BlockType::Verse => {
+      write_open_attribute_tag_ln("blockquote", &with_id("", input), indent, out)?;
+      write_block_title(input, indent + 1, out)?;
+      let attribution = input.get_attribute("attribution");
+      let citetitle = input.get_attribute("citetitle");
+      if attribution.is_some() || citetitle.is_some() {
+        write_open_tag("attribution", indent + 1, out)?;
+        out.write_all(escape_text(attribution.unwrap_or("")).as_bytes())?;
+        if let Some(citetitle) = citetitle {
+          out.write_all(format!("<citetitle>{}</citetitle>", escape_text(citetitle)).as_bytes())?;
+        }
+        write_close_tag_ln("attribution", 0, out)?;
+      }
+      match kind {
+        BlockType::Verse => {
+          write_open_tag("literallayout", indent + 1, out)?;
+          out.write_all(escape_block_text(input, content).as_bytes())?;
+          write_close_tag_ln("literallayout", 0, out)?;
+        }
+        _ => {
+          for element in input.children.iter() {
+            write_docbook(element, indent + 1, out)?;
+          }
+        }
+      }
+      write_close_tag_ln("blockquote", indent, out)?;
+    }
+    BlockType::Passtrough => {
+      // Passthrough content is written as it is
+      out.write_all(content.as_bytes())?;
+      out.write_all(b"\n")?;
+    }
+    BlockType::Comment => (),
+  }
+
+  Ok(())
+}
+
+/// Writes the content of a listing with its callout
+/// markers replaced by `co` elements
+fn write_listing_content<T: io::Write>(input: &ElementSpan, content: &str, out: &mut T) -> Result<()> {
+  for (index, line) in content.split('\n').enumerate() {
+    if index > 0 {
+      out.write_all(b"\n")?;
+    }
+    let markers = input
+      .children
+      .iter()
+      .filter(|callout| callout.get_attribute("line") == Some(&index.to_string()))
+      .collect::<Vec<_>>();
+    let code = match (markers.is_empty(), crate::util::split_callouts(line)) {
+      (false, Some((code, _))) => code,
+      _ => line,
+    };
+    out.write_all(escape_block_text(input, code).as_bytes())?;
+
+    for marker in markers {
+      match marker.get_attribute("id") {
+        Some(id) => out.write_all(format!(" <co xml:id=\"{}\"/>", escape_attribute(id)).as_bytes())?,
+        None => out.write_all(b" <co/>")?,
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Writes a table. The columns are named, so cells spanning
+/// several columns can refer to them.
+fn write_table<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) -> Result<()> {
+  let widths: Vec<_> = input
+    .get_attribute("colwidths")
+    .unwrap_or("")
+    .split(',')
+    .filter(|width| !width.is_empty())
+    .collect();
+  let cols = match widths.len() {
+    0 => input
+      .children
+      .iter()
+      .map(|row| {
+        row
+          .children
+          .iter()
+          .map(|cell| cell.get_attribute("colspan").and_then(|span| span.parse().ok()).unwrap_or(1))
+          .sum::<usize>()
+      })
+      .max()
+      .unwrap_or(1),
+    cols => cols,
+  };
+
+  let tag = match input.get_attribute("title") {
+    Some(_) => "table",
+    None => "informaltable",
+  };
+  write_open_attribute_tag_ln(tag, &with_id("frame=\"all\" rowsep=\"1\" colsep=\"1\"", input), indent, out)?;
+  write_block_title(input, indent + 1, out)?;
+  write_open_attribute_tag_ln("tgroup", &format!("cols=\"{}\"", cols), indent + 1, out)?;
+  for col in 1..=cols {
+    let attrs = match widths.get(col - 1) {
+      Some(width) => format!("colname=\"col_{}\" colwidth=\"{}*\"", col, escape_attribute(width)),
+      None => format!("colname=\"col_{}\"", col),
+    };
+    write_open_attribute_tag(&format!("colspec {}/", attrs), "", indent + 2, out)?;
+    out.write_all(b"\n")?;
+  }
+
+  // The number of rows the columns are still taken by
+  // cells of rows above
+  let mut taken: Vec<usize> = vec![0; cols];
+  for (section, tag) in [("header", "thead"), ("footer", "tfoot"), ("body", "tbody")] {
+    let rows = input
+      .children
+      .iter()
+      .filter(|row| row.get_attribute("section").unwrap_or("body") == section)
+      .collect::<Vec<_>>();
+    if rows.is_empty() {
+      continue;
+    }
+
+    write_open_tag_ln(tag, indent + 2, out)?;
+    for row in rows {
+      write_open_tag_ln("row", indent + 3, out)?;
+      let mut col = 0;
+      for cell in row.children.iter() {
+        while col < cols && taken[col] > 0 {
+          col += 1;
+        }
+        let colspan = cell.get_attribute("colspan").and_then(|span| span.parse().ok()).unwrap_or(1);
+        let rowspan = cell.get_attribute("rowspan").and_then(|span| span.parse().ok()).unwrap_or(1);
+
+        let mut attrs = Vec::new();
+        if colspan > 1 {
+          attrs.push(format!("namest=\"col_{}\" nameend=\"col_{}\"", col + 1, (col + colspan).min(cols)));
+        }
+        if rowspan > 1 {
+          attrs.push(format!("morerows=\"{}\"", rowspan - 1));
+        }
+        if let Some(halign) = cell.get_attribute("halign") {
+          attrs.push(format!("align=\"{}\"", escape_attribute(halign)));
+        }
+        if let Some(valign) = cell.get_attribute("valign") {
+          attrs.push(format!("valign=\"{}\"", escape_attribute(valign)));
+        }
+        write_table_cell(cell, &attrs.join(" "), indent + 4, out)?;
+
+        for taken in taken.iter_mut().skip(col).take(colspan) {
+          *taken = rowspan;
+        }
+        col += colspan;
+      }
+      for taken in taken.iter_mut() {
+        *taken = taken.saturating_sub(1);
+      }
+      write_close_tag_ln("row", indent + 3, out)?;
+    }
+    write_close_tag_ln(tag, indent + 2, out)?;
+  }
+  write_close_tag_ln("tgroup", indent + 1, out)?;
+  write_close_tag_ln(tag, indent, out)?;
+
+  Ok(())
+}
+
+/// Writes a cell. Cells with a single paragraph are written on
+/// one line.
+fn write_table_cell<T: io::Write>(input: &ElementSpan, attrs: &str, indent: usize, out: &mut T) -> Result<()> {
+  let style = input.get_attribute("style").unwrap_or("default");
+  let content = input.get_attribute("content").unwrap_or(&input.content);
+
+  write_open_attribute_tag("entry", attrs, indent, out)?;
+  match style {
+    "literal" | "verse" => {
+      write_open_tag("literallayout", 0, out)?;
+      out.write_all(escape_text(content).as_bytes())?;
+      write_close_tag("literallayout", 0, out)?;
+    }
+    "asciidoc" => {
+      out.write_all(b"\n")?;
+      for element in input.children.iter() {
+        write_docbook(element, indent + 1, out)?;
+      }
+      out.write_all(&b"  ".repeat(indent))?;
+    }
+    _ => {
+      let wrapper = match style {
+        "emphasis" => Some("emphasis"),
+        "strong" => Some("emphasis role=\"strong\""),
+        "monospaced" => Some("literal"),
+        _ => None,
+      };
+      let paragraphs: Vec<_> = input
+        .children
+        .iter()
+        .filter(|element| element.element == Element::Paragraph)
+        .collect();
+      for paragraph in paragraphs.iter() {
+        if paragraphs.len() > 1 {
+          write_open_tag("simpara", 0, out)?;
+        }
+        if let Some(wrapper) = wrapper {
+          write_open_tag(wrapper, 0, out)?;
+        }
+        write_inlines(&paragraph.children, out)?;
+        if let Some(wrapper) = wrapper {
+          write_close_tag(wrapper.split(' ').next().unwrap_or(wrapper), 0, out)?;
+        }
+        if paragraphs.len() > 1 {
+          write_close_tag("simpara", 0, out)?;
+        }
+      }
+    }
+  }
+  write_close_tag_ln("entry", 0, out)?;
+
+  Ok(())
+}
+
+fn write_inlines<T: io::Write>(elements: &[ElementSpan], out: &mut T) -> Result<()> {
+  for element in elements.iter() {
+    write_inline(element, out)?;
+  }
+
+  Ok(())
+}
+
+fn write_inline<T: io::Write>(input: &ElementSpan, out: &mut T) -> Result<()> {
+  match &input.element {
+    Element::Text => {
+      let content = input.get_attribute("content").unwrap_or(&input.content);
+      out.write_all(escape_text(content).as_bytes())?;
+    }
+    Element::Styled => {
+      let (open, close) = match input.get_attribute("style").unwrap_or("") {
+        "strong" => ("<emphasis role=\"strong\">", "</emphasis>"),
+        "em" | "emphasis" => ("<emphasis>", "</emphasis>"),
+        "monospaced" => ("<literal>", "</literal>"),
+        "mark" => ("<emphasis role=\"marked\">", "</emphasis>"),
+        "sup" => ("<superscript>", "</superscript>"),
+        "sub" => ("<subscript>", "</subscript>"),
+        "doublequote" => ("<quote>", "</quote>"),
+        "singlequote" => ("&#8216;", "&#8217;"),
+        _ => ("", ""),
+      };
+      // A role (e.g. `[.big]#text#`) becomes a phrase
+      let roles: Vec<_> = input
+        .positional_attributes
+        .iter()
+        .filter_map(|value| value.as_str().strip_prefix('.'))
+        .flat_map(|roles| roles.split('.'))
+        .collect();
+      let (open, close) = match (roles.is_empty(), input.get_attribute("style")) {
+        (true, _) => (open.to_string(), close),
+        (false, Some("mark")) => (format!("<phrase role=\"{}\">", escape_attribute(&roles.join(" "))), "</phrase>"),
+        (false, _) => (format!("<phrase role=\"{}\">{}", escape_attribute(&roles.join(" ")), open), close),
+      };
+      out.write_all(open.as_bytes())?;
+      match input.children.is_empty() {
+        true => out.write_all(escape_text(input.get_attribute("content").unwrap_or("")).as_bytes())?,
+        false => write_inlines(&input.children, out)?,
+      }
+      out.write_all(close.as_bytes())?;
+      if !roles.is_empty() && input.get_attribute("style") != Some("mark") {
+        out.write_all(b"</phrase>")?;
+      }
+    }
+    Element::XRef => {
+      let id = escape_attribute(input.get_attribute("id").unwrap_or(""));
+      match input.get_attribute("content") {
+        Some(content) => out.write_all(format!("<link linkend=\"{}\">{}</link>", id, escape_text(content)).as_bytes())?,
+        None => out.write_all(format!("<xref linkend=\"{}\"/>", id).as_bytes())?,
+      }
+    }
+    Element::Link => {
+      let url = escape_attribute(input.get_attribute("url").unwrap_or(""));
+      let content = match input.positional_attributes.first() {
+        Some(text) => text.as_str(),
+        None => input.get_attribute("url").unwrap_or(""),
+      };
+      out.write_all(format!("<link xl:href=\"{}\">{}</link>", url, escape_text(content)).as_bytes())?;
+    }
+    Element::Footnote => {
+      let id = input.get_attribute("id").map(|id| format!("_footnote_{}", id));
+      match input.get_attribute("content") {
+        Some(content) => {
+          let attrs = match &id {
+            Some(id) => format!(" xml:id=\"{}\"", escape_attribute(id)),
+            None => String::new(),
+          };
+          out.write_all(format!("<footnote{}><simpara>{}</simpara></footnote>", attrs, escape_text(content)).as_bytes())?;
+        }
+        None => out.write_all(
+          format!("<footnoteref linkend=\"{}\"/>", escape_attribute(&id.unwrap_or_default())).as_bytes(),
+        )?,
+      }
+    }
+    Element::Keyboard => {
+      let keys: Vec<_> = crate::util::split_keys(input.get_attribute("keys").unwrap_or(""))
+        .iter()
+        .map(|key| format!("<keycap>{}</keycap>", escape_text(key)))
+        .collect();
+      match keys.len() {
+        1 => out.write_all(keys[0].as_bytes())?,
+        _ => out.write_all(format!("<keycombo>{}</keycombo>", keys.join("")).as_bytes())?,
+      }
+    }
+    Element::Button => {
+      let label = input.get_attribute("label").unwrap_or("");
+      out.write_all(format!("<guibutton>{}</guibutton>", escape_text(label)).as_bytes())?;
+    }
+    Element::Menu => {
+      let menu = format!("<guimenu>{}</guimenu>", escape_text(input.get_attribute("menu").unwrap_or("")));
+      let items: Vec<_> = input
+        .get_attribute("items")
+        .unwrap_or("")
+        .split('>')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect();
+      if items.is_empty() {
+        out.write_all(menu.as_bytes())?;
+      } else {
+        let mut parts = vec![menu];
+        for (index, item) in items.iter().enumerate() {
+          let tag = if index + 1 == items.len() { "guimenuitem" } else { "guisubmenu" };
+          parts.push(format!("<{}>{}</{}>", tag, escape_text(item), tag));
+        }
+        out.write_all(format!("<menuchoice>{}</menuchoice>", parts.join("")).as_bytes())?;
+      }
+    }
+    Element::Passthrough => {
+      out.write_all(input.get_attribute("content").unwrap_or("").as_bytes())?;
+    }
+    Element::Anchor => {
+      let id = escape_attribute(input.get_attribute("id").unwrap_or(""));
+      match input.get_attribute("content") {
+        Some(label) if !label.is_empty() => {
+          out.write_all(format!("<anchor xml:id=\"{}\" xreflabel=\"{}\"/>", id, escape_attribute(label)).as_bytes())?
+        }
+        _ => out.write_all(format!("<anchor xml:id=\"{}\"/>", id).as_bytes())?,
+      }
+    }
+    Element::InlineImage => {
+      out.write_all(format!("<inlinemediaobject>{}</inlinemediaobject>", image_object(input)).as_bytes())?;
+    }
+    Element::LineBreak => {
+      out.write_all(b"<?asciidoc-br?>")?;
+    }
+    Element::InlineMacro if matches!(input.get_attribute("name"), Some("link" | "xref")) => {
+      let target = input.get_attribute("target").unwrap_or("");
+      let text = input.positional_attributes.first().map(|text| text.as_str());
+      // References to other documents are links to their files
+      let link = match (input.get_attribute("name"), target.split_once('#')) {
+        (Some("xref"), Some((_, id))) => format!("linkend=\"{}\"", escape_attribute(id)),
+        (Some("xref"), None) if !target.ends_with(".adoc") => format!("linkend=\"{}\"", escape_attribute(target)),
+        _ => format!("xl:href=\"{}\"", escape_attribute(target)),
+      };
+      match text {
+        Some(text) if !text.is_empty() => {
+          out.write_all(format!("<link {}>{}</link>", link, escape_text(text)).as_bytes())?
+        }
+        _ if link.starts_with("linkend") => out.write_all(format!("<xref {}/>", link).as_bytes())?,
+        _ => out.write_all(format!("<link {}>{}</link>", link, escape_text(target)).as_bytes())?,
+      }
+    }
+    // The markers are written with the listing
+    Element::Callout(_) | Element::Attribute(_) => (),
+    // Unknown macros and wrong formatted text are kept as text
+    _ => {
+      out.write_all(escape_text(&input.content).as_bytes())?;
+    }
+  }
+
+  Ok(())
+}
+
+/// The image and the alternative text of an image. The
+/// alt text defaults to the file name without extension.
+fn image_object(input: &ElementSpan) -> String {
+  let path = input.get_attribute("path").unwrap_or("");
+  let mut attrs = vec![format!("fileref=\"{}\"", escape_attribute(path))];
+  let sizes = input
+    .positional_attributes
+    .iter()
+    .skip(1)
+    .map(|size| size.as_str())
+    .zip(["width", "height"])
+    .map(|(size, name)| (name, size))
+    .chain(
+      ["width", "height"]
+        .iter()
+        .filter_map(|name| input.get_attribute(name).map(|size| (*name, size))),
+    );
+  for (name, size) in sizes {
+    let name = match name {
+      "width" => "contentwidth",
+      _ => "contentdepth",
+    };
+    attrs.push(format!("{}=\"{}\"", name, escape_attribute(size)));
+  }
+  let alt = match input.positional_attributes.first() {
+    Some(alt) => alt.as_str().to_string(),
+    None => {
+      let name = path.split('?').next().unwrap_or(path);
+      let name = name.rsplit('/').next().unwrap_or(name);
+      name.rsplit_once('.').map_or(name, |(name, _)| name).replace(['-', '_'], " ")
+    }
+  };
+
+  format!(
+    "<imageobject><imagedata {}/></imageobject><textobject><phrase>{}</phrase></textobject>",
+    attrs.join(" "),
+    escape_text(&alt)
+  )
+}
+
+// Helper Functions
+//----------------------------------------------------
+
+/// The id of a section is its anchor or made from its title
+fn section_id(title: &ElementSpan) -> String {
+  match title.get_attribute("anchor") {
+    Some(id) => id.to_string(),
+    None => {
+      let name = title.get_attribute("name").unwrap_or("");
+      let id: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+      "_".to_string() + &id
+    }
+  }
+}
+
+/// Adds the anchor of an element as `xml:id` to the attributes
+fn with_id(attrs: &str, input: &ElementSpan) -> String {
+  match (input.get_attribute("anchor"), attrs) {
+    (Some(id), "") => format!("xml:id=\"{}\"", escape_attribute(id)),
+    (Some(id), attrs) => format!("xml:id=\"{}\" {}", escape_attribute(id), attrs),
+    (None, attrs) => attrs.to_string(),
+  }
+}
+
+fn write_block_title<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) -> Result<()> {
+  if let Some(title) = input.get_attribute("title") {
+    write_text_tag("title", title, indent, out)?;
+  }
+  Ok(())
+}
+
+fn escape_text(input: &str) -> String {
+  input.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_attribute(input: &str) -> String {
+  escape_text(input).replace('"', "&quot;")
+}
+
+/// Escapes the text of a block unless its `subs`
+/// leave out the special characters
+fn escape_block_text(input: &ElementSpan, text: &str) -> String {
+  match subs::of(input).contains(&Substitution::SpecialChars) {
+    true => escape_text(text),
+    false => text.to_string(),
+  }
+}
+
+/// Writes a tag with escaped text inside on its own line
+fn write_text_tag<T: io::Write>(tag: &str, text: &str, indent: usize, out: &mut T) -> Result<()> {
+  write_open_tag(tag, indent, out)?;
+  out.write_all(escape_text(text).as_bytes())?;
+  write_close_tag_ln(tag, 0, out)
+}
+
+fn write_open_tag<T: io::Write>(tag: &str, indent: usize, out: &mut T) -> Result<()> {
+  write_open_attribute_tag(tag, "", indent, out)
+}
+
+fn write_open_attribute_tag<T: io::Write>(tag: &str, attrs: &str, indent: usize, out: &mut T) -> Result<()> {
+  out.write_all(&b"  ".repeat(indent))?;
+  out.write_all(format!("<{}", tag).as_bytes())?;
+  if !attrs.is_empty() {
+    out.write_all(b" ")?;
+    out.write_all(attrs.as_bytes())?;
+  }
+  out.write_all(b">")?;
+  Ok(())
+}
+
+fn write_open_tag_ln<T: io::Write>(tag: &str, indent: usize, out: &mut T) -> Result<()> {
+  write_open_tag(tag, indent, out)?;
+  out.write_all(b"\n")?;
+  Ok(())
+}
+
+fn write_open_attribute_tag_ln<T: io::Write>(tag: &str, attrs: &str, indent: usize, out: &mut T) -> Result<()> {
+  write_open_attribute_tag(tag, attrs, indent, out)?;
+  out.write_all(b"\n")?;
+  Ok(())
+}
+
+fn write_close_tag<T: io::Write>(tag: &str, indent: usize, out: &mut T) -> Result<()> {
+  out.write_all(&b"  ".repeat(indent))?;
+  out.write_all(format!("</{}>", tag).as_bytes())?;
+  Ok(())
+}
+
+fn write_close_tag_ln<T: io::Write>(tag: &str, indent: usize, out: &mut T) -> Result<()> {
+  write_close_tag(tag, indent, out)?;
+  out.write_all(b"\n")?;
+  Ok(())
+}
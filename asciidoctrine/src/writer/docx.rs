@@ -1,6 +1,7 @@
 pub use crate::ast::*;
-use crate::{options, Result, AsciidoctrineError};
-use std::io;
+use crate::{options, Result};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use docx_rs::*;
 
 pub struct DocxWriter {}
@@ -12,57 +13,911 @@ impl DocxWriter {
 }
 
 impl<T: io::Write + io::Seek> crate::Writer<T> for DocxWriter {
-  fn write<'a>(&mut self, ast: AST, _args: &options::Opts, out: T) -> Result<()> {
+  fn write<'a>(&mut self, ast: AST, args: &options::Opts, out: T) -> Result<()> {
+    let mut doc = Docx::new();
+    if let Some(path) = &args.reference_doc {
+      // Only the styles are taken, not the content
+      let reference = read_docx(&std::fs::read(path)?)?;
+      doc = doc.styles(reference.styles);
+    }
+    doc = add_default_styles(doc);
+
+    let mut ctx = Context {
+      base: ast
+        .get_attribute("source")
+        .and_then(|source| Path::new(source).parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default(),
+      bookmarks: 0,
+      numberings: 0,
+    };
     let doc = ast
       .elements
       .iter()
-      .try_fold(Docx::new(), |doc, element| write_doc(element, doc))?;
-    doc.build().pack(out).unwrap();
+      .try_fold(doc, |doc, element| write_doc(element, &mut ctx, doc))?;
+
+    let footnotes = ast.footnotes();
+    if footnotes.is_empty() {
+      doc.build().pack(out)?;
+    } else {
+      let mut packed = io::Cursor::new(Vec::new());
+      doc.build().pack(&mut packed)?;
+      add_footnotes(packed.into_inner(), &footnotes, out)?;
+    }
 
     Ok(())
   }
 }
 
-fn paragraph(input: &ElementSpan, out: Paragraph) -> Result<Paragraph> {
+/// The state while writing a document
+struct Context {
+  /// The directory of the document. Images are found relative to it.
+  base: PathBuf,
+  /// The last id given to a bookmark
+  bookmarks: usize,
+  /// The last id given to the numbering of a list
+  numberings: usize,
+}
+
+impl Context {
+  fn next_bookmark(&mut self) -> usize {
+    self.bookmarks += 1;
+    self.bookmarks
+  }
+}
+
+const MONOSPACE: &str = "Courier New";
+
+/// Adds the styles used by the writer, unless they are already
+/// defined (by a reference document)
+fn add_default_styles(doc: Docx) -> Docx {
+  let mono = RunFonts::new().ascii(MONOSPACE).hi_ansi(MONOSPACE);
+  let mut styles = vec![
+    Style::new("Title", StyleType::Paragraph)
+      .name("Title")
+      .size(52)
+      .color("17365D"),
+    Style::new("Caption", StyleType::Paragraph)
+      .name("Caption")
+      .italic()
+      .size(18)
+      .color("1F497D"),
+    Style::new("Quote", StyleType::Paragraph)
+      .name("Quote")
+      .italic()
+      .indent(Some(720), None, Some(720), None),
+    Style::new("SourceCode", StyleType::Paragraph)
+      .name("Source Code")
+      .fonts(mono)
+      .size(18),
+    Style::new("Hyperlink", StyleType::Character)
+      .name("Hyperlink")
+      .color("0563C1")
+      .underline("single"),
+  ];
+  // Word numbers the headings from the first level of sections
+  for (level, size) in [32, 26, 24, 22, 22, 22].iter().enumerate() {
+    styles.push(
+      Style::new(format!("Heading{}", level + 1), StyleType::Paragraph)
+        .name(format!("Heading {}", level + 1))
+        .bold()
+        .size(*size)
+        .color("365F91")
+        .outline_lvl(level),
+    );
+  }
+
+  styles.into_iter().fold(doc, |doc, style| {
+    match doc.styles.find_style_by_id(&style.style_id) {
+      Some(_) => doc,
+      None => doc.add_style(style),
+    }
+  })
+}
+
+/// docx-rs can't write footnotes. So we put a placeholder into the
+/// text for every footnote reference and replace it in the packed
+/// document. The texts are added as an own part (`footnotes.xml`).
+fn add_footnotes<T: io::Write + io::Seek>(
+  packed: Vec<u8>,
+  footnotes: &[&ElementSpan],
+  out: T,
+) -> Result<()> {
+  let mut archive = zip::ZipArchive::new(io::Cursor::new(packed))?;
+  let mut zip = zip::ZipWriter::new(out);
+  let options =
+    zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+  for index in 0..archive.len() {
+    let mut file = archive.by_index(index)?;
+    let name = file.name().to_string();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+
+    let content = match name.as_str() {
+      "word/document.xml" => {
+        let mut document = String::from_utf8_lossy(&content).to_string();
+        for footnote in footnotes.iter() {
+          let number = footnote.get_attribute("number").unwrap_or("");
+          let superscript = "<w:rPr><w:vertAlign w:val=\"superscript\" /></w:rPr>";
+          document = document
+            .replace(
+              &format!("<w:rPr /><w:t xml:space=\"preserve\">{}</w:t>", footnote_marker(number, false)),
+              &format!("{}<w:footnoteReference w:id=\"{}\" />", superscript, number),
+            )
+            // Word allows only one reference per footnote
+            .replace(
+              &format!("<w:rPr /><w:t xml:space=\"preserve\">{}</w:t>", footnote_marker(number, true)),
+              &format!("{}<w:t>{}</w:t>", superscript, number),
+            );
+        }
+        document.into_bytes()
+      }
+      "word/_rels/document.xml.rels" => String::from_utf8_lossy(&content)
+        .replace(
+          "</Relationships>",
+          "<Relationship Id=\"rIdFootnotes\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/footnotes\" Target=\"footnotes.xml\" /></Relationships>",
+        )
+        .into_bytes(),
+      "[Content_Types].xml" => String::from_utf8_lossy(&content)
+        .replace(
+          "</Types>",
+          "<Override ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml\" PartName=\"/word/footnotes.xml\"></Override></Types>",
+        )
+        .into_bytes(),
+      _ => content,
+    };
+
+    zip.start_file(name, options)?;
+    zip.write_all(&content)?;
+  }
+
+  zip.start_file("word/footnotes.xml", options)?;
+  zip.write_all(footnotes_xml(footnotes).as_bytes())?;
+  zip.finish()?;
+
+  Ok(())
+}
+
+fn footnote_marker(number: &str, reference: bool) -> String {
+  let kind = if reference { "footnoteref" } else { "footnote" };
+  format!("asciidoctrine-{}-{}", kind, number)
+}
+
+fn footnotes_xml(footnotes: &[&ElementSpan]) -> String {
+  let mut xml = String::from(concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<w:footnotes xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">"#,
+    r#"<w:footnote w:type="separator" w:id="-1"><w:p><w:r><w:separator /></w:r></w:p></w:footnote>"#,
+    r#"<w:footnote w:type="continuationSeparator" w:id="0"><w:p><w:r><w:continuationSeparator /></w:r></w:p></w:footnote>"#,
+  ));
+
+  for footnote in footnotes.iter() {
+    let content = footnote
+      .get_attribute("content")
+      .unwrap_or("")
+      .replace('&', "&amp;")
+      .replace('<', "&lt;")
+      .replace('>', "&gt;");
+    xml.push_str(&format!(
+      concat!(
+        r#"<w:footnote w:id="{}"><w:p>"#,
+        r#"<w:r><w:rPr><w:vertAlign w:val="superscript" /></w:rPr><w:footnoteRef /></w:r>"#,
+        r#"<w:r><w:t xml:space="preserve"> {}</w:t></w:r>"#,
+        r#"</w:p></w:footnote>"#,
+      ),
+      footnote.get_attribute("number").unwrap_or(""),
+      content
+    ));
+  }
+  xml.push_str("</w:footnotes>");
+
+  xml
+}
+
+
+/// The character formatting of the text inside styled elements
+#[derive(Clone, Copy, Default)]
+struct Format {
+  bold: bool,
+  italic: bool,
+  monospaced: bool,
+  highlight: bool,
+  superscript: bool,
+  subscript: bool,
+  link: bool,
+}
+
+impl Format {
+  fn run(&self, text: &str) -> Run {
+    let mut run = Run::new().add_text(text);
+    if self.bold {
+      run = run.bold();
+    }
+    if self.italic {
+      run = run.italic();
+    }
+    if self.monospaced {
+      run = run.fonts(RunFonts::new().ascii(MONOSPACE).hi_ansi(MONOSPACE));
+    }
+    if self.highlight {
+      run = run.highlight("yellow");
+    }
+    if self.link {
+      run = run.style("Hyperlink");
+    }
+    if self.superscript || self.subscript {
+      let align = match self.superscript {
+        true => VertAlignType::SuperScript,
+        false => VertAlignType::SubScript,
+      };
+      run.run_property = run.run_property.vert_align(align);
+    }
+
+    run
+  }
+}
+
+fn paragraph(input: &ElementSpan, format: Format, ctx: &mut Context, out: Paragraph) -> Result<Paragraph> {
   match &input.element {
-    Element::Text => {
-      let r = Run::new();
-      Ok(out.add_run(r.add_text(input.content.to_string())))
+    Element::Text | Element::Passthrough => {
+      // The lines of a paragraph are joined (docx-rs drops the line breaks)
+      let content = input.get_attribute("content").unwrap_or(&input.content);
+      Ok(out.add_run(format.run(&content.replace('\n', " "))))
+    }
+    Element::LineBreak => Ok(out.add_run(Run::new().add_break(BreakType::TextWrapping))),
+    Element::Styled => {
+      let style = input.get_attribute("style").unwrap_or("");
+      let mut inner = format;
+      match style {
+        "strong" => inner.bold = true,
+        "em" => inner.italic = true,
+        "monospaced" => inner.monospaced = true,
+        "mark" => inner.highlight = true,
+        "sup" => inner.superscript = true,
+        "sub" => inner.subscript = true,
+        _ => (),
+      }
+      let (open, close) = match style {
+        "doublequote" => ("\u{201c}", "\u{201d}"),
+        "singlequote" => ("\u{2018}", "\u{2019}"),
+        _ => ("", ""),
+      };
+
+      let mut out = out;
+      if !open.is_empty() {
+        out = out.add_run(format.run(open));
+      }
+      out = match input.children.is_empty() {
+        true => out.add_run(inner.run(input.get_attribute("content").unwrap_or(""))),
+        false => input
+          .children
+          .iter()
+          .try_fold(out, |p, element| paragraph(element, inner, ctx, p))?,
+      };
+      if !close.is_empty() {
+        out = out.add_run(format.run(close));
+      }
+      Ok(out)
     }
     Element::Link => {
       let url = input.get_attribute("url").unwrap_or("");
-      let content = match input.positional_attributes.get(0) {
-        Some(value) => match value {
-          AttributeValue::Ref(value) => value.to_string(),
-          AttributeValue::String(value) => value.clone(),
-        },
-        None => "".to_string(),
+      let content = match input.positional_attributes.first() {
+        Some(value) if !value.as_str().is_empty() => value.as_str(),
+        _ => url,
+      };
+      let link = Format { link: true, ..format };
+
+      Ok(out.add_hyperlink(Hyperlink::new(url, HyperlinkType::External).add_run(link.run(content))))
+    }
+    Element::XRef => {
+      let id = input.get_attribute("id").unwrap_or("");
+      let content = input.get_attribute("content").unwrap_or(id);
+      let link = Format { link: true, ..format };
+
+      Ok(out.add_hyperlink(Hyperlink::new(id, HyperlinkType::Anchor).add_run(link.run(content))))
+    }
+    Element::InlineMacro if matches!(input.get_attribute("name"), Some("link" | "xref")) => {
+      let target = input.get_attribute("target").unwrap_or("");
+      let content = match input.positional_attributes.first() {
+        Some(value) if !value.as_str().is_empty() => value.as_str(),
+        _ => target,
+      };
+      let link = Format { link: true, ..format };
+      let hyperlink = match input.get_attribute("name") {
+        // References to other documents stay links to the files
+        Some("xref") if !target.contains(".adoc") => {
+          Hyperlink::new(target.trim_start_matches('#'), HyperlinkType::Anchor)
+        }
+        _ => Hyperlink::new(target, HyperlinkType::External),
+      };
+
+      Ok(out.add_hyperlink(hyperlink.add_run(link.run(content))))
+    }
+    Element::Anchor => {
+      let id = ctx.next_bookmark();
+      Ok(
+        out
+          .add_bookmark_start(id, input.get_attribute("id").unwrap_or(""))
+          .add_bookmark_end(id),
+      )
+    }
+    Element::Footnote => {
+      // Replaced by a real footnote reference after packing
+      let number = input.get_attribute("number").unwrap_or("");
+      let reference = input.get_attribute("content").is_none();
+      Ok(out.add_run(Run::new().add_text(footnote_marker(number, reference))))
+    }
+    Element::InlineImage => {
+      let path = input.get_attribute("path").unwrap_or("");
+      Ok(out.add_run(image(input, path, ctx)))
+    }
+    Element::Keyboard => {
+      let keys = Format { monospaced: true, ..format };
+      Ok(out.add_run(keys.run(input.get_attribute("keys").unwrap_or(""))))
+    }
+    Element::Button => {
+      let button = Format { bold: true, ..format };
+      let label = format!("[{}]", input.get_attribute("label").unwrap_or(""));
+      Ok(out.add_run(button.run(&label)))
+    }
+    Element::Menu => {
+      let mut parts = vec![input.get_attribute("menu").unwrap_or("")];
+      parts.extend(
+        input
+          .get_attribute("items")
+          .unwrap_or("")
+          .split('>')
+          .map(str::trim)
+          .filter(|item| !item.is_empty()),
+      );
+      let menu = Format { bold: true, ..format };
+      Ok(out.add_run(menu.run(&parts.join(" \u{203a} "))))
+    }
+    Element::Callout(_) | Element::Attribute(_) | Element::Comment => Ok(out),
+    _ => Ok(out.add_run(format.run(&input.content))),
+  }
+}
+
+/// Writes the inline elements of a paragraph
+fn paragraph_content(input: &ElementSpan, format: Format, ctx: &mut Context, out: Paragraph) -> Result<Paragraph> {
+  input
+    .children
+    .iter()
+    .try_fold(out, |p, element| paragraph(element, format, ctx, p))
+}
+
+/// Creates a run with an image. Images which can't be read are
+/// replaced by their alt text.
+fn image(input: &ElementSpan, path: &str, ctx: &Context) -> Run {
+  let alt = input
+    .positional_attributes
+    .first()
+    .map(|alt| alt.as_str())
+    .unwrap_or(path);
+  let data = match std::fs::read(ctx.base.join(path)) {
+    Ok(data) => data,
+    Err(error) => {
+      warn!("image `{}` can't be written to docx: {}", path, error);
+      return Run::new().add_text(format!("[{}]", alt));
+    }
+  };
+  // docx-rs panics on images it can't decode
+  let (width, height) = match image::load_from_memory(&data) {
+    Ok(image) => (image.width(), image.height()),
+    Err(error) => {
+      warn!("image `{}` can't be written to docx: {}", path, error);
+      return Run::new().add_text(format!("[{}]", alt));
+    }
+  };
+
+  let size = |index: usize, key: &str| {
+    input
+      .positional_attributes
+      .get(index)
+      .map(|value| value.as_str())
+      .or(input.get_attribute(key))
+      .and_then(|value| value.trim_end_matches("px").parse::<f64>().ok())
+  };
+  let ratio = height as f64 / width as f64;
+  let mut width = match (size(1, "width"), size(2, "height")) {
+    (Some(width), _) => width,
+    (None, Some(height)) => height / ratio,
+    (None, None) => width as f64,
+  };
+  // A pixel has 9525 emu and a twip 635
+  let max_width = TEXT_WIDTH * 635.0 / 9525.0;
+  if width > max_width {
+    width = max_width;
+  }
+  let emu = |pixels: f64| (pixels * 9525.0) as u32;
+
+  Run::new().add_image(Pic::new(&data).size(emu(width), emu(width * ratio)))
+}
+
+/// Adds a block to a table cell (of a table or the
+/// shaded cell of an admonition)
+fn cell_content(input: &ElementSpan, ctx: &mut Context, out: TableCell) -> Result<TableCell> {
+  match &input.element {
+    Element::Paragraph => {
+      let p = paragraph_content(input, Format::default(), ctx, Paragraph::new())?;
+      Ok(out.add_paragraph(p))
+    }
+    Element::TypedBlock {
+      kind: BlockType::Listing | BlockType::Literal,
+    } => Ok(out.add_paragraph(listing(input))),
+    Element::List(_) | Element::Image | Element::Admonition(_) | Element::TypedBlock { .. } => {
+      // The blocks are written into a document of their own and moved into the cell
+      let inner = write_doc(input, ctx, Docx::new())?;
+      Ok(inner.document.children.into_iter().fold(out, |cell, child| match child {
+        DocumentChild::Paragraph(p) => cell.add_paragraph(*p),
+        DocumentChild::Table(table) => cell.add_table(*table),
+        _ => cell,
+      }))
+    }
+    _ => {
+      let content = input.get_attribute("content").unwrap_or(&input.content);
+      Ok(out.add_paragraph(Paragraph::new().add_run(Run::new().add_text(content))))
+    }
+  }
+}
+
+/// The width of the text on an A4 page (in twips) with
+/// the default margins of docx-rs
+const TEXT_WIDTH: f64 = 8504.0;
+
+/// Writes a table. Cells spanning multiple rows are merged with
+/// (empty) continuation cells in the rows below.
+fn table(input: &ElementSpan, ctx: &mut Context) -> Result<Table> {
+  let grid: Vec<usize> = input
+    .get_attribute("colwidths")
+    .unwrap_or("")
+    .split(',')
+    .filter_map(|width| width.parse::<f64>().ok())
+    .map(|width| (width * TEXT_WIDTH / 100.0) as usize)
+    .collect();
+  // The rows left to merge and the colspan for every column
+  // where a cell spanning rows starts
+  let mut merges: Vec<Option<(usize, usize)>> = vec![None; grid.len()];
+
+  let mut rows = Vec::new();
+  for row in input.children.iter().filter(|row| row.element == Element::TableRow) {
+    let continued = merges.clone();
+    let mut cells = Vec::new();
+    let mut children = row.children.iter();
+    let mut column = 0;
+    while column < grid.len() {
+      if let Some((_, colspan)) = continued[column] {
+        let cell = TableCell::new()
+          .vertical_merge(VMergeType::Continue)
+          .grid_span(colspan)
+          .add_paragraph(Paragraph::new());
+        cells.push(cell);
+        column += colspan;
+        continue;
+      }
+
+      let input = match children.next() {
+        Some(input) => input,
+        None => break,
+      };
+      let span = |name| {
+        input
+          .get_attribute(name)
+          .and_then(|span| span.parse::<usize>().ok())
+          .unwrap_or(1)
+      };
+      let (colspan, rowspan) = (span("colspan"), span("rowspan"));
+
+      let mut cell = table_cell(input, ctx)?.grid_span(colspan);
+      if rowspan > 1 {
+        cell = cell.vertical_merge(VMergeType::Restart);
+        merges[column] = Some((rowspan, colspan));
+      }
+      cells.push(cell);
+      column += colspan;
+    }
+    rows.push(TableRow::new(cells));
+
+    for merge in merges.iter_mut() {
+      *merge = match merge {
+        Some((rows, colspan)) if *rows > 1 => Some((*rows - 1, *colspan)),
+        _ => None,
+      };
+    }
+  }
+
+  Ok(Table::new(rows).set_grid(grid))
+}
+
+fn table_cell(input: &ElementSpan, ctx: &mut Context) -> Result<TableCell> {
+  let style = input.get_attribute("style").unwrap_or("default");
+  let align = match input.get_attribute("halign") {
+    Some("center") => AlignmentType::Center,
+    Some("right") => AlignmentType::Right,
+    _ => AlignmentType::Left,
+  };
+  let cell = TableCell::new().vertical_align(match input.get_attribute("valign") {
+    Some("middle") => VAlignType::Center,
+    Some("bottom") => VAlignType::Bottom,
+    _ => VAlignType::Top,
+  });
+
+  match style {
+    "default" | "asciidoc" => {
+      let cell = input.children.iter().try_fold(cell, |cell, element| match element.element {
+        Element::Paragraph => {
+          let p = paragraph_content(element, Format::default(), ctx, Paragraph::new().align(align))?;
+          Ok(cell.add_paragraph(p))
+        }
+        _ => cell_content(element, ctx, cell),
+      })?;
+      // A cell always needs a paragraph
+      Ok(match input.children.is_empty() {
+        true => cell.add_paragraph(Paragraph::new().align(align)),
+        false => cell,
+      })
+    }
+    _ => {
+      let content = input.get_attribute("content").unwrap_or(&input.content);
+      let mut run = Run::new();
+      for (index, line) in content.lines().enumerate() {
+        if index > 0 {
+          run = run.add_break(BreakType::TextWrapping);
+        }
+        run = run.add_text(line);
+      }
+      run = match style {
+        "header" | "strong" => run.bold(),
+        "emphasis" => run.italic(),
+        "monospaced" | "literal" => run.fonts(RunFonts::new().ascii(MONOSPACE)),
+        _ => run,
+      };
+
+      Ok(cell.add_paragraph(Paragraph::new().add_run(run).align(align)))
+    }
+  }
+}
+
+/// Writes an item of a labeled list as hanging paragraph with
+/// the term in front of the first paragraph of the description
+fn labeled_item(input: &ElementSpan, ctx: &mut Context, out: Docx) -> Result<Docx> {
+  let level = match input.element {
+    Element::ListItem(level) => level as i32,
+    _ => 1,
+  };
+  let left = 720 * (level + 1);
+  let term = Run::new()
+    .add_text(input.get_attribute("term").unwrap_or(""))
+    .bold();
+  let p = Paragraph::new()
+    .add_run(term)
+    .indent(Some(left), Some(SpecialIndentType::Hanging(1440)), None, None);
+
+  list_item_content(input, p, left, ctx, out)
+}
+
+/// Writes the blocks of a list item. The first paragraph is
+/// added to `first` (with the marker of the item), the others
+/// are indented like the text of the item.
+fn list_item_content(input: &ElementSpan, first: Paragraph, left: i32, ctx: &mut Context, out: Docx) -> Result<Docx> {
+  let mut p = first;
+  let mut children = input.children.iter().peekable();
+  if let Some(element) = children.peek() {
+    if element.element == Element::Paragraph {
+      p = paragraph_content(element, Format::default(), ctx, p.add_run(Run::new().add_tab()))?;
+      children.next();
+    }
+  }
+
+  children.try_fold(out.add_paragraph(p), |doc, element| match &element.element {
+    Element::Paragraph => {
+      let p = paragraph_content(
+        element,
+        Format::default(),
+        ctx,
+        Paragraph::new().indent(Some(left), None, None, None),
+      )?;
+      Ok(doc.add_paragraph(p))
+    }
+    _ => write_doc(element, ctx, doc),
+  })
+}
+
+/// Adds a numbering for a bulleted or numbered list. Every list
+/// gets its own numbering, so the numbers start again at one.
+fn add_numbering(kind: &ListType, ctx: &mut Context, out: Docx) -> (usize, Docx) {
+  ctx.numberings += 1;
+  let id = ctx.numberings;
+  let mut numbering = AbstractNumbering::new(id);
+  for level in 0..9 {
+    let (format, text) = match kind {
+      ListType::Number => {
+        let format = ["decimal", "lowerLetter", "lowerRoman"][level % 3];
+        (format, format!("%{}.", level + 1))
+      }
+      _ => ("bullet", ["\u{2022}", "\u{25e6}", "\u{25aa}"][level % 3].to_string()),
+    };
+    let indent = 720 * (level as i32 + 1);
+    numbering = numbering.add_level(
+      Level::new(
+        level,
+        Start::new(1),
+        NumberFormat::new(format),
+        LevelText::new(text),
+        LevelJc::new("left"),
+      )
+      .indent(Some(indent), Some(SpecialIndentType::Hanging(360)), None, None),
+    );
+  }
+
+  let out = out
+    .add_abstract_numbering(numbering)
+    .add_numbering(Numbering::new(id, id));
+  (id, out)
+}
+
+/// Writes a bulleted or numbered list. Nested lists are either
+/// given by the level of the items or as children of an item.
+fn list(input: &ElementSpan, kind: &ListType, ctx: &mut Context, out: Docx) -> Result<Docx> {
+  let (id, out) = add_numbering(kind, ctx, out);
+
+  input.children.iter().try_fold(out, |doc, item| {
+    let level = match item.element {
+      Element::ListItem(level) => level.max(1) as usize,
+      _ => 1,
+    };
+    let left = 720 * (level as i32 + 1);
+    let p = Paragraph::new().numbering(NumberingId::new(id), IndentLevel::new(level - 1));
+
+    let mut children = item.children.iter().peekable();
+    let mut doc = match children.next_if(|element| element.element == Element::Paragraph) {
+      Some(element) => doc.add_paragraph(paragraph_content(element, Format::default(), ctx, p)?),
+      None => doc.add_paragraph(p),
+    };
+    for element in children {
+      doc = match &element.element {
+        Element::Paragraph => {
+          let p = Paragraph::new().indent(Some(left), None, None, None);
+          doc.add_paragraph(paragraph_content(element, Format::default(), ctx, p)?)
+        }
+        _ => write_doc(element, ctx, doc)?,
       };
+    }
+
+    Ok(doc)
+  })
+}
 
-      Ok(out.add_hyperlink(Hyperlink::new(url, HyperlinkType::External)))
+/// Writes the lines of a listing into one paragraph. The callout
+/// markers are replaced by their numbers.
+fn listing(input: &ElementSpan) -> Paragraph {
+  let content = input.get_attribute("content").unwrap_or(&input.content);
+  let mut run = Run::new();
+  for (index, line) in content.split('\n').enumerate() {
+    if index > 0 {
+      run = run.add_break(BreakType::TextWrapping);
     }
-    _ => Err(AsciidoctrineError::MalformedAst),
+    let numbers: Vec<_> = input
+      .children
+      .iter()
+      .filter(|callout| callout.get_attribute("line") == Some(&index.to_string()))
+      .filter_map(|callout| match callout.element {
+        Element::Callout(number) => Some(format!("({})", number)),
+        _ => None,
+      })
+      .collect();
+    let line = match (numbers.is_empty(), crate::util::split_callouts(line)) {
+      (false, Some((code, _))) => format!("{} {}", code.trim_end(), numbers.join(" ")),
+      _ => line.to_string(),
+    };
+    for (index, part) in line.split('\t').enumerate() {
+      if index > 0 {
+        run = run.add_tab();
+      }
+      if !part.is_empty() {
+        run = run.add_text(part);
+      }
+    }
+  }
+
+  Paragraph::new().style("SourceCode").keep_lines(true).add_run(run)
+}
+
+/// Writes the title of a block (e.g. `.Title`) as caption
+fn block_title(input: &ElementSpan, out: Docx) -> Docx {
+  match input.get_attribute("title") {
+    Some(title) => out.add_paragraph(
+      Paragraph::new()
+        .style("Caption")
+        .keep_next(true)
+        .add_run(Run::new().add_text(title)),
+    ),
+    None => out,
+  }
+}
+
+/// Writes the author and source of a quote or verse
+fn attribution(input: &ElementSpan, out: Docx) -> Docx {
+  let parts: Vec<_> = ["attribution", "citetitle"]
+    .iter()
+    .filter_map(|key| input.get_attribute(key))
+    .collect();
+  match parts.is_empty() {
+    true => out,
+    false => out.add_paragraph(
+      Paragraph::new()
+        .style("Quote")
+        .align(AlignmentType::Right)
+        .add_run(Run::new().add_text(format!("\u{2014} {}", parts.join(", ")))),
+    ),
+  }
+}
+
+/// The id of a section title, like in html
+fn section_id(input: &ElementSpan) -> String {
+  match input.get_attribute("anchor") {
+    Some(id) => id.to_string(),
+    None => "_".to_string() + &input.get_attribute("name").unwrap_or("").replace(' ', "_").to_lowercase(),
+  }
+}
+
+fn admonition_color(kind: &AdmonitionType) -> &'static str {
+  match kind {
+    AdmonitionType::Note => "DEEAF6",
+    AdmonitionType::Tip => "E2EFDA",
+    AdmonitionType::Important => "FFF2CC",
+    AdmonitionType::Caution => "FCE4D6",
+    AdmonitionType::Warning => "F8CBAD",
   }
 }
 
-fn write_doc(input: &ElementSpan, out: Docx) -> Result<Docx> {
+/// Writes blocks into a shaded table cell (for admonitions,
+/// sidebars and examples)
+fn shaded_block(input: &ElementSpan, label: Option<&str>, fill: &str, ctx: &mut Context, out: Docx) -> Result<Docx> {
+  let cell = TableCell::new().shading(Shading::new().shd_type(ShdType::Clear).fill(fill));
+  let cell = match label {
+    Some(label) => cell.add_paragraph(Paragraph::new().add_run(Run::new().add_text(label).bold())),
+    None => cell,
+  };
+  let cell = input
+    .children
+    .iter()
+    .try_fold(cell, |cell, element| cell_content(element, ctx, cell))?;
+  Ok(out.add_table(Table::new(vec![TableRow::new(vec![cell])])))
+}
+
+fn write_doc(input: &ElementSpan, ctx: &mut Context, out: Docx) -> Result<Docx> {
+  // Blocks with an anchor (`[[id]]`) are enclosed in a bookmark
+  // to be the target of references
+  match (&input.element, input.get_attribute("anchor")) {
+    (Element::Title { .. }, _) | (_, None) => write_block(input, ctx, out),
+    (_, Some(anchor)) => {
+      let id = ctx.next_bookmark();
+      let out = write_block(input, ctx, out.add_bookmark_start(id, anchor))?;
+      Ok(out.add_bookmark_end(id))
+    }
+  }
+}
+
+fn write_block(input: &ElementSpan, ctx: &mut Context, out: Docx) -> Result<Docx> {
   match &input.element {
     Element::Title { level } => {
       let title = input.get_attribute("name").unwrap_or("");
-      let p = Paragraph::new()
-        .add_run(Run::new().add_text(title))
-        .outline_lvl(*level as usize)
-        .style(&format!("Heading{}", level));
-      Ok(out.add_paragraph(p))
+      let p = match level {
+        1 => Paragraph::new().style("Title"),
+        level => {
+          let id = ctx.next_bookmark();
+          Paragraph::new()
+            .style(&format!("Heading{}", level - 1))
+            .add_bookmark_start(id, section_id(input))
+            .add_bookmark_end(id)
+        }
+      };
+      Ok(out.add_paragraph(p.keep_next(true).add_run(Run::new().add_text(title))))
     }
     Element::Paragraph => {
-      let p = input
+      let out = block_title(input, out);
+      let p = paragraph_content(input, Format::default(), ctx, Paragraph::new())?;
+      Ok(out.add_paragraph(p))
+    }
+    Element::IncludeElement(include) => include
+      .inner
+      .elements
+      .iter()
+      .try_fold(out, |doc, element| write_doc(element, ctx, doc)),
+    Element::Section { .. } => input
+      .children
+      .iter()
+      .try_fold(out, |doc, element| write_doc(element, ctx, doc)),
+    Element::Admonition(kind) => shaded_block(input, Some(kind.caption()), admonition_color(kind), ctx, out),
+    Element::List(ListType::Labeled) => {
+      let out = block_title(input, out);
+      input
         .children
         .iter()
-        .try_fold(Paragraph::new(), |p, element| paragraph(element, p))?;
+        .try_fold(out, |doc, item| labeled_item(item, ctx, doc))
+    }
+    Element::List(ListType::Callout) => input.children.iter().try_fold(out, |doc, item| {
+      let number = format!("({})", item.get_attribute("callout").unwrap_or(""));
+      let p = Paragraph::new()
+        .add_run(Run::new().add_text(number).bold())
+        .indent(Some(720), Some(SpecialIndentType::Hanging(720)), None, None);
+      list_item_content(item, p, 720, ctx, doc)
+    }),
+    Element::List(kind) => {
+      let out = block_title(input, out);
+      list(input, kind, ctx, out)
+    }
+    Element::TypedBlock { kind } => match kind {
+      BlockType::Listing | BlockType::Literal => {
+        let out = block_title(input, out);
+        Ok(out.add_paragraph(listing(input)))
+      }
+      BlockType::Quote => {
+        let out = block_title(input, out);
+        let out = input.children.iter().try_fold(out, |doc, element| match element.element {
+          Element::Paragraph => {
+            let p = paragraph_content(element, Format::default(), ctx, Paragraph::new().style("Quote"))?;
+            Ok(doc.add_paragraph(p))
+          }
+          _ => write_doc(element, ctx, doc),
+        })?;
+        Ok(attribution(input, out))
+      }
+      BlockType::Verse => {
+        let out = block_title(input, out);
+        let content = input.get_attribute("content").unwrap_or(&input.content);
+        let mut run = Run::new();
+        for (index, line) in content.lines().enumerate() {
+          if index > 0 {
+            run = run.add_break(BreakType::TextWrapping);
+          }
+          run = run.add_text(line);
+        }
+        let out = out.add_paragraph(Paragraph::new().style("Quote").add_run(run));
+        Ok(attribution(input, out))
+      }
+      BlockType::Sidebar | BlockType::Example => {
+        shaded_block(input, input.get_attribute("title"), "F2F2F2", ctx, out)
+      }
+      BlockType::Open => {
+        let out = block_title(input, out);
+        input
+          .children
+          .iter()
+          .try_fold(out, |doc, element| write_doc(element, ctx, doc))
+      }
+      // Passthrough content is meant for other formats
+      BlockType::Passtrough | BlockType::Comment => Ok(out),
+    },
+    Element::Image => {
+      let path = input.get_attribute("path").unwrap_or("");
+      let p = Paragraph::new().keep_next(input.get_attribute("title").is_some());
+      let out = out.add_paragraph(p.add_run(image(input, path, ctx)));
+      Ok(block_title(input, out))
+    }
+    Element::Table => {
+      let out = block_title(input, out);
+      Ok(out.add_table(table(input, ctx)?))
+    }
+    Element::Anchor => {
+      let id = ctx.next_bookmark();
+      let name = input.get_attribute("id").unwrap_or("");
+      Ok(out.add_bookmark_start(id, name).add_bookmark_end(id))
+    }
+    Element::Attribute(_) | Element::Comment => Ok(out),
+    // Inline elements outside of a paragraph
+    Element::Text
+    | Element::Styled
+    | Element::Link
+    | Element::XRef
+    | Element::Footnote
+    | Element::InlineImage
+    | Element::InlineMacro => {
+      let p = paragraph(input, Format::default(), ctx, Paragraph::new())?;
       Ok(out.add_paragraph(p))
     }
-    Element::Text | Element::Link => Err(AsciidoctrineError::MalformedAst),
     _ => {
       error!(
         "<NOT-YET-SUPPORTED:{:?}>{}</NOT-YET-SUPPORTED>\n",
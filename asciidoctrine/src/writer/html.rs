@@ -1,5 +1,6 @@
 pub use crate::ast::*;
 use crate::util::Environment;
+use crate::subs::{self, Substitution};
 use crate::{options, Result, AsciidoctrineError};
 use std::io;
 use tera::{Context, Tera};
@@ -23,6 +24,7 @@ impl<T: io::Write> crate::Writer<T> for HtmlWriter {
     for element in ast.elements.iter() {
       write_html(element, 0, &mut buf)?;
     }
+    write_footnotes(&ast.footnotes(), &mut buf)?;
     let bytes = buf.into_inner()?;
 
     let mut context = Context::new();
@@ -80,7 +82,7 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
             let title = input
               .get_attribute("name")
               .ok_or(AsciidoctrineError::MalformedAst)?;
-            "_".to_string() + &title.replace(" ", "_").to_lowercase()
+            section_id(title)
           }
         };
         let attrs = format!("id=\"{}\"", id);
@@ -94,17 +96,43 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
       write_tag("p", input, indent, out)?;
       out.write_all(b"\n")?;
     }
+    Element::List(ListType::Callout) => {
+      write_open_attribute_tag_ln("div", "class=\"colist arabic\"", indent, out)?;
+      write_open_tag_ln("table", indent + 1, out)?;
+      for item in input.children.iter() {
+        let number = item.get_attribute("callout").unwrap_or("");
+        write_open_tag_ln("tr", indent + 2, out)?;
+        write_open_tag("td", indent + 3, out)?;
+        let badge = format!("<i class=\"conum\" data-value=\"{}\"></i><b>{}</b>", number, number);
+        match item.get_attribute("target") {
+          Some(id) => out.write_all(format!("<a href=\"#{}\">{}</a>", id, badge).as_bytes())?,
+          None => out.write_all(badge.as_bytes())?,
+        }
+        write_close_tag_ln("td", 0, out)?;
+        write_open_tag_ln("td", indent + 3, out)?;
+        for element in item.children.iter() {
+          write_html(element, indent + 4, out)?;
+        }
+        write_close_tag_ln("td", indent + 3, out)?;
+        write_close_tag_ln("tr", indent + 2, out)?;
+      }
+      write_close_tag_ln("table", indent + 1, out)?;
+      write_close_tag_ln("div", indent, out)?;
+    }
+    Element::List(ListType::Labeled) => {
+      write_labeled_list(&input.children, indent, out)?;
+    }
     Element::List(list_type) => {
       let list_element = match list_type {
         ListType::Bullet => "ul",
-        ListType::Number => "ol",
+        ListType::Number | ListType::Callout | ListType::Labeled => "ol",
       };
 
       let mut current_level = 0;
       for element in input.children.iter() {
         if let Element::ListItem(item_level) = element.element {
           let attrs = match list_type {
-            ListType::Bullet => "",
+            ListType::Bullet | ListType::Callout | ListType::Labeled => "",
             ListType::Number => {
               if item_level % 2 == 0 {
                 "class=\"loweralpha\" type=\"a\""
@@ -128,22 +156,51 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
                 write_close_tag_ln(list_element, indent + offset - (2 * i) - 1, out)?;
               }
             }
-            write_close_tag_ln("li", indent + item_level, out)?;
-            write_open_tag_ln("li", indent + item_level, out)?;
+            write_close_tag_ln("li", indent + item_level + offset, out)?;
+            write_open_tag_ln("li", indent + item_level + offset, out)?;
           }
           write_html(element, indent + item_level + offset, out)?;
 
           current_level = item_level;
         }
       }
-      write_close_tag_ln("li", indent + 1, out)?;
-      write_close_tag_ln(list_element, indent, out)?;
+      for level in (1..=current_level.max(1)).rev() {
+        write_close_tag_ln("li", indent + (level * 2) - 1, out)?;
+        write_close_tag_ln(list_element, indent + (level * 2) - 2, out)?;
+      }
     }
     Element::ListItem(_) => {
       for element in input.children.iter() {
         write_html(element, indent + 1, out)?;
       }
     }
+    Element::Admonition(kind) => {
+      let mut attrs = format!("class=\"admonitionblock {}\"", kind.caption().to_lowercase());
+      if let Some(id) = input.get_attribute("anchor") {
+        attrs = format!("id=\"{}\" {}", id, attrs);
+      }
+      write_open_attribute_tag_ln("div", &attrs, indent, out)?;
+      write_open_tag_ln("table", indent + 1, out)?;
+      write_open_tag_ln("tr", indent + 2, out)?;
+      write_open_attribute_tag_ln("td", "class=\"icon\"", indent + 3, out)?;
+      write_open_attribute_tag("div", "class=\"title\"", indent + 4, out)?;
+      out.write_all(kind.caption().as_bytes())?;
+      write_close_tag_ln("div", 0, out)?;
+      write_close_tag_ln("td", indent + 3, out)?;
+      write_open_attribute_tag_ln("td", "class=\"content\"", indent + 3, out)?;
+      if let Some(title) = input.get_attribute("title") {
+        write_open_attribute_tag("div", "class=\"title\"", indent + 4, out)?;
+        out.write_all(escape_text(title).as_bytes())?;
+        write_close_tag_ln("div", 0, out)?;
+      }
+      for element in input.children.iter() {
+        write_html(element, indent + 4, out)?;
+      }
+      write_close_tag_ln("td", indent + 3, out)?;
+      write_close_tag_ln("tr", indent + 2, out)?;
+      write_close_tag_ln("table", indent + 1, out)?;
+      write_close_tag_ln("div", indent, out)?;
+    }
     Element::TypedBlock { kind } => {
       if kind == &BlockType::Comment {
         // Comments are not printed in html
@@ -166,7 +223,7 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
           write_open_tag("details", indent, out)?;
         }
 
-        let title = input.get_attribute("title").unwrap_or("Details");
+        let title = escape_text(input.get_attribute("title").unwrap_or("Details"));
         out.write_all(&format!("\n  <summary class=\"title\">{}</summary>\n", title).as_bytes())?;
 
         write_open_tag_ln("div class=\"content\"", indent + 1, out)?;
@@ -181,6 +238,55 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
         return Ok(());
       }
 
+      match kind {
+        BlockType::Passtrough => {
+          // Passthrough content is written as it is
+          let content = input.get_attribute("content").unwrap_or(&input.content);
+          out.write_all(content.as_bytes())?;
+          out.write_all(b"\n")?;
+          return Ok(());
+        }
+        BlockType::Sidebar | BlockType::Open => {
+          let class = match kind {
+            BlockType::Sidebar => "sidebarblock",
+            _ => "openblock",
+          };
+          write_open_attribute_tag_ln("div", &block_attrs(class, input), indent, out)?;
+          write_open_attribute_tag_ln("div", "class=\"content\"", indent + 1, out)?;
+          write_block_title(input, indent + 2, out)?;
+          for element in input.children.iter() {
+            write_html(element, indent + 2, out)?;
+          }
+          write_close_tag_ln("div", indent + 1, out)?;
+          write_close_tag_ln("div", indent, out)?;
+          return Ok(());
+        }
+        BlockType::Quote => {
+          write_open_attribute_tag_ln("div", &block_attrs("quoteblock", input), indent, out)?;
+          write_block_title(input, indent + 1, out)?;
+          write_open_tag_ln("blockquote", indent + 1, out)?;
+          for element in input.children.iter() {
+            write_html(element, indent + 2, out)?;
+          }
+          write_close_tag_ln("blockquote", indent + 1, out)?;
+          write_attribution(input, indent + 1, out)?;
+          write_close_tag_ln("div", indent, out)?;
+          return Ok(());
+        }
+        BlockType::Verse => {
+          write_open_attribute_tag_ln("div", &block_attrs("verseblock", input), indent, out)?;
+          write_block_title(input, indent + 1, out)?;
+          write_open_attribute_tag("pre", "class=\"content\"", indent + 1, out)?;
+          let content = input.get_attribute("content").unwrap_or(&input.content);
+          out.write_all(escape_block_text(input, content).as_bytes())?;
+          write_close_tag_ln("pre", 0, out)?;
+          write_attribution(input, indent + 1, out)?;
+          write_close_tag_ln("div", indent, out)?;
+          return Ok(());
+        }
+        _ => (),
+      }
+
       out.write_all(b"<div")?;
 
       if let Some(id) = input.get_attribute("anchor") {
@@ -194,6 +300,7 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
       out.write_all(&format!(" class=\"{}\">\n", class).as_bytes())?;
 
       if let Some(title) = input.get_attribute("title") {
+        let title = escape_text(title);
         out.write_all(&format!("\n  <div class=\"title\">{}</div>\n", title).as_bytes())?;
       };
 
@@ -201,8 +308,12 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
         out.write_all(b"  <pre>")?;
       }
 
-      let content = input.get_attribute("content").unwrap_or(input.content);
-      out.write_all(escape_text(&content).as_bytes())?;
+      let content = input.get_attribute("content").unwrap_or(&input.content);
+      if kind == &BlockType::Listing && !input.children.is_empty() {
+        write_callout_listing(input, content, &input.children, out)?;
+      } else {
+        out.write_all(escape_block_text(input, content).as_bytes())?;
+      }
 
       if kind == &BlockType::Listing {
         out.write_all(b"</pre>\n")?;
@@ -227,6 +338,97 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
 
       out.write_all(&format!("<a href=\"#{}\">{}</a>", id, content).as_bytes())?;
     }
+    Element::Footnote => {
+      let number = input.get_attribute("number").unwrap_or("");
+      let link = format!("href=\"#_footnotedef_{}\" title=\"View footnote.\">{}</a>]", number, number);
+
+      if input.get_attribute("content").is_some() {
+        let id = match input.get_attribute("id") {
+          Some(id) => format!(" id=\"_footnote_{}\"", id),
+          None => "".to_string(),
+        };
+        out.write_all(
+          format!(
+            "<sup class=\"footnote\"{}>[<a id=\"_footnoteref_{}\" class=\"footnote\" {}</sup>",
+            id, number, link
+          )
+          .as_bytes(),
+        )?;
+      } else {
+        out.write_all(format!("<sup class=\"footnoteref\">[<a class=\"footnote\" {}</sup>", link).as_bytes())?;
+      }
+    }
+    Element::Keyboard => {
+      let keys = crate::util::split_keys(input.get_attribute("keys").unwrap_or(""));
+      let keys: Vec<_> = keys.iter().map(|key| format!("<kbd>{}</kbd>", escape_text(key))).collect();
+      match keys.len() {
+        1 => out.write_all(keys[0].as_bytes())?,
+        _ => out.write_all(format!("<span class=\"keyseq\">{}</span>", keys.join("+")).as_bytes())?,
+      }
+    }
+    Element::Button => {
+      let label = input.get_attribute("label").unwrap_or("");
+      out.write_all(format!("<b class=\"button\">{}</b>", escape_text(label)).as_bytes())?;
+    }
+    Element::Menu => {
+      let menu = escape_text(input.get_attribute("menu").unwrap_or(""));
+      let items: Vec<_> = input
+        .get_attribute("items")
+        .unwrap_or("")
+        .split('>')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect();
+      if items.is_empty() {
+        out.write_all(format!("<b class=\"menuref\">{}</b>", menu).as_bytes())?;
+      } else {
+        let caret = "&#160;<i class=\"fa fa-angle-right caret\"></i> ";
+        let mut parts = vec![format!("<b class=\"menu\">{}</b>", menu)];
+        for (index, item) in items.iter().enumerate() {
+          let class = if index + 1 == items.len() { "menuitem" } else { "submenu" };
+          parts.push(format!("<b class=\"{}\">{}</b>", class, escape_text(item)));
+        }
+        out.write_all(format!("<span class=\"menuseq\">{}</span>", parts.join(caret)).as_bytes())?;
+      }
+    }
+    Element::Passthrough => {
+      out.write_all(input.get_attribute("content").unwrap_or("").as_bytes())?;
+    }
+    Element::Anchor => {
+      let id = input.get_attribute("id").unwrap_or("");
+      out.write_all(format!("<a id=\"{}\"></a>", id).as_bytes())?;
+    }
+    Element::InlineImage => {
+      let path = input.get_attribute("path").unwrap_or("");
+      // The alt text defaults to the file name without extension
+      let alt = match input.positional_attributes.first() {
+        Some(alt) => alt.as_str().to_string(),
+        None => {
+          let name = path.split('?').next().unwrap_or(path);
+          let name = name.rsplit('/').next().unwrap_or(name);
+          name.rsplit_once('.').map_or(name, |(name, _)| name).replace(['-', '_'], " ")
+        }
+      };
+      let mut img = format!("<img src=\"{}\" alt=\"{}\"", path, alt);
+      let sizes = input.positional_attributes.iter().skip(1).map(|size| size.as_str());
+      for (name, size) in ["width", "height"].iter().zip(sizes) {
+        img.push_str(&format!(" {}=\"{}\"", name, size));
+      }
+      for name in ["width", "height", "title"] {
+        if let Some(value) = input.get_attribute(name) {
+          img.push_str(&format!(" {}=\"{}\"", name, value));
+        }
+      }
+      img.push('>');
+      if let Some(link) = input.get_attribute("link") {
+        img = format!("<a class=\"image\" href=\"{}\">{}</a>", link, img);
+      }
+      out.write_all(format!("<span class=\"image\">{}</span>", img).as_bytes())?;
+    }
+    // Unknown macros are left as they are
+    Element::InlineMacro => {
+      out.write_all(input.content.as_bytes())?;
+    }
     Element::Image => {
       if let Some(path) = input.get_attribute("path") {
         match input.get_attribute("opts") {
@@ -251,25 +453,31 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
     }
     Element::Table => {
       write_open_attribute_tag_ln("table", "class=\"tableblock frame-all grid-all stretch\"", indent, out)?;
-      write_open_tag_ln("colgroup", indent+1, out)?;
-      write_open_attribute_tag_ln("col", "style=\"width: 50%;\"", indent+2, out)?;
-      write_open_attribute_tag_ln("col", "style=\"width: 50%;\"", indent+2, out)?;
-      write_close_tag_ln("colgroup", indent+1, out)?;
-      write_open_tag_ln("tbody", indent+1, out)?;
-      for table_row in input.children.iter() {
-        match &table_row.element {
+      write_open_tag_ln("colgroup", indent + 1, out)?;
+      for width in input.get_attribute("colwidths").unwrap_or("").split(',').filter(|width| !width.is_empty()) {
+        let attrs = format!("style=\"width: {}%;\"", width);
+        write_open_attribute_tag_ln("col", &attrs, indent + 2, out)?;
+      }
+      write_close_tag_ln("colgroup", indent + 1, out)?;
+      for (section, tag) in [("header", "thead"), ("body", "tbody"), ("footer", "tfoot")] {
+        let rows = input
+          .children
+          .iter()
+          .filter(|row| row.get_attribute("section").unwrap_or("body") == section)
+          .collect::<Vec<_>>();
+        if rows.is_empty() {
+          continue;
+        }
+
+        write_open_tag_ln(tag, indent + 1, out)?;
+        for table_row in rows {
+          match &table_row.element {
             Element::TableRow => {
-              write_open_tag_ln("tr", indent+2, out)?;
+              write_open_tag_ln("tr", indent + 2, out)?;
               for table_cell in table_row.children.iter() {
-                  match &table_cell.element {
-                      Element::TableCell => {
-                        write_tag("td", table_cell, indent+3, out)?;
-                      }
-                      _ => (),
-                  }
-                  out.write_all(b"\n")?;
+                write_table_cell(table_cell, indent + 3, out)?;
               }
-              write_close_tag_ln("tr", indent+2, out)?;
+              write_close_tag_ln("tr", indent + 2, out)?;
             }
             _ => {
               out.write_all(
@@ -279,21 +487,75 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
                 )
                 .as_bytes(),
               )?;
-            },
+            }
+          }
         }
+        write_close_tag_ln(tag, indent + 1, out)?;
       }
-      write_close_tag_ln("tbody", indent+1, out)?;
       write_close_tag_ln("table", indent, out)?;
     }
     Element::Text => {
-      out.write_all(input.content.as_bytes())?;
+      let content = input.get_attribute("content").unwrap_or(&input.content);
+      out.write_all(escape_text(content).as_bytes())?;
+    }
+    Element::LineBreak => {
+      out.write_all(b"<br>")?;
+    }
+    // Attribute entries are already applied by the reader
+    // and comments are not printed
+    Element::Attribute(_) | Element::Comment => (),
+    Element::IncludeElement(include) => {
+      for element in include.inner.elements.iter() {
+        write_html(element, indent, out)?;
+      }
+    }
+    Element::Section { level } => {
+      out.write_all(format!("<div class=\"sect{}\">\n", level).as_bytes())?;
+      let mut children = input.children.iter();
+      if let Some(title) = children.next() {
+        write_html(title, indent, out)?;
+      }
+      // Like asciidoctor we wrap the body of top level sections
+      if *level == 1 {
+        out.write_all(b"<div class=\"sectionbody\">\n")?;
+      }
+      for element in children {
+        write_html(element, indent, out)?;
+      }
+      if *level == 1 {
+        out.write_all(b"</div>\n")?;
+      }
+      out.write_all(b"</div>\n")?;
+    }
+    Element::Styled if matches!(input.get_attribute("style"), Some("doublequote" | "singlequote")) => {
+      let (open, close) = match input.get_attribute("style") {
+        Some("doublequote") => ("&#8220;", "&#8221;"),
+        _ => ("&#8216;", "&#8217;"),
+      };
+      out.write_all(open.as_bytes())?;
+      for element in input.children.iter() {
+        write_html(element, 0, out)?;
+      }
+      out.write_all(close.as_bytes())?;
     }
     Element::Styled => {
+      // A role (e.g. `[.big]#text#`) becomes the class
+      let roles: Vec<_> = input
+        .positional_attributes
+        .iter()
+        .filter_map(|value| value.as_str().strip_prefix('.'))
+        .flat_map(|roles| roles.split('.'))
+        .collect();
       let style = match input.get_attribute("style").unwrap_or("") {
         "monospaced" => "code",
+        "mark" if !roles.is_empty() => "span",
         style => style,
       };
-      write_tag(style, input, indent, out)?;
+      let attrs = match roles.is_empty() {
+        true => String::new(),
+        false => format!("class=\"{}\"", roles.join(" ")),
+      };
+      write_attribute_tag(style, &attrs, input, indent, out)?;
     }
     _ => {
       out.write_all(
@@ -312,8 +574,264 @@ fn write_html<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) ->
 // Helper Functions
 //----------------------------------------------------
 
+/// Writes a cell with its spans and alignment. Cells with a single
+/// line of text are written on one line.
+fn write_table_cell<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) -> Result<()> {
+  let style = input.get_attribute("style").unwrap_or("default");
+  let tag = if style == "header" { "th" } else { "td" };
+
+  let mut attrs = Vec::new();
+  for span in ["colspan", "rowspan"] {
+    if let Some(value) = input.get_attribute(span) {
+      attrs.push(format!("{}=\"{}\"", span, value));
+    }
+  }
+  let halign = input.get_attribute("halign").unwrap_or("left");
+  let valign = input.get_attribute("valign").unwrap_or("top");
+  if halign != "left" || valign != "top" {
+    attrs.push(format!("class=\"halign-{} valign-{}\"", halign, valign));
+  }
+  write_open_attribute_tag(tag, &attrs.join(" "), indent, out)?;
+
+  let content = input.get_attribute("content").unwrap_or(&input.content);
+  let inline = match style {
+    "literal" => {
+      out.write_all(b"<div class=\"literal\"><pre>")?;
+      out.write_all(escape_text(content).as_bytes())?;
+      out.write_all(b"</pre></div>")?;
+      true
+    }
+    "verse" => {
+      out.write_all(b"<div class=\"verse\">")?;
+      out.write_all(escape_text(content).as_bytes())?;
+      out.write_all(b"</div>")?;
+      true
+    }
+    "header" => {
+      for element in input.children.iter() {
+        for element in element.children.iter() {
+          write_html(element, 0, out)?;
+        }
+      }
+      true
+    }
+    "asciidoc" => false,
+    _ => input.children.len() < 2 && !content.contains('\n'),
+  };
+
+  if !inline {
+    out.write_all(b"\n")?;
+  }
+  let wrapper = match style {
+    "emphasis" => Some("em"),
+    "strong" => Some("strong"),
+    "monospaced" => Some("code"),
+    _ => None,
+  };
+  for element in input.children.iter() {
+    match (style, &element.element) {
+      ("literal" | "verse" | "header", _) => (),
+      ("asciidoc", _) => write_html(element, indent + 1, out)?,
+      (_, Element::Paragraph) => {
+        let indent = if inline { 0 } else { indent + 1 };
+        let mut paragraph = Vec::new();
+        for element in element.children.iter() {
+          write_html(element, 0, &mut paragraph)?;
+        }
+        // Lines of text are aligned with the paragraph
+        let paragraph = String::from_utf8_lossy(&paragraph).replace('\n', &format!("\n{}", "  ".repeat(indent)));
+
+        write_open_tag("p", indent, out)?;
+        match wrapper {
+          Some(wrapper) => out.write_all(format!("<{}>{}</{}>", wrapper, paragraph, wrapper).as_bytes())?,
+          None => out.write_all(paragraph.as_bytes())?,
+        }
+        write_close_tag("p", 0, out)?;
+        if !inline {
+          out.write_all(b"\n")?;
+        }
+      }
+      _ => write_html(element, indent + 1, out)?,
+    }
+  }
+  if inline {
+    write_close_tag_ln(tag, 0, out)?;
+  } else {
+    write_close_tag_ln(tag, indent, out)?;
+  }
+
+  Ok(())
+}
+
+/// Writes the items of a labeled list. The items following an
+/// item with a higher level form a nested list in its description.
+fn write_labeled_list<T: io::Write>(items: &[ElementSpan], indent: usize, out: &mut T) -> Result<()> {
+  let level = |item: &ElementSpan| match item.element {
+    Element::ListItem(level) => level,
+    _ => 1,
+  };
+
+  write_open_attribute_tag_ln("div", "class=\"dlist\"", indent, out)?;
+  write_open_tag_ln("dl", indent + 1, out)?;
+  let mut index = 0;
+  while index < items.len() {
+    let item = &items[index];
+    let nested = items[index + 1..]
+      .iter()
+      .take_while(|nested| level(nested) > level(item))
+      .count();
+
+    write_open_attribute_tag("dt", "class=\"hdlist1\"", indent + 2, out)?;
+    out.write_all(escape_text(item.get_attribute("term").unwrap_or("")).as_bytes())?;
+    write_close_tag_ln("dt", 0, out)?;
+    if !item.children.is_empty() || nested > 0 {
+      write_open_tag_ln("dd", indent + 2, out)?;
+      for element in item.children.iter() {
+        write_html(element, indent + 3, out)?;
+      }
+      if nested > 0 {
+        write_labeled_list(&items[index + 1..index + 1 + nested], indent + 3, out)?;
+      }
+      write_close_tag_ln("dd", indent + 2, out)?;
+    }
+
+    index += 1 + nested;
+  }
+  write_close_tag_ln("dl", indent + 1, out)?;
+  write_close_tag_ln("div", indent, out)?;
+
+  Ok(())
+}
+
+/// Writes the texts of the footnotes at the end of the document
+fn write_footnotes<T: io::Write>(footnotes: &[&ElementSpan], out: &mut T) -> Result<()> {
+  if footnotes.is_empty() {
+    return Ok(());
+  }
+
+  write_open_attribute_tag_ln("div", "id=\"footnotes\"", 0, out)?;
+  out.write_all(b"<hr>\n")?;
+  for footnote in footnotes.iter() {
+    let number = footnote.get_attribute("number").unwrap_or("");
+    let content = escape_text(footnote.get_attribute("content").unwrap_or(""));
+    let attrs = format!("class=\"footnote\" id=\"_footnotedef_{}\"", number);
+    write_open_attribute_tag_ln("div", &attrs, 0, out)?;
+    out.write_all(
+      format!("<a href=\"#_footnoteref_{}\">{}</a>. {}\n", number, number, content).as_bytes(),
+    )?;
+    write_close_tag_ln("div", 0, out)?;
+  }
+  write_close_tag_ln("div", 0, out)?;
+
+  Ok(())
+}
+
+/// Writes a listing with its callout markers replaced by badges
+fn write_callout_listing<T: io::Write>(
+  input: &ElementSpan,
+  content: &str,
+  callouts: &[ElementSpan],
+  out: &mut T,
+) -> Result<()> {
+  for (index, line) in content.split('\n').enumerate() {
+    if index > 0 {
+      out.write_all(b"\n")?;
+    }
+    let markers = callouts
+      .iter()
+      .filter(|callout| callout.get_attribute("line") == Some(&index.to_string()))
+      .collect::<Vec<_>>();
+    let code = match (markers.is_empty(), crate::util::split_callouts(line)) {
+      (false, Some((code, _))) => code,
+      _ => line,
+    };
+    out.write_all(escape_block_text(input, code).as_bytes())?;
+
+    for marker in markers {
+      if let Element::Callout(number) = marker.element {
+        let id = match marker.get_attribute("id") {
+          Some(id) => format!(" id=\"{}\"", id),
+          None => String::new(),
+        };
+        out.write_all(
+          format!(" <i class=\"conum\"{} data-value=\"{}\"></i><b>({})</b>", id, number, number)
+            .as_bytes(),
+        )?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// The attributes of a block div with an optional id
+fn block_attrs(class: &str, input: &ElementSpan) -> String {
+  match input.get_attribute("anchor") {
+    Some(id) => format!("id=\"{}\" class=\"{}\"", id, class),
+    None => format!("class=\"{}\"", class),
+  }
+}
+
+fn write_block_title<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) -> Result<()> {
+  if let Some(title) = input.get_attribute("title") {
+    write_open_attribute_tag("div", "class=\"title\"", indent, out)?;
+    out.write_all(escape_text(title).as_bytes())?;
+    write_close_tag_ln("div", 0, out)?;
+  }
+  Ok(())
+}
+
+/// Writes the author and source of a quote or verse
+fn write_attribution<T: io::Write>(input: &ElementSpan, indent: usize, out: &mut T) -> Result<()> {
+  let attribution = input.get_attribute("attribution");
+  let citetitle = input.get_attribute("citetitle");
+  if attribution.is_none() && citetitle.is_none() {
+    return Ok(());
+  }
+
+  write_open_attribute_tag_ln("div", "class=\"attribution\"", indent, out)?;
+  out.write_all(&b"  ".repeat(indent + 1))?;
+  if let Some(attribution) = attribution {
+    out.write_all(format!("&#8212; {}", escape_text(attribution)).as_bytes())?;
+    if citetitle.is_some() {
+      out.write_all(b"<br>\n")?;
+      out.write_all(&b"  ".repeat(indent + 1))?;
+    }
+  }
+  if let Some(citetitle) = citetitle {
+    out.write_all(format!("<cite>{}</cite>", escape_text(citetitle)).as_bytes())?;
+  }
+  out.write_all(b"\n")?;
+  write_close_tag_ln("div", indent, out)?;
+  Ok(())
+}
+
+/// Makes the id of a section from its title. Only lowercase
+/// letters, digits, `_` and `-` are kept, every run of other
+/// characters becomes a `_`.
+fn section_id(title: &str) -> String {
+  let mut id = "_".to_string();
+  for c in title.to_lowercase().chars() {
+    match c {
+      'a'..='z' | '0'..='9' | '-' => id.push(c),
+      _ if id.ends_with('_') => (),
+      _ => id.push('_'),
+    }
+  }
+
+  id
+}
+
 fn escape_text(input: &str) -> String {
-  input.replace("<", "&lt;").replace(">", "&gt;")
+  input.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Escapes the text of a block unless its `subs`
+/// leave out the special characters
+fn escape_block_text(input: &ElementSpan, text: &str) -> String {
+  match subs::of(input).contains(&Substitution::SpecialChars) {
+    true => escape_text(text),
+    false => text.to_string(),
+  }
 }
 
 fn write_tag<T: io::Write>(
@@ -339,20 +857,27 @@ fn write_attribute_tag<T: io::Write>(
       let title = inner
         .get_attribute("name")
         .ok_or(AsciidoctrineError::MalformedAst)?;
-      out.write_all(title.as_bytes())?;
+      out.write_all(escape_text(title).as_bytes())?;
     }
     Element::Paragraph => {
+      // Inline elements are never indented
       for element in inner.children.iter() {
-        write_html(element, indent, out)?;
+        write_html(element, 0, out)?;
       }
     }
-    Element::Styled => {
+    Element::Styled if inner.children.is_empty() => {
       let content = inner.get_attribute("content").unwrap_or("");
-      out.write_all(content.as_bytes())?;
+      out.write_all(escape_text(content).as_bytes())?;
+    }
+    Element::Styled => {
+      for element in inner.children.iter() {
+        write_html(element, 0, out)?;
+      }
     }
     Element::TableCell => {
       write_open_tag("p", 0, out)?;
-      out.write_all(inner.content.as_bytes())?;
+      let content = inner.get_attribute("content").unwrap_or(&inner.content);
+      out.write_all(content.as_bytes())?;
       write_close_tag("p", 0, out)?;
     }
     el => write_html(inner, indent + 1, out)?,
@@ -1,3 +1,6 @@
 pub mod html;
 pub mod docx;
+pub mod docbook;
+pub mod pdf;
 pub mod json;
+pub mod asciidoc;
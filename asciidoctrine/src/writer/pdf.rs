@@ -0,0 +1,1932 @@
+pub use crate::ast::*;
+use crate::util::Environment;
+use crate::{options, Result, AsciidoctrineError};
+use log::warn;
+use pdf_writer::types::{ActionType, AnnotationType};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use serde::Deserialize;
+use std::collections::HashMap;
+use svg2pdf::usvg::{self, TreeParsing, TreePostProc};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes an AST as PDF. The text is set with the standard
+/// fonts of PDF (Helvetica and Courier), so nothing has to be
+/// embedded. The layout can be changed by a theme.
+pub struct PdfWriter {
+  io: crate::util::Env,
+}
+
+impl PdfWriter {
+  pub fn new() -> Self {
+    PdfWriter {
+      io: crate::util::Env::Io(crate::util::Io::new()),
+    }
+  }
+}
+
+impl Default for PdfWriter {
+  fn default() -> Self {
+    PdfWriter::new()
+  }
+}
+
+impl<T: io::Write> crate::Writer<T> for PdfWriter {
+  fn write<'a>(&mut self, ast: AST, args: &options::Opts, mut out: T) -> Result<()> {
+    let theme = match &args.theme {
+      Some(path) => {
+        let path = path
+          .to_str()
+          .ok_or_else(|| AsciidoctrineError::Path(path.clone()))?;
+        serde_json::from_str(&self.io.read_to_string(path)?)?
+      }
+      None => Theme::default(),
+    };
+    // Images are found relative to the document
+    let base = ast
+      .get_attribute("source")
+      .and_then(|source| Path::new(source).parent())
+      .map(Path::to_path_buf)
+      .unwrap_or_default();
+
+    let mut layout = Layout::new(&theme, &mut self.io, base);
+    layout.blocks(&ast.elements);
+    layout.footnotes(&ast.footnotes());
+    out.write_all(&layout.finish())?;
+    out.flush()?;
+
+    Ok(())
+  }
+}
+
+/// The sizes (in points) and colors (as rgb from 0 to 1) of
+/// the layout. A theme is read from a json file, every value
+/// left out keeps its default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+  pub page_width: f32,
+  pub page_height: f32,
+  pub margin: f32,
+  pub font_size: f32,
+  /// The height of a line relative to the font size
+  pub line_height: f32,
+  /// The font sizes of the document title and the section
+  /// titles by their level
+  pub heading_sizes: Vec<f32>,
+  pub code_font_size: f32,
+  /// The space between blocks
+  pub block_spacing: f32,
+  /// The indentation of every level of a list
+  pub list_indent: f32,
+  pub text_color: [f32; 3],
+  pub heading_color: [f32; 3],
+  pub link_color: [f32; 3],
+  pub code_background: [f32; 3],
+  pub table_header_background: [f32; 3],
+  pub border_color: [f32; 3],
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Theme {
+      // A4
+      page_width: 595.28,
+      page_height: 841.89,
+      margin: 56.0,
+      font_size: 10.5,
+      line_height: 1.4,
+      heading_sizes: vec![24.0, 18.0, 15.0, 13.0, 11.5, 10.5],
+      code_font_size: 9.0,
+      block_spacing: 8.0,
+      list_indent: 18.0,
+      text_color: [0.2, 0.2, 0.2],
+      heading_color: [0.1, 0.1, 0.1],
+      link_color: [0.15, 0.3, 0.6],
+      code_background: [0.96, 0.96, 0.96],
+      table_header_background: [0.93, 0.93, 0.93],
+      border_color: [0.8, 0.8, 0.8],
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Font {
+  Regular,
+  Bold,
+  Italic,
+  BoldItalic,
+  Mono,
+  MonoBold,
+}
+
+const FONTS: [Font; 6] = [
+  Font::Regular,
+  Font::Bold,
+  Font::Italic,
+  Font::BoldItalic,
+  Font::Mono,
+  Font::MonoBold,
+];
+
+impl Font {
+  /// The name of the font in the resources of the pages
+  fn resource(&self) -> &'static [u8] {
+    match self {
+      Font::Regular => b"F1",
+      Font::Bold => b"F2",
+      Font::Italic => b"F3",
+      Font::BoldItalic => b"F4",
+      Font::Mono => b"F5",
+      Font::MonoBold => b"F6",
+    }
+  }
+
+  fn base_font(&self) -> &'static [u8] {
+    match self {
+      Font::Regular => b"Helvetica",
+      Font::Bold => b"Helvetica-Bold",
+      Font::Italic => b"Helvetica-Oblique",
+      Font::BoldItalic => b"Helvetica-BoldOblique",
+      Font::Mono => b"Courier",
+      Font::MonoBold => b"Courier-Bold",
+    }
+  }
+
+  fn bold(self) -> Self {
+    match self {
+      Font::Regular => Font::Bold,
+      Font::Italic => Font::BoldItalic,
+      Font::Mono => Font::MonoBold,
+      font => font,
+    }
+  }
+
+  fn italic(self) -> Self {
+    match self {
+      Font::Regular => Font::Italic,
+      Font::Bold => Font::BoldItalic,
+      font => font,
+    }
+  }
+
+  /// The width of a character in thousandths of the font size
+  /// (the metrics of the standard fonts). The oblique fonts are
+  /// as wide as the upright ones.
+  fn width(&self, c: char) -> f32 {
+    let widths = match self {
+      Font::Mono | Font::MonoBold => return 600.0,
+      Font::Regular | Font::Italic => &HELVETICA,
+      Font::Bold | Font::BoldItalic => &HELVETICA_BOLD,
+    };
+    match c {
+      ' '..='~' => widths[c as usize - 32] as f32,
+      '\u{a0}' => 278.0,
+      '‘' | '’' | '‚' => 222.0,
+      '‹' | '›' => 333.0,
+      '“' | '”' | '„' => 333.0,
+      '•' => 350.0,
+      '—' | '…' | '‰' => 1000.0,
+      '©' | '®' => 737.0,
+      '°' => 400.0,
+      _ => 556.0,
+    }
+  }
+}
+
+#[rustfmt::skip]
+const HELVETICA: [u16; 95] = [
+  278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+  556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+  1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+  667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+  333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+  556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const HELVETICA_BOLD: [u16; 95] = [
+  278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+  556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+  975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+  667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+  333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+  611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Encodes a text for the standard fonts (WinAnsiEncoding).
+/// Characters the fonts don't have become a `?`.
+fn encode(text: &str) -> Vec<u8> {
+  text
+    .chars()
+    .filter(|c| *c != '\u{200b}')
+    .map(|c| match c {
+      ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+      '€' => 0x80,
+      '‚' => 0x82,
+      '„' => 0x84,
+      '…' => 0x85,
+      '‰' => 0x89,
+      '‹' => 0x8b,
+      '‘' => 0x91,
+      '’' => 0x92,
+      '“' => 0x93,
+      '”' => 0x94,
+      '•' => 0x95,
+      '–' => 0x96,
+      '—' => 0x97,
+      '™' => 0x99,
+      '›' => 0x9b,
+      '\u{2000}'..='\u{200a}' | '\t' => b' ',
+      _ => b'?',
+    })
+    .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Style {
+  font: Font,
+  size: f32,
+  color: [f32; 3],
+  /// The shift of the baseline (for super- and subscripts)
+  rise: f32,
+}
+
+impl Style {
+  fn width(&self, text: &str) -> f32 {
+    text
+      .chars()
+      .filter(|c| *c != '\u{200b}')
+      .map(|c| self.font.width(c))
+      .sum::<f32>()
+      * self.size
+      / 1000.0
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+  /// An id in the document
+  Internal(String),
+  External(String),
+}
+
+/// A piece of text in the same style. A span with a line
+/// break as text ends the line.
+#[derive(Debug, Clone)]
+struct Span {
+  text: String,
+  style: Style,
+  link: Option<Target>,
+  /// The ids of anchors at the start of the span
+  anchors: Vec<String>,
+}
+
+/// A line of a paragraph with the spans placed on it
+struct Line {
+  fragments: Vec<(f32, Span)>,
+  width: f32,
+  size: f32,
+}
+
+enum Op {
+  Text {
+    x: f32,
+    y: f32,
+    style: Style,
+    text: String,
+  },
+  Rect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    color: [f32; 3],
+  },
+  Line {
+    from: (f32, f32),
+    to: (f32, f32),
+    color: [f32; 3],
+  },
+  Image {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    image: usize,
+  },
+}
+
+#[derive(Default)]
+struct Page {
+  ops: Vec<Op>,
+  links: Vec<(Rect, Target)>,
+}
+
+enum Image {
+  /// A png or jpeg image
+  Raster(Raster),
+  /// A svg image. It is written as form (not as pixels).
+  Vector(usvg::Tree),
+}
+
+impl Image {
+  /// The size in pixels
+  fn size(&self) -> (f32, f32) {
+    match self {
+      Image::Raster(raster) => (raster.width as f32, raster.height as f32),
+      Image::Vector(tree) => (tree.size.width(), tree.size.height()),
+    }
+  }
+}
+
+struct Raster {
+  width: u32,
+  height: u32,
+  data: Vec<u8>,
+  filter: Filter,
+  color_space: &'static [u8],
+  /// The compressed alpha channel
+  alpha: Option<Vec<u8>>,
+}
+
+/// A title for the outline of the document
+struct Heading {
+  level: u32,
+  title: String,
+  page: usize,
+  y: f32,
+}
+
+/// Places the content on pages. The blocks are placed from the
+/// top to the bottom. `y` is where the next block starts and
+/// `x` where the lines start (with the indentation of lists and
+/// so on).
+struct Layout<'t> {
+  theme: &'t Theme,
+  /// Reads the images
+  io: &'t mut crate::util::Env,
+  base: PathBuf,
+  pages: Vec<Page>,
+  x: f32,
+  y: f32,
+  images: Vec<Image>,
+  /// The fonts for the texts of svg images. They are only
+  /// loaded for the first svg image.
+  svg_fonts: Option<usvg::fontdb::Database>,
+  /// Where the ids are found (page and top of the line)
+  anchors: HashMap<String, (usize, f32)>,
+  headings: Vec<Heading>,
+  /// A marker (like the bullet of a list item) put in front
+  /// of the next line
+  marker: Option<Span>,
+}
+
+impl<'t> Layout<'t> {
+  fn new(theme: &'t Theme, io: &'t mut crate::util::Env, base: PathBuf) -> Self {
+    Layout {
+      theme,
+      io,
+      base,
+      pages: vec![Page::default()],
+      x: theme.margin,
+      y: theme.page_height - theme.margin,
+      images: Vec::new(),
+      svg_fonts: None,
+      anchors: HashMap::new(),
+      headings: Vec::new(),
+      marker: None,
+    }
+  }
+
+  fn right(&self) -> f32 {
+    self.theme.page_width - self.theme.margin
+  }
+
+  fn top(&self) -> f32 {
+    self.theme.page_height - self.theme.margin
+  }
+
+  fn width(&self) -> f32 {
+    self.right() - self.x
+  }
+
+  fn page(&mut self) -> &mut Page {
+    let index = self.pages.len() - 1;
+    &mut self.pages[index]
+  }
+
+  fn new_page(&mut self) {
+    self.pages.push(Page::default());
+    self.y = self.top();
+  }
+
+  /// Starts a new page if there isn't enough space left
+  fn ensure(&mut self, height: f32) {
+    if self.y - height < self.theme.margin && self.y < self.top() {
+      self.new_page();
+    }
+  }
+
+  /// Leaves space between blocks (but not at the top of a page)
+  fn space(&mut self, height: f32) {
+    if self.y < self.top() {
+      self.y -= height;
+    }
+  }
+
+  fn anchor(&mut self, id: &str) {
+    let position = (self.pages.len() - 1, self.y);
+    self.anchors.entry(id.to_string()).or_insert(position);
+  }
+
+  fn style(&self) -> Style {
+    Style {
+      font: Font::Regular,
+      size: self.theme.font_size,
+      color: self.theme.text_color,
+      rise: 0.0,
+    }
+  }
+
+  fn line_height(&self, size: f32) -> f32 {
+    size * self.theme.line_height
+  }
+
+  fn blocks(&mut self, elements: &[ElementSpan]) {
+    for element in elements.iter() {
+      self.block(element);
+    }
+  }
+
+  fn block(&mut self, input: &ElementSpan) {
+    if let Some(id) = input.get_attribute("anchor") {
+      self.anchor(id);
+    }
+
+    match &input.element {
+      Element::Title { level } => self.title(input, *level),
+      Element::Section { .. } => self.blocks(&input.children),
+      Element::Paragraph => {
+        self.block_title(input);
+        let spans = self.inlines(&input.children, self.style(), None);
+        self.paragraph(spans);
+        self.space(self.theme.block_spacing);
+      }
+      Element::List(kind) => {
+        self.block_title(input);
+        self.list(input, kind);
+        self.space(self.theme.block_spacing);
+      }
+      Element::Admonition(kind) => {
+        let label = Span {
+          text: kind.caption().to_uppercase(),
+          style: Style {
+            font: Font::Bold,
+            ..self.style()
+          },
+          link: None,
+          anchors: Vec::new(),
+        };
+        self.ensure(self.line_height(self.theme.font_size) * 2.0);
+        let start = (self.pages.len() - 1, self.y);
+        let x = self.x;
+        self.text_line(x, label);
+        self.y = start.1;
+        self.x += 72.0;
+        self.block_title(input);
+        self.blocks(&input.children);
+        self.x = x;
+        self.bar(start, x + 64.0);
+        self.space(self.theme.block_spacing);
+      }
+      Element::TypedBlock { kind } => self.typed_block(input, kind),
+      Element::Image => {
+        self.image(input);
+        self.block_title(input);
+        self.space(self.theme.block_spacing);
+      }
+      Element::Table => {
+        self.block_title(input);
+        self.table(input);
+        self.space(self.theme.block_spacing);
+      }
+      Element::IncludeElement(include) => self.blocks(&include.inner.elements),
+      Element::Attribute(_) | Element::Comment => (),
+      Element::Anchor => {
+        if let Some(id) = input.get_attribute("id") {
+          self.anchor(id);
+        }
+      }
+      Element::ListItem(_) | Element::TableRow | Element::TableCell => self.blocks(&input.children),
+      // Inline elements outside of a paragraph
+      _ => {
+        let spans = self.inlines(std::slice::from_ref(input), self.style(), None);
+        self.paragraph(spans);
+        self.space(self.theme.block_spacing);
+      }
+    }
+  }
+
+  fn title(&mut self, input: &ElementSpan, level: u32) {
+    let sizes = &self.theme.heading_sizes;
+    let size = sizes
+      .get(level as usize - 1)
+      .or(sizes.last())
+      .copied()
+      .unwrap_or(self.theme.font_size);
+    let name = input.get_attribute("name").unwrap_or("").to_string();
+
+    // A title isn't left alone at the bottom of a page
+    self.space(size * 0.8);
+    self.ensure(self.line_height(size) + self.line_height(self.theme.font_size) * 3.0);
+    if level > 1 {
+      let id = match input.get_attribute("anchor") {
+        Some(id) => id.to_string(),
+        None => "_".to_string() + &name.replace(' ', "_").to_lowercase(),
+      };
+      self.anchor(&id);
+      self.headings.push(Heading {
+        level,
+        title: name.clone(),
+        page: self.pages.len() - 1,
+        y: self.y,
+      });
+    }
+    let style = Style {
+      font: Font::Bold,
+      size,
+      color: self.theme.heading_color,
+      rise: 0.0,
+    };
+    self.paragraph(vec![Span {
+      text: name,
+      style,
+      link: None,
+      anchors: Vec::new(),
+    }]);
+    self.space(size * 0.4);
+  }
+
+  fn block_title(&mut self, input: &ElementSpan) {
+    if let Some(title) = input.get_attribute("title") {
+      let style = Style {
+        font: Font::Italic,
+        ..self.style()
+      };
+      self.paragraph(vec![Span {
+        text: title.to_string(),
+        style,
+        link: None,
+        anchors: Vec::new(),
+      }]);
+      self.space(2.0);
+    }
+  }
+
+  /// Lays out the items of a list. The items of asciidoc lists
+  /// are flat with their level, the ones of markdown lists have
+  /// their nested lists as children.
+  fn list(&mut self, input: &ElementSpan, kind: &ListType) {
+    let x = self.x;
+    let mut numbers: Vec<usize> = Vec::new();
+    for item in input.children.iter() {
+      let level = match item.element {
+        Element::ListItem(level) => level.max(1) as usize,
+        _ => 1,
+      };
+      numbers.truncate(level);
+      numbers.resize(level, 0);
+      numbers[level - 1] += 1;
+      let number = numbers[level - 1];
+
+      self.x = x + self.theme.list_indent * level as f32;
+      let marker = match kind {
+        ListType::Bullet => Some(match level % 2 {
+          1 => "•".to_string(),
+          _ => "–".to_string(),
+        }),
+        ListType::Number => Some(match level % 2 {
+          1 => format!("{}.", number),
+          _ => format!("{}.", (b'a' + ((number - 1) % 26) as u8) as char),
+        }),
+        ListType::Callout => Some(format!("({})", item.get_attribute("callout").unwrap_or(""))),
+        ListType::Labeled => None,
+      };
+      match marker {
+        Some(marker) => {
+          self.marker = Some(Span {
+            text: marker,
+            style: self.style(),
+            link: None,
+            anchors: Vec::new(),
+          })
+        }
+        None => {
+          self.x -= self.theme.list_indent;
+          let style = Style {
+            font: Font::Bold,
+            ..self.style()
+          };
+          let term = item.get_attribute("term").unwrap_or("").to_string();
+          self.paragraph(vec![Span {
+            text: term,
+            style,
+            link: None,
+            anchors: Vec::new(),
+          }]);
+          self.x += self.theme.list_indent;
+        }
+      }
+      for (index, element) in item.children.iter().enumerate() {
+        if index > 0 {
+          self.space(self.theme.block_spacing / 2.0);
+        }
+        match &element.element {
+          Element::Paragraph => {
+            let spans = self.inlines(&element.children, self.style(), None);
+            self.paragraph(spans);
+          }
+          Element::List(kind) => self.list(element, kind),
+          _ => self.block(element),
+        }
+      }
+      // An empty item still shows its marker
+      if let Some(marker) = self.marker.take() {
+        self.paragraph(vec![marker]);
+      }
+      self.space(2.0);
+    }
+    self.x = x;
+  }
+
+  fn typed_block(&mut self, input: &ElementSpan, kind: &BlockType) {
+    let content = input.get_attribute("content").unwrap_or(&input.content);
+    match kind {
+      BlockType::Listing | BlockType::Literal => {
+        self.block_title(input);
+        self.listing(input, content);
+      }
+      BlockType::Sidebar | BlockType::Example | BlockType::Quote => {
+        self.ensure(self.line_height(self.theme.font_size) * 2.0);
+        let start = (self.pages.len() - 1, self.y);
+        let x = self.x;
+        self.x += 12.0;
+        self.block_title(input);
+        self.blocks(&input.children);
+        self.attribution(input);
+        self.x = x;
+        self.bar(start, x + 3.0);
+      }
+      BlockType::Verse => {
+        let x = self.x;
+        self.x += 12.0;
+        self.block_title(input);
+        let style = Style {
+          font: Font::Italic,
+          ..self.style()
+        };
+        for line in content.lines() {
+          self.paragraph(vec![Span {
+            text: line.to_string(),
+            style,
+            link: None,
+            anchors: Vec::new(),
+          }]);
+        }
+        self.attribution(input);
+        self.x = x;
+      }
+      BlockType::Open => {
+        self.block_title(input);
+        self.blocks(&input.children);
+        return;
+      }
+      // Passthrough content is meant for other formats
+      BlockType::Passtrough | BlockType::Comment => return,
+    }
+    self.space(self.theme.block_spacing);
+  }
+
+  /// Writes the author and source of a quote or verse
+  fn attribution(&mut self, input: &ElementSpan) {
+    let parts: Vec<_> = ["attribution", "citetitle"]
+      .iter()
+      .filter_map(|key| input.get_attribute(key))
+      .collect();
+    if !parts.is_empty() {
+      let style = Style {
+        size: self.theme.font_size * 0.9,
+        ..self.style()
+      };
+      self.paragraph(vec![Span {
+        text: format!("— {}", parts.join(", ")),
+        style,
+        link: None,
+        anchors: Vec::new(),
+      }]);
+    }
+  }
+
+  /// Lays out a listing line by line, so it can be broken
+  /// at every line. Lines too long for the page are wrapped.
+  fn listing(&mut self, input: &ElementSpan, content: &str) {
+    let style = Style {
+      font: Font::Mono,
+      size: self.theme.code_font_size,
+      color: self.theme.text_color,
+      rise: 0.0,
+    };
+    let height = self.line_height(style.size);
+    let padding = 6.0;
+    let columns = (((self.width() - 2.0 * padding) / style.width(" ")) as usize).max(1);
+
+    self.space(2.0);
+    let mut first = true;
+    let lines: Vec<_> = content.split('\n').collect();
+    for (index, line) in lines.iter().enumerate() {
+      let markers = input
+        .children
+        .iter()
+        .filter(|callout| callout.get_attribute("line") == Some(&index.to_string()))
+        .collect::<Vec<_>>();
+      let code = match (markers.is_empty(), crate::util::split_callouts(line)) {
+        (false, Some((code, _))) => code,
+        _ => line,
+      };
+      let code = code.replace('\t', "    ");
+      let chars: Vec<_> = code.chars().collect();
+      let mut rows: Vec<String> = chars
+        .chunks(columns)
+        .map(|chunk| chunk.iter().collect())
+        .collect();
+      if rows.is_empty() {
+        rows.push(String::new());
+      }
+      let last = rows.len() - 1;
+      for (row, text) in rows.into_iter().enumerate() {
+        let last_line = index + 1 == lines.len() && row == last;
+        let extra = if first { padding } else { 0.0 } + if last_line { padding } else { 0.0 };
+        if self.y - height - extra < self.theme.margin {
+          self.new_page();
+          first = true;
+        }
+        let extra = if first { padding } else { 0.0 } + if last_line { padding } else { 0.0 };
+        let (x, y, width) = (self.x, self.y - height - extra, self.width());
+        let color = self.theme.code_background;
+        self.page().ops.push(Op::Rect {
+          x,
+          y,
+          width,
+          height: height + extra,
+          color,
+        });
+        if first {
+          self.y -= padding;
+        }
+        first = false;
+
+        let baseline = self.y - height + (height - style.size) / 2.0 + style.size * 0.22;
+        let end = x + padding + style.width(&text);
+        self.page().ops.push(Op::Text {
+          x: x + padding,
+          y: baseline,
+          style,
+          text,
+        });
+        if row == last {
+          let numbers: Vec<_> = markers
+            .iter()
+            .filter_map(|marker| match marker.element {
+              Element::Callout(number) => Some(format!("({})", number)),
+              _ => None,
+            })
+            .collect();
+          if !numbers.is_empty() {
+            self.page().ops.push(Op::Text {
+              x: end + style.width(" "),
+              y: baseline,
+              style: Style {
+                font: Font::MonoBold,
+                ..style
+              },
+              text: numbers.join(" "),
+            });
+          }
+        }
+        self.y -= height;
+        if last_line {
+          self.y -= padding;
+        }
+      }
+    }
+  }
+
+  fn image(&mut self, input: &ElementSpan) {
+    let path = input.get_attribute("path").unwrap_or("");
+    let alt = input
+      .positional_attributes
+      .first()
+      .map(|alt| alt.as_str())
+      .unwrap_or(path)
+      .to_string();
+
+    let image = match self.load_image(&self.base.join(path)) {
+      Ok(image) => image,
+      Err(error) => {
+        warn!("image `{}` can't be written to pdf: {}", path, error);
+        // The alt text is shown in a frame instead
+        self.ensure(self.line_height(self.theme.font_size) * 2.0);
+        let start = self.y;
+        self.y -= 4.0;
+        let x = self.x;
+        self.x += 6.0;
+        let style = Style {
+          font: Font::Italic,
+          ..self.style()
+        };
+        self.paragraph(vec![Span {
+          text: format!("[{}]", alt),
+          style,
+          link: None,
+          anchors: Vec::new(),
+        }]);
+        self.x = x;
+        self.y -= 4.0;
+        let (y, width, color) = (self.y, self.width(), self.theme.border_color);
+        let page = self.page();
+        for (from, to) in [
+          ((x, start), (x + width, start)),
+          ((x + width, start), (x + width, y)),
+          ((x + width, y), (x, y)),
+          ((x, y), (x, start)),
+        ] {
+          page.ops.push(Op::Line { from, to, color });
+        }
+        return;
+      }
+    };
+
+    // Pixels are as big as in html (3/4 of a point)
+    let size = |index: usize, key: &str| {
+      input
+        .positional_attributes
+        .get(index)
+        .map(|value| value.as_str())
+        .or(input.get_attribute(key))
+        .and_then(|value| value.trim_end_matches("px").parse::<f32>().ok())
+    };
+    let (image_width, image_height) = image.size();
+    let ratio = image_height / image_width;
+    let mut width = match (size(1, "width"), size(2, "height")) {
+      (Some(width), _) => width * 0.75,
+      (None, Some(height)) => height * 0.75 / ratio,
+      (None, None) => image_width * 0.75,
+    };
+    width = width.min(self.width());
+    let max_height = self.top() - self.theme.margin;
+    if width * ratio > max_height {
+      width = max_height / ratio;
+    }
+    let height = width * ratio;
+
+    self.ensure(height);
+    self.images.push(image);
+    let (x, y, image) = (self.x, self.y - height, self.images.len() - 1);
+    self.page().ops.push(Op::Image {
+      x,
+      y,
+      width,
+      height,
+      image,
+    });
+    self.y -= height + 4.0;
+  }
+
+  /// Reads a png, jpeg or svg image
+  fn load_image(&mut self, path: &Path) -> std::result::Result<Image, String> {
+    let data = self
+      .io
+      .read(&path.to_string_lossy())
+      .map_err(|error| error.to_string())?;
+    if data.starts_with(b"\x89PNG") {
+      load_png(&data).map(Image::Raster)
+    } else if data.starts_with(&[0xff, 0xd8]) {
+      load_jpeg(data).map(Image::Raster)
+    } else {
+      let mut tree = usvg::Tree::from_data(&data, &usvg::Options::default())
+        .map_err(|_| "only png, jpeg and svg images are supported".to_string())?;
+      let fonts = self.svg_fonts.get_or_insert_with(|| {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+        fonts
+      });
+      tree.postprocess(usvg::PostProcessingSteps::default(), fonts);
+      Ok(Image::Vector(tree))
+    }
+  }
+
+  /// Lays out a table row by row. Rows are not broken, the
+  /// header rows are repeated on every page.
+  fn table(&mut self, input: &ElementSpan) {
+    let rows: Vec<_> = input
+      .children
+      .iter()
+      .filter(|row| row.element == Element::TableRow)
+      .collect();
+    let cols = rows
+      .iter()
+      .map(|row| {
+        row
+          .children
+          .iter()
+          .map(|cell| span(cell, "colspan"))
+          .sum::<usize>()
+      })
+      .max()
+      .unwrap_or(0);
+    if cols == 0 {
+      return;
+    }
+    let mut widths: Vec<f32> = input
+      .get_attribute("colwidths")
+      .unwrap_or("")
+      .split(',')
+      .filter_map(|width| width.trim().parse().ok())
+      .collect();
+    if widths.len() != cols {
+      widths = vec![1.0; cols];
+    }
+    let total: f32 = widths.iter().sum();
+    let table_width = self.width();
+    let widths: Vec<f32> = widths
+      .iter()
+      .map(|width| width / total * table_width)
+      .collect();
+    let padding = 4.0;
+
+    // The cells are laid out first to know the heights of the rows
+    let mut taken = vec![0; cols];
+    let mut laid_out = Vec::new();
+    for row in rows.iter() {
+      let header = row.get_attribute("section") == Some("header");
+      let mut col = 0;
+      let mut cells = Vec::new();
+      for cell in row.children.iter() {
+        while col < cols && taken[col] > 0 {
+          col += 1;
+        }
+        let colspan = span(cell, "colspan").min(cols.saturating_sub(col)).max(1);
+        let rowspan = span(cell, "rowspan");
+        let x: f32 = widths[..col.min(cols)].iter().sum();
+        let width: f32 = widths[col.min(cols)..(col + colspan).min(cols)]
+          .iter()
+          .sum();
+        let lines = self.cell_lines(cell, header, width - 2.0 * padding);
+        for taken in taken.iter_mut().skip(col).take(colspan) {
+          *taken = rowspan;
+        }
+        cells.push((x, width, rowspan, lines));
+        col += colspan;
+      }
+      for taken in taken.iter_mut() {
+        *taken = taken.saturating_sub(1);
+      }
+      let height = cells
+        .iter()
+        .filter(|(_, _, rowspan, _)| *rowspan == 1)
+        .map(|(_, _, _, lines)| {
+          lines
+            .iter()
+            .map(|line| self.line_height(line.size))
+            .sum::<f32>()
+        })
+        .fold(self.line_height(self.theme.font_size), f32::max)
+        + 2.0 * padding;
+      laid_out.push((header, height, cells));
+    }
+
+    let x = self.x;
+    let headers: Vec<_> = (0..laid_out.len())
+      .take_while(|index| laid_out[*index].0)
+      .collect();
+    for index in 0..laid_out.len() {
+      let height = laid_out[index].1;
+      if self.y - height < self.theme.margin && self.y < self.top() {
+        self.new_page();
+        if !laid_out[index].0 {
+          for header in headers.iter() {
+            self.table_row(&laid_out, *header, x, padding);
+          }
+        }
+      }
+      self.table_row(&laid_out, index, x, padding);
+    }
+  }
+
+  #[allow(clippy::type_complexity)]
+  fn table_row(
+    &mut self,
+    rows: &[(bool, f32, Vec<(f32, f32, usize, Vec<Line>)>)],
+    index: usize,
+    x: f32,
+    padding: f32,
+  ) {
+    let (header, height, cells) = &rows[index];
+    let top = self.y;
+    for (cell_x, width, rowspan, lines) in cells.iter() {
+      let cell_height: f32 = rows[index..(index + rowspan).min(rows.len())]
+        .iter()
+        .map(|(_, height, _)| height)
+        .sum::<f32>()
+        .min(top - self.theme.margin);
+      let left = x + cell_x;
+      if *header {
+        let color = self.theme.table_header_background;
+        self.page().ops.push(Op::Rect {
+          x: left,
+          y: top - cell_height,
+          width: *width,
+          height: cell_height,
+          color,
+        });
+      }
+      let color = self.theme.border_color;
+      let (right, bottom) = (left + width, top - cell_height);
+      let page = self.page();
+      for (from, to) in [
+        ((left, top), (right, top)),
+        ((right, top), (right, bottom)),
+        ((right, bottom), (left, bottom)),
+        ((left, bottom), (left, top)),
+      ] {
+        page.ops.push(Op::Line { from, to, color });
+      }
+
+      self.y = top - padding;
+      for line in lines.iter() {
+        self.place_line(left + padding, line);
+      }
+    }
+    self.y = top - height;
+  }
+
+  /// Wraps the content of a cell to lines
+  fn cell_lines(&self, input: &ElementSpan, header: bool, width: f32) -> Vec<Line> {
+    let style = input.get_attribute("style").unwrap_or("default");
+    let mut base = self.style();
+    if header || style == "header" || style == "strong" {
+      base.font = base.font.bold();
+    }
+    match style {
+      "emphasis" => base.font = base.font.italic(),
+      "monospaced" | "literal" => {
+        base.font = Font::Mono;
+        base.size = self.theme.code_font_size;
+      }
+      _ => (),
+    }
+
+    let mut lines = Vec::new();
+    match style {
+      "literal" | "verse" => {
+        let content = input.get_attribute("content").unwrap_or(&input.content);
+        for line in content.lines() {
+          let span = Span {
+            text: line.to_string(),
+            style: base,
+            link: None,
+            anchors: Vec::new(),
+          };
+          lines.extend(wrap(vec![span], width));
+        }
+      }
+      _ => {
+        // Only the text of the blocks inside is written
+        let mut paragraphs = Vec::new();
+        collect_paragraphs(&input.children, &mut paragraphs);
+        for paragraph in paragraphs {
+          lines.extend(wrap(self.inlines(&paragraph.children, base, None), width));
+        }
+      }
+    }
+
+    lines
+  }
+
+  /// Draws a vertical bar from the start of a block to the
+  /// current position, on every page the block is on
+  fn bar(&mut self, start: (usize, f32), x: f32) {
+    let color = self.theme.border_color;
+    let end = self.pages.len() - 1;
+    for index in start.0..=end {
+      let top = if index == start.0 {
+        start.1
+      } else {
+        self.top()
+      };
+      let bottom = if index == end {
+        self.y
+      } else {
+        self.theme.margin
+      };
+      self.pages[index].ops.push(Op::Line {
+        from: (x, top),
+        to: (x, bottom),
+        color,
+      });
+    }
+  }
+
+  /// Turns inline elements into spans of text
+  fn inlines(&self, elements: &[ElementSpan], style: Style, link: Option<&Target>) -> Vec<Span> {
+    let mut spans = Vec::new();
+    for element in elements.iter() {
+      self.inline(element, style, link, &mut spans);
+    }
+
+    spans
+  }
+
+  fn inline(
+    &self,
+    input: &ElementSpan,
+    style: Style,
+    link: Option<&Target>,
+    spans: &mut Vec<Span>,
+  ) {
+    let text = |text: &str, style: Style| Span {
+      text: text.replace('\n', " "),
+      style,
+      link: link.cloned(),
+      anchors: Vec::new(),
+    };
+    let linked = Style {
+      color: self.theme.link_color,
+      ..style
+    };
+
+    match &input.element {
+      Element::Text => spans.push(text(
+        input.get_attribute("content").unwrap_or(&input.content),
+        style,
+      )),
+      Element::Styled => {
+        let mut inner = style;
+        match input.get_attribute("style").unwrap_or("") {
+          "strong" => inner.font = style.font.bold(),
+          "em" | "emphasis" => inner.font = style.font.italic(),
+          "monospaced" => {
+            inner.font = match style.font {
+              Font::Bold | Font::BoldItalic => Font::MonoBold,
+              _ => Font::Mono,
+            }
+          }
+          "sup" => {
+            inner.size = style.size * 0.7;
+            inner.rise = style.size * 0.35;
+          }
+          "sub" => {
+            inner.size = style.size * 0.7;
+            inner.rise = -style.size * 0.15;
+          }
+          "doublequote" => spans.push(text("“", style)),
+          "singlequote" => spans.push(text("‘", style)),
+          _ => (),
+        }
+        match input.children.is_empty() {
+          true => spans.push(text(input.get_attribute("content").unwrap_or(""), inner)),
+          false => {
+            for element in input.children.iter() {
+              self.inline(element, inner, link, spans);
+            }
+          }
+        }
+        match input.get_attribute("style") {
+          Some("doublequote") => spans.push(text("”", style)),
+          Some("singlequote") => spans.push(text("’", style)),
+          _ => (),
+        }
+      }
+      Element::XRef => {
+        let id = input.get_attribute("id").unwrap_or("");
+        let label = input.get_attribute("content").unwrap_or(id);
+        spans.push(Span {
+          link: Some(Target::Internal(id.to_string())),
+          ..text(label, linked)
+        });
+      }
+      Element::Link => {
+        let url = input.get_attribute("url").unwrap_or("");
+        let label = input
+          .positional_attributes
+          .first()
+          .map(|text| text.as_str())
+          .unwrap_or(url);
+        spans.push(Span {
+          link: Some(Target::External(url.to_string())),
+          ..text(label, linked)
+        });
+      }
+      Element::InlineMacro if matches!(input.get_attribute("name"), Some("link" | "xref")) => {
+        let target = input.get_attribute("target").unwrap_or("");
+        let label = match input
+          .positional_attributes
+          .first()
+          .map(|text| text.as_str())
+        {
+          Some(label) if !label.is_empty() => label,
+          _ => target,
+        };
+        let target = match input.get_attribute("name") {
+          Some("xref") if !target.contains(".adoc") => {
+            Target::Internal(target.trim_start_matches('#').to_string())
+          }
+          _ => Target::External(target.to_string()),
+        };
+        spans.push(Span {
+          link: Some(target),
+          ..text(label, linked)
+        });
+      }
+      Element::Footnote => {
+        let number = input.get_attribute("number").unwrap_or("");
+        let mut span = text(
+          &format!("[{}]", number),
+          Style {
+            size: style.size * 0.7,
+            rise: style.size * 0.35,
+            ..linked
+          },
+        );
+        span.link = Some(Target::Internal(format!("_footnotedef_{}", number)));
+        spans.push(span);
+      }
+      Element::Keyboard => spans.push(text(
+        input.get_attribute("keys").unwrap_or(""),
+        Style {
+          font: Font::Mono,
+          ..style
+        },
+      )),
+      Element::Button => spans.push(text(
+        &format!("[{}]", input.get_attribute("label").unwrap_or("")),
+        Style {
+          font: style.font.bold(),
+          ..style
+        },
+      )),
+      Element::Menu => {
+        let mut parts = vec![input.get_attribute("menu").unwrap_or("")];
+        parts.extend(
+          input
+            .get_attribute("items")
+            .unwrap_or("")
+            .split('>')
+            .map(str::trim)
+            .filter(|item| !item.is_empty()),
+        );
+        spans.push(text(
+          &parts.join(" › "),
+          Style {
+            font: style.font.bold(),
+            ..style
+          },
+        ));
+      }
+      Element::Passthrough => spans.push(text(input.get_attribute("content").unwrap_or(""), style)),
+      Element::LineBreak => spans.push(Span {
+        text: "\n".to_string(),
+        ..text("", style)
+      }),
+      Element::Anchor => spans.push(Span {
+        anchors: vec![input.get_attribute("id").unwrap_or("").to_string()],
+        ..text("", style)
+      }),
+      Element::InlineImage => {
+        let alt = input
+          .positional_attributes
+          .first()
+          .map(|alt| alt.as_str())
+          .or(input.get_attribute("path"))
+          .unwrap_or("");
+        spans.push(text(&format!("[{}]", alt), style));
+      }
+      Element::Callout(_) | Element::Attribute(_) => (),
+      _ => spans.push(text(&input.content, style)),
+    }
+  }
+
+  /// Lays out spans as lines at the current position
+  fn paragraph(&mut self, spans: Vec<Span>) {
+    for line in wrap(spans, self.width()) {
+      self.ensure(self.line_height(line.size));
+      let x = self.x;
+      self.place_line(x, &line);
+    }
+  }
+
+  fn text_line(&mut self, x: f32, span: Span) {
+    for line in wrap(vec![span], self.right() - x) {
+      self.ensure(self.line_height(line.size));
+      self.place_line(x, &line);
+    }
+  }
+
+  /// Draws a line (and the waiting marker) at the current position
+  fn place_line(&mut self, x: f32, line: &Line) {
+    let height = self.line_height(line.size);
+    let baseline = self.y - height + (height - line.size) / 2.0 + line.size * 0.22;
+    let page = self.pages.len() - 1;
+
+    if let Some(marker) = self.marker.take() {
+      let marker_x = x - marker.style.width(&marker.text) - 6.0;
+      self.pages[page].ops.push(Op::Text {
+        x: marker_x,
+        y: baseline,
+        style: marker.style,
+        text: marker.text,
+      });
+    }
+    for (offset, span) in line.fragments.iter() {
+      for id in span.anchors.iter() {
+        self.anchors.entry(id.clone()).or_insert((page, self.y));
+      }
+      let width = span.style.width(&span.text);
+      if let Some(link) = &span.link {
+        let rect = Rect::new(
+          x + offset,
+          baseline - span.style.size * 0.25,
+          x + offset + width,
+          baseline + span.style.size * 0.9,
+        );
+        self.pages[page].links.push((rect, link.clone()));
+      }
+      if !span.text.is_empty() {
+        self.pages[page].ops.push(Op::Text {
+          x: x + offset,
+          y: baseline + span.style.rise,
+          style: span.style,
+          text: span.text.clone(),
+        });
+      }
+    }
+    self.y -= height;
+  }
+
+  /// Lists the footnotes at the end of the document
+  fn footnotes(&mut self, footnotes: &[&ElementSpan]) {
+    if footnotes.is_empty() {
+      return;
+    }
+    self.space(self.theme.block_spacing);
+    self.ensure(self.line_height(self.theme.font_size) * 2.0);
+    let (x, y, color) = (self.x, self.y, self.theme.border_color);
+    self.page().ops.push(Op::Line {
+      from: (x, y),
+      to: (x + 120.0, y),
+      color,
+    });
+    self.space(4.0);
+    let style = Style {
+      size: self.theme.font_size * 0.85,
+      ..self.style()
+    };
+    for footnote in footnotes {
+      let number = footnote.get_attribute("number").unwrap_or("");
+      self.paragraph(vec![Span {
+        text: format!(
+          "{}. {}",
+          number,
+          footnote.get_attribute("content").unwrap_or("")
+        ),
+        style,
+        link: None,
+        anchors: vec![format!("_footnotedef_{}", number)],
+      }]);
+    }
+  }
+
+  /// Writes the pages, images, links and the outline as pdf
+  fn finish(self) -> Vec<u8> {
+    let mut next = 1;
+    let mut alloc = || {
+      let id = Ref::new(next);
+      next += 1;
+      id
+    };
+    let catalog_id = alloc();
+    let tree_id = alloc();
+    let font_ids: Vec<_> = FONTS.iter().map(|_| alloc()).collect();
+    let page_ids: Vec<_> = self.pages.iter().map(|_| (alloc(), alloc())).collect();
+    let outline_id = alloc();
+    let item_ids: Vec<_> = self.headings.iter().map(|_| alloc()).collect();
+
+    let mut pdf = Pdf::new();
+    let mut catalog = pdf.catalog(catalog_id);
+    catalog.pages(tree_id);
+    if !self.headings.is_empty() {
+      catalog.outlines(outline_id);
+      catalog.page_mode(pdf_writer::types::PageMode::UseOutlines);
+    }
+    catalog.finish();
+    pdf
+      .pages(tree_id)
+      .kids(page_ids.iter().map(|(page, _)| *page))
+      .count(page_ids.len() as i32);
+    for (font, id) in FONTS.iter().zip(font_ids.iter()) {
+      pdf
+        .type1_font(*id)
+        .base_font(Name(font.base_font()))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+    // A svg image takes as many ids as it needs, so the
+    // images get theirs after everything else
+    let mut image_ids = Vec::new();
+    for image in self.images.iter() {
+      let id = Ref::new(next);
+      image_ids.push(id);
+      let image = match image {
+        Image::Raster(image) => image,
+        Image::Vector(tree) => {
+          next = svg2pdf::convert_tree_into(tree, svg2pdf::Options::default(), &mut pdf, id).get();
+          continue;
+        }
+      };
+      next += 1;
+      let alpha_id = image.alpha.as_ref().map(|_| {
+        next += 1;
+        Ref::new(next - 1)
+      });
+
+      let mut xobject = pdf.image_xobject(id, &image.data);
+      xobject.filter(image.filter);
+      xobject.width(image.width as i32);
+      xobject.height(image.height as i32);
+      xobject.color_space_name(Name(image.color_space));
+      xobject.bits_per_component(8);
+      if let Some(alpha_id) = alpha_id {
+        xobject.s_mask(alpha_id);
+      }
+      xobject.finish();
+      if let (Some(alpha), Some(alpha_id)) = (&image.alpha, alpha_id) {
+        let mut mask = pdf.image_xobject(alpha_id, alpha);
+        mask.filter(Filter::FlateDecode);
+        mask.width(image.width as i32);
+        mask.height(image.height as i32);
+        mask.color_space_name(Name(b"DeviceGray"));
+        mask.bits_per_component(8);
+      }
+    }
+
+    let count = self.pages.len();
+    for (index, (page, (page_id, content_id))) in self.pages.iter().zip(page_ids.iter()).enumerate()
+    {
+      let mut content = Content::new();
+      for op in page.ops.iter() {
+        write_op(&mut content, op);
+      }
+      // The page number at the bottom
+      let number = format!("{} / {}", index + 1, count);
+      let style = Style {
+        size: self.theme.font_size * 0.8,
+        ..self.style()
+      };
+      let x = (self.theme.page_width - style.width(&number)) / 2.0;
+      write_op(
+        &mut content,
+        &Op::Text {
+          x,
+          y: self.theme.margin / 2.0,
+          style,
+          text: number,
+        },
+      );
+      pdf.stream(*content_id, &content.finish());
+
+      let mut writer = pdf.page(*page_id);
+      writer
+        .parent(tree_id)
+        .media_box(Rect::new(
+          0.0,
+          0.0,
+          self.theme.page_width,
+          self.theme.page_height,
+        ))
+        .contents(*content_id);
+      let mut resources = writer.resources();
+      let mut fonts = resources.fonts();
+      for (font, id) in FONTS.iter().zip(font_ids.iter()) {
+        fonts.pair(Name(font.resource()), *id);
+      }
+      fonts.finish();
+      let mut xobjects = resources.x_objects();
+      for (image, id) in image_ids.iter().enumerate() {
+        xobjects.pair(Name(format!("Im{}", image + 1).as_bytes()), *id);
+      }
+      xobjects.finish();
+      resources.finish();
+
+      let mut annotations = writer.annotations();
+      for (rect, target) in page.links.iter() {
+        let destination = match target {
+          Target::Internal(id) => match self.anchors.get(id) {
+            Some(destination) => Some(*destination),
+            None => {
+              warn!("no target `{}` for a reference in pdf", id);
+              continue;
+            }
+          },
+          Target::External(_) => None,
+        };
+        let mut annotation = annotations.push();
+        annotation
+          .subtype(AnnotationType::Link)
+          .rect(*rect)
+          .border(0.0, 0.0, 0.0, None);
+        let mut action = annotation.action();
+        match (target, destination) {
+          (Target::External(url), _) => {
+            action.action_type(ActionType::Uri).uri(Str(url.as_bytes()));
+          }
+          (_, Some((page, y))) => {
+            action
+              .action_type(ActionType::GoTo)
+              .destination()
+              .page(page_ids[page].0)
+              .xyz(0.0, y, None);
+          }
+          _ => (),
+        }
+      }
+    }
+
+    if !self.headings.is_empty() {
+      write_outline(&mut pdf, outline_id, &item_ids, &self.headings, &page_ids);
+    }
+
+    pdf.finish()
+  }
+}
+
+/// Writes the outline with the titles nested by their level
+fn write_outline(
+  pdf: &mut Pdf,
+  outline_id: Ref,
+  item_ids: &[Ref],
+  headings: &[Heading],
+  page_ids: &[(Ref, Ref)],
+) {
+  // The parent of every title (`None` for the outline itself)
+  let mut parents: Vec<Option<usize>> = Vec::new();
+  let mut stack: Vec<usize> = Vec::new();
+  for (index, heading) in headings.iter().enumerate() {
+    while let Some(last) = stack.last() {
+      if headings[*last].level < heading.level {
+        break;
+      }
+      stack.pop();
+    }
+    parents.push(stack.last().copied());
+    stack.push(index);
+  }
+  let children = |parent: Option<usize>| -> Vec<usize> {
+    (0..headings.len())
+      .filter(|index| parents[*index] == parent)
+      .collect()
+  };
+  let descendants = |index: usize| -> i32 {
+    let mut count = 0;
+    let mut current = vec![index];
+    while let Some(item) = current.pop() {
+      for child in (0..headings.len()).filter(|child| parents[*child] == Some(item)) {
+        count += 1;
+        current.push(child);
+      }
+    }
+    count
+  };
+
+  let top = children(None);
+  let mut outline = pdf.outline(outline_id);
+  if let (Some(first), Some(last)) = (top.first(), top.last()) {
+    outline.first(item_ids[*first]).last(item_ids[*last]);
+  }
+  outline.count(headings.len() as i32);
+  outline.finish();
+
+  for (index, heading) in headings.iter().enumerate() {
+    let siblings = children(parents[index]);
+    let position = siblings
+      .iter()
+      .position(|sibling| *sibling == index)
+      .unwrap_or(0);
+    let mut item = pdf.outline_item(item_ids[index]);
+    item.title(TextStr(&heading.title));
+    item.parent(match parents[index] {
+      Some(parent) => item_ids[parent],
+      None => outline_id,
+    });
+    if position > 0 {
+      item.prev(item_ids[siblings[position - 1]]);
+    }
+    if let Some(next) = siblings.get(position + 1) {
+      item.next(item_ids[*next]);
+    }
+    let own = children(Some(index));
+    if let (Some(first), Some(last)) = (own.first(), own.last()) {
+      item.first(item_ids[*first]).last(item_ids[*last]);
+      item.count(descendants(index));
+    }
+    item
+      .dest()
+      .page(page_ids[heading.page].0)
+      .xyz(0.0, heading.y, None);
+  }
+}
+
+fn write_op(content: &mut Content, op: &Op) {
+  match op {
+    Op::Text { x, y, style, text } => {
+      let [r, g, b] = style.color;
+      content.set_fill_rgb(r, g, b);
+      content
+        .begin_text()
+        .set_font(Name(style.font.resource()), style.size)
+        .next_line(*x, *y)
+        .show(Str(&encode(text)))
+        .end_text();
+    }
+    Op::Rect {
+      x,
+      y,
+      width,
+      height,
+      color,
+    } => {
+      let [r, g, b] = *color;
+      content
+        .set_fill_rgb(r, g, b)
+        .rect(*x, *y, *width, *height)
+        .fill_nonzero();
+    }
+    Op::Line { from, to, color } => {
+      let [r, g, b] = *color;
+      content
+        .set_stroke_rgb(r, g, b)
+        .set_line_width(0.5)
+        .move_to(from.0, from.1)
+        .line_to(to.0, to.1)
+        .stroke();
+    }
+    Op::Image {
+      x,
+      y,
+      width,
+      height,
+      image,
+    } => {
+      content
+        .save_state()
+        .transform([*width, 0.0, 0.0, *height, *x, *y])
+        .x_object(Name(format!("Im{}", image + 1).as_bytes()))
+        .restore_state();
+    }
+  }
+}
+
+/// Breaks spans into lines not wider than `width`. Words wider
+/// than a line are broken anywhere.
+fn wrap(spans: Vec<Span>, width: f32) -> Vec<Line> {
+  let mut lines = Vec::new();
+  let mut line = Line {
+    fragments: Vec::new(),
+    width: 0.0,
+    size: 0.0,
+  };
+  let finish = |line: &mut Line, lines: &mut Vec<Line>| {
+    // Spaces at the end of a line aren't seen
+    if let Some((_, span)) = line.fragments.last_mut() {
+      span.text = span.text.trim_end().to_string();
+    }
+    let size = line
+      .fragments
+      .iter()
+      .map(|(_, span)| span.style.size)
+      .fold(0.0, f32::max);
+    line.size = if size > 0.0 { size } else { line.size };
+    lines.push(std::mem::replace(
+      line,
+      Line {
+        fragments: Vec::new(),
+        width: 0.0,
+        size: line.size,
+      },
+    ));
+  };
+
+  for span in spans {
+    line.size = line.size.max(span.style.size);
+    if span.text == "\n" {
+      finish(&mut line, &mut lines);
+      continue;
+    }
+    if span.text.is_empty() {
+      line.fragments.push((line.width, span));
+      continue;
+    }
+
+    let mut anchors = span.anchors.clone();
+    for word in split_words(&span.text) {
+      let mut word = word.to_string();
+      if line.width == 0.0 {
+        word = word.trim_start().to_string();
+      }
+      let mut word_width = span.style.width(&word);
+      if line.width
+        + word
+          .trim_end()
+          .chars()
+          .map(|c| span.style.font.width(c))
+          .sum::<f32>()
+          * span.style.size
+          / 1000.0
+        > width
+        && line.width > 0.0
+      {
+        finish(&mut line, &mut lines);
+        word = word.trim_start().to_string();
+        word_width = span.style.width(&word);
+      }
+      // A word wider than the line is broken
+      while word_width > width && word.chars().count() > 1 {
+        let mut split = 0;
+        let mut taken = 0.0;
+        for (index, c) in word.char_indices() {
+          let next = span.style.width(&c.to_string());
+          if taken + next > width - line.width && index > 0 {
+            split = index;
+            break;
+          }
+          taken += next;
+        }
+        if split == 0 {
+          break;
+        }
+        let rest = word.split_off(split);
+        push_fragment(&mut line, &span, word, std::mem::take(&mut anchors));
+        finish(&mut line, &mut lines);
+        word = rest;
+        word_width = span.style.width(&word);
+      }
+      push_fragment(&mut line, &span, word, std::mem::take(&mut anchors));
+    }
+  }
+  if !line.fragments.is_empty() {
+    finish(&mut line, &mut lines);
+  }
+
+  lines
+}
+
+/// Adds a word to the line. Words of the same span are joined.
+fn push_fragment(line: &mut Line, span: &Span, word: String, anchors: Vec<String>) {
+  let width = span.style.width(&word);
+  match line.fragments.last_mut() {
+    Some((_, last)) if anchors.is_empty() && last.style == span.style && last.link == span.link => {
+      last.text.push_str(&word);
+    }
+    _ => line.fragments.push((
+      line.width,
+      Span {
+        text: word,
+        anchors,
+        ..span.clone()
+      },
+    )),
+  }
+  line.width += width;
+}
+
+/// Splits a text into words with the spaces in front of them
+fn split_words(text: &str) -> Vec<&str> {
+  let mut words = Vec::new();
+  let mut start = 0;
+  let mut in_word = false;
+  for (index, c) in text.char_indices() {
+    if c == ' ' && in_word {
+      words.push(&text[start..index]);
+      start = index;
+      in_word = false;
+    } else if c != ' ' {
+      in_word = true;
+    }
+  }
+  if start < text.len() {
+    words.push(&text[start..]);
+  }
+
+  words
+}
+
+fn span(cell: &ElementSpan, key: &str) -> usize {
+  cell
+    .get_attribute(key)
+    .and_then(|span| span.parse().ok())
+    .unwrap_or(1)
+    .max(1)
+}
+
+/// Gets the paragraphs of blocks (e.g. the ones of an asciidoc cell)
+fn collect_paragraphs<'e, 'a>(
+  elements: &'e [ElementSpan<'a>],
+  paragraphs: &mut Vec<&'e ElementSpan<'a>>,
+) {
+  for element in elements.iter() {
+    match element.element {
+      Element::Paragraph => paragraphs.push(element),
+      _ => collect_paragraphs(&element.children, paragraphs),
+    }
+  }
+}
+
+fn load_png(data: &[u8]) -> std::result::Result<Raster, String> {
+  let mut decoder = png::Decoder::new(data);
+  decoder.set_transformations(png::Transformations::normalize_to_color8());
+  let mut reader = decoder.read_info().map_err(|error| error.to_string())?;
+  let mut buf = vec![0; reader.output_buffer_size()];
+  let info = reader
+    .next_frame(&mut buf)
+    .map_err(|error| error.to_string())?;
+  let pixels = &buf[..info.buffer_size()];
+
+  let (channels, color_space, alpha) = match info.color_type {
+    png::ColorType::Grayscale => (1, &b"DeviceGray"[..], false),
+    png::ColorType::GrayscaleAlpha => (1, &b"DeviceGray"[..], true),
+    png::ColorType::Rgb => (3, &b"DeviceRGB"[..], false),
+    png::ColorType::Rgba => (3, &b"DeviceRGB"[..], true),
+    png::ColorType::Indexed => return Err("indexed colors weren't expanded".to_string()),
+  };
+  let (color, alpha) = match alpha {
+    true => {
+      let mut color = Vec::new();
+      let mut mask = Vec::new();
+      for pixel in pixels.chunks(channels + 1) {
+        color.extend_from_slice(&pixel[..channels]);
+        mask.push(pixel[channels]);
+      }
+      (color, Some(compress(&mask)))
+    }
+    false => (pixels.to_vec(), None),
+  };
+
+  Ok(Raster {
+    width: info.width,
+    height: info.height,
+    data: compress(&color),
+    filter: Filter::FlateDecode,
+    color_space,
+    alpha,
+  })
+}
+
+/// Jpeg images are embedded as they are. Only the size and
+/// colors are read from the frame header.
+fn load_jpeg(data: Vec<u8>) -> std::result::Result<Raster, String> {
+  let mut index = 2;
+  while index + 9 < data.len() {
+    if data[index] != 0xff {
+      return Err("broken jpeg".to_string());
+    }
+    let marker = data[index + 1];
+    let length = u16::from_be_bytes([data[index + 2], data[index + 3]]) as usize;
+    // The start of frame markers (without DHT, JPG and DAC)
+    if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+      let height = u16::from_be_bytes([data[index + 5], data[index + 6]]) as u32;
+      let width = u16::from_be_bytes([data[index + 7], data[index + 8]]) as u32;
+      let color_space = match data[index + 9] {
+        1 => &b"DeviceGray"[..],
+        4 => &b"DeviceCMYK"[..],
+        _ => &b"DeviceRGB"[..],
+      };
+      return Ok(Raster {
+        width,
+        height,
+        data,
+        filter: Filter::DctDecode,
+        color_space,
+        alpha: None,
+      });
+    }
+    index += 2 + length;
+  }
+
+  Err("no frame in jpeg".to_string())
+}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+  let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+  // Writing to a vector doesn't fail
+  let _ = encoder.write_all(data);
+  encoder.finish().unwrap_or_default()
+}
@@ -7,7 +7,7 @@ use pretty_assertions::assert_eq;
 #[test]
 fn parse_empty_document() -> Result<()> {
   let ast = AST {
-    content: "",
+    content: "".into(),
     elements: Vec::new(),
     attributes: Vec::new(),
   };
@@ -22,7 +22,7 @@ fn parse_empty_document() -> Result<()> {
 #[test]
 fn parse_whitespace_only() -> Result<()> {
   let ast = AST {
-    content: "  ",
+    content: "  ".into(),
     elements: Vec::new(),
     attributes: Vec::new(),
   };
@@ -41,10 +41,10 @@ fn parse_whitespace_only() -> Result<()> {
 #[test]
 fn parse_basic_header() -> Result<()> {
   let ast = AST {
-    content: "= test\n",
+    content: "= test\n".into(),
     elements: vec![ElementSpan {
       source: None,
-      content: "= test",
+      content: "= test".into(),
       element: Element::Title { level: 1 },
       start: 0,
       end: 6,
@@ -75,10 +75,10 @@ fn parse_basic_header() -> Result<()> {
 #[test]
 fn parse_title_with_anchor() -> Result<()> {
   let ast = AST {
-    content: "[[test-anchor]]\n== test\n",
+    content: "[[test-anchor]]\n== test\n".into(),
     elements: vec![ElementSpan {
       source: None,
-      content: "[[test-anchor]]\n== test",
+      content: "[[test-anchor]]\n== test".into(),
       element: Element::Title { level: 2 },
       start: 0,
       end: 23,
@@ -115,10 +115,10 @@ fn parse_title_with_anchor() -> Result<()> {
 #[test]
 fn parse_atx_header() -> Result<()> {
   let ast = AST {
-    content: "== test\n",
+    content: "== test\n".into(),
     elements: vec![ElementSpan {
       source: None,
-      content: "== test",
+      content: "== test".into(),
       element: Element::Title { level: 2 },
       start: 0,
       end: 7,
@@ -146,10 +146,10 @@ fn parse_atx_header() -> Result<()> {
 #[test]
 fn parse_setext_header() -> Result<()> {
   let ast = AST {
-    content: "test\n====\n",
+    content: "test\n====\n".into(),
     elements: vec![ElementSpan {
       source: None,
-      content: "test\n====",
+      content: "test\n====".into(),
       element: Element::Title { level: 1 },
       start: 0,
       end: 9,
@@ -195,10 +195,10 @@ require "mytestmodule"
 "#;
 
   let ast = AST {
-    content: input,
+    content: input.into(),
     elements: vec![ElementSpan {
       source: None,
-      content: input.trim(),
+      content: input.trim().into(),
       element: Element::TypedBlock {
         kind: BlockType::Listing,
       },
@@ -262,10 +262,10 @@ require "mytestmodule"
 asciidoctrine dont sees it."#;
 
   let ast = AST {
-    content: input,
+    content: input.into(),
     elements: vec![ElementSpan {
       source: None,
-      content: input.trim(),
+      content: input.trim().into(),
       element: Element::TypedBlock {
         kind: BlockType::Listing,
       },
@@ -314,10 +314,10 @@ it has an internal <<reference>>. Both should be parsed.
 "#;
 
   let ast = AST {
-    content: input,
+    content: input.into(),
     elements: vec![ElementSpan {
       source: None,
-      content: input.trim(),
+      content: input.trim().into(),
       element: Element::Paragraph,
       start: 1,
       end: 148,
@@ -328,7 +328,7 @@ it has an internal <<reference>>. Both should be parsed.
       children: vec![
         ElementSpan {
           source: None,
-          content: "This is a basic paragraph. It has a link to ",
+          content: "This is a basic paragraph. It has a link to ".into(),
           element: Element::Text,
           start: 1,
           end: 45,
@@ -342,7 +342,7 @@ it has an internal <<reference>>. Both should be parsed.
         },
         ElementSpan {
           source: None,
-          content: "https://www.mytestsite.org[A test website]",
+          content: "https://www.mytestsite.org[A test website]".into(),
           element: Element::Link,
           start: 45,
           end: 87,
@@ -365,7 +365,7 @@ it has an internal <<reference>>. Both should be parsed.
         },
         ElementSpan {
           source: None,
-          content: " and\nit has an internal ",
+          content: " and\nit has an internal ".into(),
           element: Element::Text,
           start: 87,
           end: 111,
@@ -379,7 +379,7 @@ it has an internal <<reference>>. Both should be parsed.
         },
         ElementSpan {
           source: None,
-          content: "<<reference>>",
+          content: "<<reference>>".into(),
           element: Element::XRef,
           start: 111,
           end: 124,
@@ -396,7 +396,7 @@ it has an internal <<reference>>. Both should be parsed.
         },
         ElementSpan {
           source: None,
-          content: ". Both should be parsed.",
+          content: ". Both should be parsed.".into(),
           element: Element::Text,
           start: 124,
           end: 148,
@@ -430,10 +430,10 @@ This is a basic paragraph. It has an inline [[myanchor]]`anchor`.
 "#;
 
   let ast = AST {
-    content: input,
+    content: input.into(),
     elements: vec![ElementSpan {
       source: None,
-      content: input.trim(),
+      content: input.trim().into(),
       element: Element::Paragraph,
       start: 1,
       end: 66,
@@ -444,7 +444,7 @@ This is a basic paragraph. It has an inline [[myanchor]]`anchor`.
       children: vec![
         ElementSpan {
           source: None,
-          content: "This is a basic paragraph. It has an inline ",
+          content: "This is a basic paragraph. It has an inline ".into(),
           element: Element::Text,
           start: 1,
           end: 45,
@@ -458,7 +458,7 @@ This is a basic paragraph. It has an inline [[myanchor]]`anchor`.
         },
         ElementSpan {
           source: None,
-          content: "[[myanchor]]`anchor`",
+          content: "[[myanchor]]`anchor`".into(),
           element: Element::Styled,
           start: 45,
           end: 65,
@@ -466,7 +466,20 @@ This is a basic paragraph. It has an inline [[myanchor]]`anchor`.
           start_col: 45,
           end_line: 2,
           end_col: 65,
-          children: Vec::new(),
+          children: vec![ElementSpan {
+            source: None,
+            content: "anchor".into(),
+            element: Element::Text,
+            start: 58,
+            end: 64,
+            start_line: 2,
+            start_col: 58,
+            end_line: 2,
+            end_col: 64,
+            children: Vec::new(),
+            positional_attributes: Vec::new(),
+            attributes: Vec::new(),
+          }],
           positional_attributes: vec![],
           attributes: vec![
             Attribute {
@@ -485,7 +498,7 @@ This is a basic paragraph. It has an inline [[myanchor]]`anchor`.
         },
         ElementSpan {
           source: None,
-          content: ".",
+          content: ".".into(),
           element: Element::Text,
           start: 65,
           end: 66,
@@ -529,10 +542,10 @@ fn parse_bullet_list() -> Result<()> {
 "#;
 
   let ast = AST {
-    content: input,
+    content: input.into(),
     elements: vec![ElementSpan {
       source: None,
-      content: input.trim_start(),
+      content: input.trim_start().into(),
       element: Element::List(ListType::Bullet),
       start: 1,
       end: 28,
@@ -543,7 +556,7 @@ fn parse_bullet_list() -> Result<()> {
       children: vec![
         ElementSpan {
           source: None,
-          content: "* Item 1\n",
+          content: "* Item 1\n".into(),
           element: Element::ListItem(1),
           start: 1,
           end: 10,
@@ -553,7 +566,7 @@ fn parse_bullet_list() -> Result<()> {
           end_col: 1,
           children: vec![ElementSpan {
             source: None,
-            content: "Item 1",
+            content: "Item 1".into(),
             start: 3,
             end: 9,
             start_line: 2,
@@ -563,7 +576,7 @@ fn parse_bullet_list() -> Result<()> {
             element: Element::Paragraph,
             children: vec![ElementSpan {
               source: None,
-              content: "Item 1",
+              content: "Item 1".into(),
               start: 3,
               end: 9,
               start_line: 2,
@@ -583,7 +596,7 @@ fn parse_bullet_list() -> Result<()> {
         },
         ElementSpan {
           source: None,
-          content: "* Item 2\n",
+          content: "* Item 2\n".into(),
           element: Element::ListItem(1),
           start: 10,
           end: 19,
@@ -593,7 +606,7 @@ fn parse_bullet_list() -> Result<()> {
           end_col: 1,
           children: vec![ElementSpan {
             source: None,
-            content: "Item 2",
+            content: "Item 2".into(),
             start: 12,
             end: 18,
             start_line: 3,
@@ -603,7 +616,7 @@ fn parse_bullet_list() -> Result<()> {
             element: Element::Paragraph,
             children: vec![ElementSpan {
               source: None,
-              content: "Item 2",
+              content: "Item 2".into(),
               start: 12,
               end: 18,
               start_line: 3,
@@ -623,7 +636,7 @@ fn parse_bullet_list() -> Result<()> {
         },
         ElementSpan {
           source: None,
-          content: "* Item 3\n",
+          content: "* Item 3\n".into(),
           element: Element::ListItem(1),
           start: 19,
           end: 28,
@@ -633,7 +646,7 @@ fn parse_bullet_list() -> Result<()> {
           end_col: 1,
           children: vec![ElementSpan {
             source: None,
-            content: "Item 3",
+            content: "Item 3".into(),
             start: 21,
             end: 27,
             start_line: 4,
@@ -643,7 +656,7 @@ fn parse_bullet_list() -> Result<()> {
             element: Element::Paragraph,
             children: vec![ElementSpan {
               source: None,
-              content: "Item 3",
+              content: "Item 3".into(),
               start: 21,
               end: 27,
               start_line: 4,
@@ -684,10 +697,10 @@ fn parse_nested_bullet_list() -> Result<()> {
 "#;
 
   let ast = AST {
-    content: input,
+    content: input.into(),
     elements: vec![ElementSpan {
       source: None,
-      content: input.trim_start(),
+      content: input.trim_start().into(),
       element: Element::List(ListType::Bullet),
       start: 1,
       end: 31,
@@ -698,7 +711,7 @@ fn parse_nested_bullet_list() -> Result<()> {
       children: vec![
         ElementSpan {
           source: None,
-          content: "* Item 1\n",
+          content: "* Item 1\n".into(),
           element: Element::ListItem(1),
           start: 1,
           end: 10,
@@ -708,7 +721,7 @@ fn parse_nested_bullet_list() -> Result<()> {
           end_col: 1,
           children: vec![ElementSpan {
             source: None,
-            content: "Item 1",
+            content: "Item 1".into(),
             start: 3,
             end: 9,
             start_line: 2,
@@ -718,7 +731,7 @@ fn parse_nested_bullet_list() -> Result<()> {
             element: Element::Paragraph,
             children: vec![ElementSpan {
               source: None,
-              content: "Item 1",
+              content: "Item 1".into(),
               start: 3,
               end: 9,
               start_line: 2,
@@ -738,7 +751,7 @@ fn parse_nested_bullet_list() -> Result<()> {
         },
         ElementSpan {
           source: None,
-          content: "*** Item 2\n",
+          content: "*** Item 2\n".into(),
           element: Element::ListItem(3),
           start: 10,
           end: 21,
@@ -748,7 +761,7 @@ fn parse_nested_bullet_list() -> Result<()> {
           end_col: 1,
           children: vec![ElementSpan {
             source: None,
-            content: "Item 2",
+            content: "Item 2".into(),
             start: 14,
             end: 20,
             start_line: 3,
@@ -758,7 +771,7 @@ fn parse_nested_bullet_list() -> Result<()> {
             element: Element::Paragraph,
             children: vec![ElementSpan {
               source: None,
-              content: "Item 2",
+              content: "Item 2".into(),
               start: 14,
               end: 20,
               start_line: 3,
@@ -778,7 +791,7 @@ fn parse_nested_bullet_list() -> Result<()> {
         },
         ElementSpan {
           source: None,
-          content: "** Item 3\n",
+          content: "** Item 3\n".into(),
           element: Element::ListItem(2),
           start: 21,
           end: 31,
@@ -788,7 +801,7 @@ fn parse_nested_bullet_list() -> Result<()> {
           end_col: 1,
           children: vec![ElementSpan {
             source: None,
-            content: "Item 3",
+            content: "Item 3".into(),
             start: 24,
             end: 30,
             start_line: 4,
@@ -798,7 +811,7 @@ fn parse_nested_bullet_list() -> Result<()> {
             element: Element::Paragraph,
             children: vec![ElementSpan {
               source: None,
-              content: "Item 3",
+              content: "Item 3".into(),
               start: 24,
               end: 30,
               start_line: 4,
@@ -830,6 +843,50 @@ fn parse_nested_bullet_list() -> Result<()> {
   Ok(())
 }
 
+#[test]
+fn owned_ast_from_json() -> Result<()> {
+  let input = r#"= The "quoted" title
+
+A paragraph with a \ backslash.
+"#;
+
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(input, &opts, &mut env)?;
+  let json = serde_json::to_string(&ast)?;
+
+  // Escaped strings can't be borrowed from the json
+  let borrowed = JsonReader::new().parse(&json, &opts, &mut env)?;
+  assert_eq!(borrowed.clone().into_owned(), ast.clone().into_owned());
+
+  let owned: OwnedAST = JsonReader::new().parse_owned(&json)?;
+  drop(json);
+  let handle = std::thread::spawn(move || owned.elements.len());
+  assert_eq!(handle.join().unwrap(), ast.elements.len());
+  Ok(())
+}
+
+#[test]
+fn included_documents_own_their_text() -> Result<()> {
+  use asciidoctrine::util::Environment;
+  use std::borrow::Cow;
+
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  env.write("chapter.adoc", "Text of the chapter\n")?;
+  let ast = reader.parse("include::chapter.adoc[]\n", &opts, &mut env)?;
+
+  let include = match &ast.elements[0].element {
+    Element::IncludeElement(include) => include,
+    element => panic!("expected an include but got {:?}", element),
+  };
+  assert!(matches!(&include.inner.content, Cow::Owned(content) if content == "Text of the chapter\n"));
+  assert!(matches!(include.inner.elements[0].content, Cow::Owned(_)));
+  Ok(())
+}
+
 // Bullet Lists, Numbered Lists, Attribute Lists, Checked Lists
 // List Continuation, Blocks in Lists
 
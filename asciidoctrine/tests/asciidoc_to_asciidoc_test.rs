@@ -0,0 +1,203 @@
+use anyhow::Result;
+use asciidoctrine::util::Environment;
+use asciidoctrine::{self, *};
+use clap::Parser;
+use pretty_assertions::assert_eq;
+use std::io::BufWriter;
+
+#[test]
+fn normalize_blocks() -> Result<()> {
+  let content = r#"= Document
+:name: World
+
+Hello {name}, this is *bold*, __em__phasis and [.big]#role#.
+See <<intro,the intro>>.footnote:[A note]
+
+[[intro]]
+== Intro
+
+[NOTE]
+Short note
+
+- one
+-- two
+- three
++
+more
+
+[verse, Poet]
+____
+Line one
+Line two
+____
+
+[cols="1,2"]
+|===
+|Name |Value
+
+|a |b
+2+|span
+|===
+"#;
+  let mut env = util::Env::Cache(util::Cache::new());
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = AsciidocWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+
+  assert_eq!(
+    output,
+    r#"= Document
+:name: World
+
+Hello {name}, this is *bold*, __em__phasis and [.big]#role#.
+See <<intro,the intro>>.footnote:[A note]
+
+[[intro]]
+== Intro
+
+NOTE: Short note
+
+* one
+** two
+* three
++
+more
+
+[verse,Poet]
+____
+Line one
+Line two
+____
+
+[cols="1,2"]
+|===
+|Name |Value
+
+|a |b
+2+|span
+|===
+"#
+  );
+
+  // The canonical form doesn't change anymore
+  let ast = reader.parse(&output, &opts, &mut env)?;
+  let mut buf = BufWriter::new(Vec::new());
+  writer.write(ast, &opts, &mut buf)?;
+  assert_eq!(String::from_utf8(buf.into_inner()?)?, output);
+
+  Ok(())
+}
+
+#[test]
+fn keep_listings_verbatim() -> Result<()> {
+  let content = "[source,make]\n----\nall:\n\tcargo build  \n\n----\n";
+  let mut env = util::Env::Cache(util::Cache::new());
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = AsciidocWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+
+  assert_eq!(
+    output,
+    "[source,make]\n----\nall:\n\tcargo build  \n\n----\n"
+  );
+
+  let content = "----\n a\n----\n\n-----\n----\n-----\n";
+  let ast = reader.parse(content, &opts, &mut env)?;
+  let mut buf = BufWriter::new(Vec::new());
+  writer.write(ast, &opts, &mut buf)?;
+  let output = String::from_utf8(buf.into_inner()?)?;
+
+  assert_eq!(output, "----\n a\n----\n\n-----\n----\n-----\n");
+
+  Ok(())
+}
+
+#[test]
+fn inline_includes_and_resolve_attributes() -> Result<()> {
+  let mut env = util::Env::Cache(util::Cache::new());
+  env.write("inner.adoc", "= Inner\n\nFrom {place}.\n")?;
+  let content = ":place: inside\n\ninclude::inner.adoc[leveloffset=+1]\n";
+
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = AsciidocWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    ":place: inside\n\ninclude::inner.adoc[leveloffset=+1]\n"
+  );
+
+  // The cache gives out a file only once
+  env.write("inner.adoc", "= Inner\n\nFrom {place}.\n")?;
+  let opts =
+    options::Opts::parse_from(vec!["", "--inline-includes", "--resolve-attributes"].into_iter());
+  let ast = reader.parse(content, &opts, &mut env)?;
+  let mut buf = BufWriter::new(Vec::new());
+  writer.write(ast, &opts, &mut buf)?;
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(output, ":place: inside\n\n== Inner\n\nFrom inside.\n");
+
+  Ok(())
+}
+
+#[test]
+fn markdown_to_asciidoc() -> Result<()> {
+  let content = r#"# Title
+
+Some *emphasis* and a [link](http://example.org "Example").
+
+```rust
+fn main() {}
+```
+
+1. one
+   - nested
+2. two
+"#;
+  let mut env = util::Env::Cache(util::Cache::new());
+  let reader = MarkdownReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = AsciidocWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+
+  assert_eq!(
+    output,
+    r#"= Title
+
+Some _emphasis_ and a link:http://example.org[link,title=Example].
+
+[source,rust]
+----
+fn main() {}
+----
+
+. one
+* nested
+. two
+"#
+  );
+
+  Ok(())
+}
@@ -0,0 +1,215 @@
+use anyhow::Result;
+use asciidoctrine::{self, *};
+use clap::Parser;
+use pretty_assertions::assert_eq;
+use std::io::BufWriter;
+
+/// Reads the whole document, so every mistake
+/// in the markup shows up as error
+fn assert_well_formed(output: &str) {
+  for event in xml::reader::EventReader::new(output.as_bytes()) {
+    if let Err(error) = event {
+      panic!("{}\n{}", error, output);
+    }
+  }
+}
+
+#[test]
+fn sections_and_blocks() -> Result<()> {
+  let content = r#"= Manual
+:author: Jane Doe
+
+[[start]]
+== Getting Started
+
+See <<_details,the details>> and the note.footnote:[Read it & weep]
+
+.Build it
+[source,rust]
+----
+fn main() {} // <1>
+----
+<1> Starts <here>
+
+WARNING: Don't panic
+
+=== Details
+
+image::arch.png[Architecture,300]
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "docbook"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = DocbookWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_well_formed(&output);
+  assert_eq!(
+    output,
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<article xmlns="http://docbook.org/ns/docbook" xmlns:xl="http://www.w3.org/1999/xlink" version="5.0" xml:lang="en">
+  <info>
+    <title>Manual</title>
+    <author>
+      <personname>Jane Doe</personname>
+    </author>
+  </info>
+  <section xml:id="start">
+    <title>Getting Started</title>
+    <simpara>See <link linkend="_details">the details</link> and the note.<footnote><simpara>Read it &amp; weep</simpara></footnote></simpara>
+    <formalpara>
+      <title>Build it</title>
+      <para>
+        <programlisting language="rust" linenumbering="unnumbered">fn main() {} <co xml:id="CO1-1"/></programlisting>
+      </para>
+    </formalpara>
+    <calloutlist>
+      <callout arearefs="CO1-1">
+        <simpara>Starts &lt;here&gt;</simpara>
+      </callout>
+    </calloutlist>
+    <warning>
+      <simpara>Don’t panic</simpara>
+    </warning>
+    <section xml:id="_details">
+      <title>Details</title>
+      <informalfigure>
+        <mediaobject>
+          <imageobject><imagedata fileref="arch.png" contentwidth="300"/></imageobject><textobject><phrase>Architecture</phrase></textobject>
+        </mediaobject>
+      </informalfigure>
+    </section>
+  </section>
+</article>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn lists() -> Result<()> {
+  let content = r#"* one
+** nested
+* two
+
+. first
+.. sub
+
+CPU:: The brain
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "docbook"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = DocbookWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_well_formed(&output);
+  assert_eq!(
+    output,
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<article xmlns="http://docbook.org/ns/docbook" xmlns:xl="http://www.w3.org/1999/xlink" version="5.0" xml:lang="en">
+  <itemizedlist>
+    <listitem>
+      <simpara>one</simpara>
+      <itemizedlist>
+        <listitem>
+          <simpara>nested</simpara>
+        </listitem>
+      </itemizedlist>
+    </listitem>
+    <listitem>
+      <simpara>two</simpara>
+    </listitem>
+  </itemizedlist>
+  <orderedlist numeration="arabic">
+    <listitem>
+      <simpara>first</simpara>
+      <orderedlist numeration="loweralpha">
+        <listitem>
+          <simpara>sub</simpara>
+        </listitem>
+      </orderedlist>
+    </listitem>
+  </orderedlist>
+  <variablelist>
+    <varlistentry>
+      <term>CPU</term>
+      <listitem>
+        <simpara>The brain</simpara>
+      </listitem>
+    </varlistentry>
+  </variablelist>
+</article>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn tables() -> Result<()> {
+  let content = r#".Ports
+[cols="1,3",options="header"]
+|===
+|Port |Use
+
+.2+|80 |http
+|redirects
+2+^|none
+|===
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "docbook"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = DocbookWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_well_formed(&output);
+  assert_eq!(
+    output,
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<article xmlns="http://docbook.org/ns/docbook" xmlns:xl="http://www.w3.org/1999/xlink" version="5.0" xml:lang="en">
+  <table frame="all" rowsep="1" colsep="1">
+    <title>Ports</title>
+    <tgroup cols="2">
+      <colspec colname="col_1" colwidth="25*"/>
+      <colspec colname="col_2" colwidth="75*"/>
+      <thead>
+        <row>
+          <entry align="left" valign="top">Port</entry>
+          <entry align="left" valign="top">Use</entry>
+        </row>
+      </thead>
+      <tbody>
+        <row>
+          <entry morerows="1" align="left" valign="top">80</entry>
+          <entry align="left" valign="top">http</entry>
+        </row>
+        <row>
+          <entry align="left" valign="top">redirects</entry>
+        </row>
+        <row>
+          <entry namest="col_1" nameend="col_2" align="center" valign="top">none</entry>
+        </row>
+      </tbody>
+    </tgroup>
+  </table>
+</article>
+"#
+  );
+
+  Ok(())
+}
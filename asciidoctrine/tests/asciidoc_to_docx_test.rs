@@ -0,0 +1,221 @@
+use anyhow::Result;
+use asciidoctrine::{self, *};
+use clap::Parser;
+use std::io::{Cursor, Read};
+
+#[test]
+fn inline_styles_and_links() -> Result<()> {
+  let content = r#"Some *bold*, _italic_ and `mono` text
+with a https://example.org[link] and a <<target,reference>>.
+
+[[target]]
+== Target
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "docx"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = Cursor::new(Vec::new());
+  let mut writer = DocxWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let mut archive = zip::ZipArchive::new(Cursor::new(buf.into_inner()))?;
+  let mut document = String::new();
+  archive
+    .by_name("word/document.xml")?
+    .read_to_string(&mut document)?;
+  let mut styles = String::new();
+  archive
+    .by_name("word/styles.xml")?
+    .read_to_string(&mut styles)?;
+
+  assert!(document
+    .contains(r#"<w:r><w:rPr><w:b /><w:bCs /></w:rPr><w:t xml:space="preserve">bold</w:t></w:r>"#));
+  assert!(document.contains(
+    r#"<w:r><w:rPr><w:i /><w:iCs /></w:rPr><w:t xml:space="preserve">italic</w:t></w:r>"#
+  ));
+  assert!(document.contains(r#"<w:rFonts w:ascii="Courier New" w:hAnsi="Courier New" />"#));
+  assert!(document.contains(r#"<w:t xml:space="preserve"> text with a </w:t>"#));
+  assert!(document.contains(r#"<w:t xml:space="preserve">link</w:t>"#));
+  assert!(document.contains(r#"<w:hyperlink w:anchor="target" w:history="1">"#));
+  assert!(document.contains(r#"<w:t xml:space="preserve">reference</w:t>"#));
+  assert!(document.contains(r#"<w:bookmarkStart w:id="1" w:name="target" />"#));
+  assert!(document.contains(r#"<w:pStyle w:val="Heading1" />"#));
+  assert!(styles.contains(r#"w:styleId="Heading1""#));
+
+  Ok(())
+}
+
+#[test]
+fn lists_and_listings() -> Result<()> {
+  let content = r#"* one
+** nested
+* two
+
+. first
+. second
+
+[source,rust]
+----
+fn main() {
+	run(); // <1>
+}
+----
+<1> Runs it
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "docx"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = Cursor::new(Vec::new());
+  let mut writer = DocxWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let mut archive = zip::ZipArchive::new(Cursor::new(buf.into_inner()))?;
+  let mut document = String::new();
+  archive
+    .by_name("word/document.xml")?
+    .read_to_string(&mut document)?;
+  let mut numbering = String::new();
+  archive
+    .by_name("word/numbering.xml")?
+    .read_to_string(&mut numbering)?;
+
+  assert!(document.contains(r#"<w:numPr><w:numId w:val="1" /><w:ilvl w:val="1" /></w:numPr>"#));
+  assert!(document.contains(r#"<w:numPr><w:numId w:val="2" /><w:ilvl w:val="0" /></w:numPr>"#));
+  assert!(numbering.contains(r#"<w:numFmt w:val="bullet" />"#));
+  assert!(numbering.contains(r#"<w:numFmt w:val="decimal" />"#));
+  assert!(document.contains(r#"<w:pStyle w:val="SourceCode" />"#));
+  assert!(document.contains(r#"<w:tab /><w:t xml:space="preserve">run(); (1)</w:t>"#));
+  assert!(document.contains(r#"<w:t xml:space="preserve">(1)</w:t>"#));
+  assert!(document.contains(r#"<w:t xml:space="preserve">Runs it</w:t>"#));
+
+  Ok(())
+}
+
+#[test]
+fn tables_and_images() -> Result<()> {
+  let path = std::env::temp_dir().join("asciidoctrine_docx_test.png");
+  let mut encoder = png::Encoder::new(std::fs::File::create(&path)?, 4, 2);
+  encoder.set_color(png::ColorType::Rgb);
+  encoder.write_header()?.write_image_data(&[0; 24])?;
+
+  let content = format!(
+    r#".Values
+[%header]
+|===
+|Name |Value
+|a |b
+|===
+
+image::{}[Picture,40]
+
+image::missing.png[Missing]
+"#,
+    path.display()
+  );
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "docx"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(&content, &opts, &mut env)?;
+
+  let mut buf = Cursor::new(Vec::new());
+  let mut writer = DocxWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let mut archive = zip::ZipArchive::new(Cursor::new(buf.into_inner()))?;
+  let mut document = String::new();
+  archive
+    .by_name("word/document.xml")?
+    .read_to_string(&mut document)?;
+  std::fs::remove_file(&path)?;
+
+  assert!(document.contains(r#"<w:b /><w:bCs /></w:rPr><w:t xml:space="preserve">Name</w:t>"#));
+  assert!(document.contains(r#"<w:t xml:space="preserve">b</w:t>"#));
+  // 40 by 20 pixels
+  assert!(document.contains(r#"<wp:extent cx="381000" cy="190500" />"#));
+  assert!(document.contains(r#"<w:pStyle w:val="Caption" />"#));
+  assert!(document.contains(r#"<w:t xml:space="preserve">[Missing]</w:t>"#));
+
+  Ok(())
+}
+
+#[test]
+fn styles_of_reference_doc() -> Result<()> {
+  let path = std::env::temp_dir().join("asciidoctrine_reference.docx");
+  let code = docx_rs::Style::new("SourceCode", docx_rs::StyleType::Paragraph)
+    .name("Source Code")
+    .size(30);
+  docx_rs::Docx::new()
+    .add_style(code)
+    .build()
+    .pack(std::fs::File::create(&path)?)?;
+
+  let content = "----\ncode\n----\n";
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(
+    vec!["", "-w", "docx", "--reference-doc", path.to_str().unwrap()].into_iter(),
+  );
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = Cursor::new(Vec::new());
+  let mut writer = DocxWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let mut archive = zip::ZipArchive::new(Cursor::new(buf.into_inner()))?;
+  let mut styles = String::new();
+  archive
+    .by_name("word/styles.xml")?
+    .read_to_string(&mut styles)?;
+  std::fs::remove_file(&path)?;
+
+  // The styles of the reference are kept, the missing ones added
+  assert_eq!(styles.matches(r#"w:styleId="SourceCode""#).count(), 1);
+  assert!(styles.contains(r#"<w:sz w:val="30" />"#));
+  assert!(styles.contains(r#"w:styleId="Heading1""#));
+
+  Ok(())
+}
+
+#[test]
+fn footnotes() -> Result<()> {
+  let content = r#"A footnote.footnote:note[Written by hand.] And the
+same one again.footnote:note[]
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "docx"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = Cursor::new(Vec::new());
+  let mut writer = DocxWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let mut archive = zip::ZipArchive::new(Cursor::new(buf.into_inner()))?;
+  let mut document = String::new();
+  archive
+    .by_name("word/document.xml")?
+    .read_to_string(&mut document)?;
+  let mut footnotes = String::new();
+  archive
+    .by_name("word/footnotes.xml")?
+    .read_to_string(&mut footnotes)?;
+
+  // The placeholders are replaced, only the first reference is a
+  // real footnote reference
+  assert!(!document.contains("asciidoctrine-footnote"));
+  assert_eq!(
+    document
+      .matches(r#"<w:footnoteReference w:id="1" />"#)
+      .count(),
+    1
+  );
+  assert!(document.contains(r#"<w:vertAlign w:val="superscript" /></w:rPr><w:t>1</w:t>"#));
+  assert!(footnotes.contains(r#"<w:footnote w:id="1">"#));
+  assert!(footnotes.contains("Written by hand."));
+
+  Ok(())
+}
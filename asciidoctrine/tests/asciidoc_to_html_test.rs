@@ -130,6 +130,55 @@ fn bullet_list() -> Result<()> {
   Ok(())
 }
 
+#[test]
+fn nested_bullet_list_siblings() -> Result<()> {
+  let content = r#"
+* one
+** two
+** three
+* four
+** five
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<ul>
+  <li>
+    <p>one</p>
+    <ul>
+      <li>
+        <p>two</p>
+      </li>
+      <li>
+        <p>three</p>
+      </li>
+    </ul>
+  </li>
+  <li>
+    <p>four</p>
+    <ul>
+      <li>
+        <p>five</p>
+      </li>
+    </ul>
+  </li>
+</ul>
+"#
+  );
+
+  Ok(())
+}
+
 #[test]
 fn collapsible_blocks() -> Result<()> {
   let content = r#"
@@ -503,15 +552,15 @@ We can even have multiple paragraphs
               <li>
                 <p>with</p>
               </li>
-            <li>
-              <p>multiple</p>
-            </li>
-          </ul>
-        </li>
-        <li>
-          <p>entries</p>
-        </li>
-      </ul>
+              <li>
+                <p>multiple</p>
+              </li>
+            </ul>
+          </li>
+          <li>
+            <p>entries</p>
+          </li>
+        </ul>
       </td>
     </tr>
   </tbody>
@@ -523,16 +572,282 @@ We can even have multiple paragraphs
 }
 
 #[test]
-fn sourcecode_blocks() -> Result<()> {
+fn table_spans_and_sections() -> Result<()> {
   let content = r#"
-[source, bash]
+[cols="2,1,^1",options="footer"]
+|===
+| Name | Size | Unit
+
+| Disk 2+| unknown
+.2+| Memory | 16 | GB
+| 2 m| modules
+3*| x
+|===
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<table class="tableblock frame-all grid-all stretch">
+  <colgroup>
+    <col style="width: 50%;">
+    <col style="width: 25%;">
+    <col style="width: 25%;">
+  </colgroup>
+  <thead>
+    <tr>
+      <th>Name</th>
+      <th>Size</th>
+      <th class="halign-center valign-top">Unit</th>
+    </tr>
+  </thead>
+  <tbody>
+    <tr>
+      <td><p>Disk</p></td>
+      <td colspan="2"><p>unknown</p></td>
+    </tr>
+    <tr>
+      <td rowspan="2"><p>Memory</p></td>
+      <td><p>16</p></td>
+      <td class="halign-center valign-top"><p>GB</p></td>
+    </tr>
+    <tr>
+      <td><p>2</p></td>
+      <td class="halign-center valign-top"><p><code>modules</code></p></td>
+    </tr>
+  </tbody>
+  <tfoot>
+    <tr>
+      <td><p>x</p></td>
+      <td><p>x</p></td>
+      <td class="halign-center valign-top"><p>x</p></td>
+    </tr>
+  </tfoot>
+</table>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn table_spans_bigger_than_the_table() -> Result<()> {
+  let content = r#"[cols="1"]
+|===
+.3+|a
+|b
+|===
+
+[cols="2"]
+|===
+3+|c
+|d
+.4+|e
+|f
+|===
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<table class="tableblock frame-all grid-all stretch">
+  <colgroup>
+    <col style="width: 100%;">
+  </colgroup>
+  <tbody>
+    <tr>
+      <td><p>a</p></td>
+    </tr>
+    <tr>
+      <td><p>b</p></td>
+    </tr>
+  </tbody>
+</table>
+<table class="tableblock frame-all grid-all stretch">
+  <colgroup>
+    <col style="width: 50%;">
+    <col style="width: 50%;">
+  </colgroup>
+  <tbody>
+    <tr>
+      <td colspan="2"><p>c</p></td>
+    </tr>
+    <tr>
+      <td><p>d</p></td>
+      <td rowspan="2"><p>e</p></td>
+    </tr>
+    <tr>
+      <td><p>f</p></td>
+    </tr>
+  </tbody>
+</table>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn csv_and_dsv_tables() -> Result<()> {
+  let content = r#"
+,===
+Name,Value
+
+"Smith, John","say ""hi"""
+,===
+
+[format=dsv, separator=;]
+|===
+x;y\;z
+|===
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<table class="tableblock frame-all grid-all stretch">
+  <colgroup>
+    <col style="width: 50%;">
+    <col style="width: 50%;">
+  </colgroup>
+  <thead>
+    <tr>
+      <th>Name</th>
+      <th>Value</th>
+    </tr>
+  </thead>
+  <tbody>
+    <tr>
+      <td><p>Smith, John</p></td>
+      <td><p>say "hi"</p></td>
+    </tr>
+  </tbody>
+</table>
+<table class="tableblock frame-all grid-all stretch">
+  <colgroup>
+    <col style="width: 50%;">
+    <col style="width: 50%;">
+  </colgroup>
+  <tbody>
+    <tr>
+      <td><p>x</p></td>
+      <td><p>y;z</p></td>
+    </tr>
+  </tbody>
+</table>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn nested_inline_formatting() -> Result<()> {
+  let content = r#"
+A *bold _and italic_* text, **un**constrained and snake_case_name.
+
+E = mc^2^ and H~2~O with #highlight# and [.big]#role# text.
+
+Not bold: 2*3*4 and a * b * c, but ``mono *strong*`` here.
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<p>A <strong>bold <em>and italic</em></strong> text, <strong>un</strong>constrained and snake_case_name.</p>
+<p>E = mc<sup>2</sup> and H<sub>2</sub>O with <mark>highlight</mark> and <span class="big">role</span> text.</p>
+<p>Not bold: 2*3*4 and a * b * c, but <code>mono <strong>strong</strong></code> here.</p>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn inline_macros() -> Result<()> {
+  let content = r#"
+Press kbd:[Ctrl+S] or kbd:[F11], then btn:[OK].
+
+Select menu:File[Save > As] or menu:Help[].
+
+Raw pass:[<u>underline</u>], anchor:here[] and issue:42[label].
+
+image:icon.png[Icon,16,16] and image:badge.svg[link=https://example.com]
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<p>Press <span class="keyseq"><kbd>Ctrl</kbd>+<kbd>S</kbd></span> or <kbd>F11</kbd>, then <b class="button">OK</b>.</p>
+<p>Select <span class="menuseq"><b class="menu">File</b>&#160;<i class="fa fa-angle-right caret"></i> <b class="submenu">Save</b>&#160;<i class="fa fa-angle-right caret"></i> <b class="menuitem">As</b></span> or <b class="menuref">Help</b>.</p>
+<p>Raw <u>underline</u>, <a id="here"></a> and issue:42[label].</p>
+<p><span class="image"><img src="icon.png" alt="Icon" width="16" height="16"></span> and <span class="image"><a class="image" href="https://example.com"><img src="badge.svg" alt="badge"></a></span></p>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn substitutions() -> Result<()> {
+  let content = r#"
+Fish & chips <cheap> (C) it's "`quoted`" text... +
+next line with a->b.
+
+[subs="attributes+"]
 ----
-echo "hello world!"
+<{cpp}> & (C)
+----
+
+[subs="none"]
+----
+<b>raw</b> {cpp}
 ----
-[source, bash]
-....
-echo "hello world!"
-....
 "#;
   let reader = AsciidocReader::new();
   let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
@@ -547,15 +862,830 @@ echo "hello world!"
   let output = String::from_utf8(buf.into_inner()?)?;
   assert_eq!(
     output,
-    r#"<div class="listingblock">
-  <pre>echo "hello world!"</pre>
+    "<p>Fish &amp; chips &lt;cheap&gt; \u{a9} it\u{2019}s &#8220;quoted&#8221; text\u{2026}\u{200b}<br>
+next line with a\u{2192}b.</p>
+<div class=\"listingblock\">
+  <pre>&lt;C++&gt; &amp; (C)</pre>
 </div>
-<div class="listingblock">
-  <pre>echo "hello world!"</pre>
+<div class=\"listingblock\">
+  <pre><b>raw</b> {cpp}</pre>
 </div>
-"#
+"
   );
 
   Ok(())
 }
 
+#[test]
+fn markdown_compatibility() -> Result<()> {
+  let content = r#"
+# Document
+
+## Section
+
+```rust
+fn main() {}
+```
+
+> A quote with *strong* text
+> over two lines.
+>
+> Second paragraph
+> -- Some Author, Some Book
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  assert_eq!(ast.elements[0].element, Element::Title { level: 1 });
+  let listing = &ast.elements[2];
+  assert_eq!(
+    listing.positional_attributes,
+    vec![AttributeValue::Ref("source"), AttributeValue::Ref("rust")]
+  );
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<h1>Document</h1>
+<h2 id="_section">Section</h2>
+<div class="listingblock">
+  <pre>fn main() {}</pre>
+</div>
+<div class="quoteblock">
+  <blockquote>
+    <p>A quote with <strong>strong</strong> text
+over two lines.</p>
+    <p>Second paragraph</p>
+  </blockquote>
+  <div class="attribution">
+    &#8212; Some Author<br>
+    <cite>Some Book</cite>
+  </div>
+</div>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn sourcecode_blocks() -> Result<()> {
+  let content = r#"
+[source, bash]
+----
+echo "hello world!"
+----
+[source, bash]
+....
+echo "hello world!"
+....
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<div class="listingblock">
+  <pre>echo "hello world!"</pre>
+</div>
+<div class="listingblock">
+  <pre>echo "hello world!"</pre>
+</div>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn included_documents() -> Result<()> {
+  use asciidoctrine::util::Environment;
+
+  let content = r#"
+= Main document
+
+include::chapters/chapter.adoc[leveloffset=+1]
+
+include::chapters/code.adoc[tag=important]
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["", "doc/main.adoc"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  env.write(
+    "doc/chapters/chapter.adoc",
+    "== Chapter\n\nText of the chapter\n\ninclude::../main.adoc[]\n",
+  )?;
+  env.write(
+    "doc/chapters/code.adoc",
+    "Not included\n// tag::important[]\nOnly this is included\n// end::important[]\nNot included either\n",
+  )?;
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let cycle = match &ast.elements[1].element {
+    Element::IncludeElement(include) => include.inner.elements[2].element.clone(),
+    element => panic!("expected an include but got {:?}", element),
+  };
+  assert_eq!(
+    cycle,
+    Element::Error(
+      "include cycle detected: doc/main.adoc -> doc/chapters/chapter.adoc -> doc/main.adoc"
+        .to_string()
+    )
+  );
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert!(output.starts_with(
+    r#"<h1>Main document</h1>
+<h3 id="_chapter">Chapter</h3>
+<p>Text of the chapter</p>
+"#
+  ));
+  assert!(output.ends_with(
+    r#"<p>Only this is included</p>
+"#
+  ));
+
+  Ok(())
+}
+
+#[test]
+fn included_lines() -> Result<()> {
+  use asciidoctrine::util::Environment;
+
+  let content = r#"
+include::lines.adoc[lines="2..3;5"]
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  env.write("lines.adoc", "one\ntwo\nthree\nfour\nfive\n")?;
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<p>two
+three
+five</p>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn document_attributes() -> Result<()> {
+  let content = r#"= {product} manual
+:product: Lisa
+:version: 0.1
+:platform: linux
+
+{product} {version} runs on {platform}.
+
+:version: 0.2
+
+[source, bash, subs="attributes+"]
+.{platform}/version.txt
+----
+{version} {platform}
+----
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts =
+    options::Opts::parse_from(vec!["", "-a", "platform=windows", "-a", "version=1.0@"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<h1>Lisa manual</h1>
+<p>Lisa 0.1 runs on windows.</p>
+<div class="listingblock">
+
+  <div class="title">windows/version.txt</div>
+  <pre>0.2 windows</pre>
+</div>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn unset_document_attributes() -> Result<()> {
+  let content = r#":edition: 2
+:draft: yes
+
+Edition {edition}, draft {draft}.
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts =
+    options::Opts::parse_from(vec!["", "-a", "edition!", "-a", "!draft=@"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(output, "<p>Edition {edition}, draft yes.</p>\n");
+
+  Ok(())
+}
+
+#[test]
+fn diagnostics_for_malformed_blocks() -> Result<()> {
+  let content = r#"= Broken document
+
+|===
+no cell
+|===
+
+include::missing.adoc[]
+
+A paragraph
+// with a comment
+is fine.
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "doc.adoc"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let diagnostics = diagnostics::collect(&ast);
+  assert_eq!(
+    diagnostics[0],
+    diagnostics::Diagnostic {
+      file: Some("doc.adoc".to_string()),
+      start_line: 4,
+      start_col: 1,
+      end_line: 4,
+      end_col: 8,
+      message: "couldn't parse the content of the block (expected [table_cell])".to_string(),
+    }
+  );
+  assert_eq!(diagnostics[1].start_line, 7);
+  assert_eq!(diagnostics.len(), 2);
+
+  let mut buf = BufWriter::new(Vec::new());
+  diagnostics::write(&diagnostics[..1], options::Diagnostics::Human, &mut buf)?;
+  assert_eq!(
+    String::from_utf8(buf.into_inner()?)?,
+    "doc.adoc:4:1: error: couldn't parse the content of the block (expected [table_cell])\n"
+  );
+
+  Ok(())
+}
+
+#[test]
+fn nested_sections() -> Result<()> {
+  let content = r#"= Document
+
+Preamble
+
+== First
+
+Intro
+
+=== Details
+
+More
+
+== Second
+
+==== Skipped
+
+Deep
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["", "--sections"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let first = &ast.elements[2];
+  assert_eq!(first.element, Element::Section { level: 1 });
+  assert_eq!(first.content, "== First\n\nIntro\n\n=== Details\n\nMore");
+  assert_eq!(first.children[0].element, Element::Title { level: 2 });
+  assert_eq!(first.children[2].element, Element::Section { level: 2 });
+  assert_eq!(
+    ast.elements[3].children[1].element,
+    Element::Section { level: 3 }
+  );
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<h1>Document</h1>
+<p>Preamble</p>
+<div class="sect1">
+<h2 id="_first">First</h2>
+<div class="sectionbody">
+<p>Intro</p>
+<div class="sect2">
+<h3 id="_details">Details</h3>
+<p>More</p>
+</div>
+</div>
+</div>
+<div class="sect1">
+<h2 id="_second">Second</h2>
+<div class="sectionbody">
+<div class="sect3">
+<h4 id="_skipped">Skipped</h4>
+<p>Deep</p>
+</div>
+</div>
+</div>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn admonitions() -> Result<()> {
+  let content = r#"
+NOTE: Keep this in mind.
+
+[TIP]
+Use the *cache*.
+
+[[danger]]
+[WARNING]
+.Data loss
+====
+Back up first.
+
+Really.
+====
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec![""].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  assert_eq!(
+    ast.elements[0].element,
+    Element::Admonition(AdmonitionType::Note)
+  );
+  assert_eq!(
+    ast.elements[1].element,
+    Element::Admonition(AdmonitionType::Tip)
+  );
+  assert_eq!(
+    ast.elements[2].element,
+    Element::Admonition(AdmonitionType::Warning)
+  );
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<div class="admonitionblock note">
+  <table>
+    <tr>
+      <td class="icon">
+        <div class="title">Note</div>
+      </td>
+      <td class="content">
+        <p>Keep this in mind.</p>
+      </td>
+    </tr>
+  </table>
+</div>
+<div class="admonitionblock tip">
+  <table>
+    <tr>
+      <td class="icon">
+        <div class="title">Tip</div>
+      </td>
+      <td class="content">
+        <p>Use the <strong>cache</strong>.</p>
+      </td>
+    </tr>
+  </table>
+</div>
+<div id="danger" class="admonitionblock warning">
+  <table>
+    <tr>
+      <td class="icon">
+        <div class="title">Warning</div>
+      </td>
+      <td class="content">
+        <div class="title">Data loss</div>
+        <p>Back up first.</p>
+        <p>Really.</p>
+      </td>
+    </tr>
+  </table>
+</div>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn compound_blocks() -> Result<()> {
+  let content = r#"
+.Aside
+****
+Sidebar text
+****
+
+[quote, Ada Lovelace, Notes]
+____
+The engine weaves patterns.
+____
+
+[verse, Poet]
+____
+First line
+  second line
+____
+
+[quote, Someone]
+A quoted paragraph.
+
+--
+Inside an open block
+--
+
+++++
+<video src="intro.mp4"></video>
+++++
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec![""].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let kinds = ast
+    .elements
+    .iter()
+    .map(|element| element.element.clone())
+    .collect::<Vec<_>>();
+  assert_eq!(
+    kinds,
+    vec![
+      Element::TypedBlock {
+        kind: BlockType::Sidebar
+      },
+      Element::TypedBlock {
+        kind: BlockType::Quote
+      },
+      Element::TypedBlock {
+        kind: BlockType::Verse
+      },
+      Element::TypedBlock {
+        kind: BlockType::Quote
+      },
+      Element::TypedBlock {
+        kind: BlockType::Open
+      },
+      Element::TypedBlock {
+        kind: BlockType::Passtrough
+      },
+    ]
+  );
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<div class="sidebarblock">
+  <div class="content">
+    <div class="title">Aside</div>
+    <p>Sidebar text</p>
+  </div>
+</div>
+<div class="quoteblock">
+  <blockquote>
+    <p>The engine weaves patterns.</p>
+  </blockquote>
+  <div class="attribution">
+    &#8212; Ada Lovelace<br>
+    <cite>Notes</cite>
+  </div>
+</div>
+<div class="verseblock">
+  <pre class="content">First line
+  second line</pre>
+  <div class="attribution">
+    &#8212; Poet
+  </div>
+</div>
+<div class="quoteblock">
+  <blockquote>
+    <p>A quoted paragraph.</p>
+  </blockquote>
+  <div class="attribution">
+    &#8212; Someone
+  </div>
+</div>
+<div class="openblock">
+  <div class="content">
+    <p>Inside an open block</p>
+  </div>
+</div>
+<video src="intro.mp4"></video>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn callouts() -> Result<()> {
+  let content = r#"
+[source, rust]
+----
+fn main() { // <1>
+  println!("<b>"); // <2> <3>
+}
+----
+<1> The entry point
+<2> Prints text
+<3> Isn't escaped by the listing
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec![""].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let listing = &ast.elements[0];
+  assert_eq!(listing.children[0].element, Element::Callout(1));
+  assert_eq!(listing.children[0].content, "// <1>");
+  assert_eq!(listing.children[0].start_line, 4);
+  assert_eq!(listing.children[0].start_col, 13);
+  assert_eq!(listing.children[2].element, Element::Callout(3));
+  assert_eq!(ast.elements[1].element, Element::List(ListType::Callout));
+  assert_eq!(
+    ast.elements[1].children[1].get_attribute("target"),
+    Some("CO1-2")
+  );
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r##"<div class="listingblock">
+  <pre>fn main() { <i class="conum" id="CO1-1" data-value="1"></i><b>(1)</b>
+  println!("&lt;b&gt;"); <i class="conum" id="CO1-2" data-value="2"></i><b>(2)</b> <i class="conum" id="CO1-3" data-value="3"></i><b>(3)</b>
+}</pre>
+</div>
+<div class="colist arabic">
+  <table>
+    <tr>
+      <td><a href="#CO1-1"><i class="conum" data-value="1"></i><b>1</b></a></td>
+      <td>
+        <p>The entry point</p>
+      </td>
+    </tr>
+    <tr>
+      <td><a href="#CO1-2"><i class="conum" data-value="2"></i><b>2</b></a></td>
+      <td>
+        <p>Prints text</p>
+      </td>
+    </tr>
+    <tr>
+      <td><a href="#CO1-3"><i class="conum" data-value="3"></i><b>3</b></a></td>
+      <td>
+        <p>Isn’t escaped by the listing</p>
+      </td>
+    </tr>
+  </table>
+</div>
+"##
+  );
+
+  Ok(())
+}
+
+#[test]
+fn footnotes() -> Result<()> {
+  let content = r#"
+:tool: asciidoctrine
+
+A footnote.footnote:[Written with {tool}.] And
+one with an id.footnote:disclaimer[Opinions & <views> are my own\] only.]
+
+A reference to the same footnote.footnote:disclaimer[]
+And an old one.footnoteref:[old, Still supported.]
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec![""].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let footnotes = ast.footnotes();
+  assert_eq!(footnotes.len(), 3);
+  assert_eq!(
+    footnotes[1].get_attribute("content"),
+    Some("Opinions & <views> are my own] only.")
+  );
+  assert_eq!(footnotes[2].get_attribute("number"), Some("3"));
+  let reference = &ast.elements[2].children[1];
+  assert_eq!(reference.element, Element::Footnote);
+  assert_eq!(reference.get_attribute("number"), Some("2"));
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r##"<p>A footnote.<sup class="footnote">[<a id="_footnoteref_1" class="footnote" href="#_footnotedef_1" title="View footnote.">1</a>]</sup> And
+one with an id.<sup class="footnote" id="_footnote_disclaimer">[<a id="_footnoteref_2" class="footnote" href="#_footnotedef_2" title="View footnote.">2</a>]</sup></p>
+<p>A reference to the same footnote.<sup class="footnoteref">[<a class="footnote" href="#_footnotedef_2" title="View footnote.">2</a>]</sup>
+And an old one.<sup class="footnote" id="_footnote_old">[<a id="_footnoteref_3" class="footnote" href="#_footnotedef_3" title="View footnote.">3</a>]</sup></p>
+<div id="footnotes">
+<hr>
+<div class="footnote" id="_footnotedef_1">
+<a href="#_footnoteref_1">1</a>. Written with asciidoctrine.
+</div>
+<div class="footnote" id="_footnotedef_2">
+<a href="#_footnoteref_2">2</a>. Opinions &amp; &lt;views&gt; are my own] only.
+</div>
+<div class="footnote" id="_footnotedef_3">
+<a href="#_footnoteref_3">3</a>. Still supported.
+</div>
+</div>
+"##
+  );
+
+  Ok(())
+}
+
+#[test]
+fn labeled_list() -> Result<()> {
+  let content = r#"
+CPU:: The brain
+  of the computer.
+RAM::
+Memory.
+cache::: A faster one.
+* on the chip
+Disk & <SSD>:: Storage.
++
+More about disks.
+Other::
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec![""].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let list = &ast.elements[0];
+  assert_eq!(list.element, Element::List(ListType::Labeled));
+  assert_eq!(list.children[2].element, Element::ListItem(2));
+  assert_eq!(list.children[2].get_attribute("term"), Some("cache"));
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<div class="dlist">
+  <dl>
+    <dt class="hdlist1">CPU</dt>
+    <dd>
+      <p>The brain
+  of the computer.</p>
+    </dd>
+    <dt class="hdlist1">RAM</dt>
+    <dd>
+      <p>Memory.</p>
+      <div class="dlist">
+        <dl>
+          <dt class="hdlist1">cache</dt>
+          <dd>
+            <p>A faster one.</p>
+            <ul>
+              <li>
+                <p>on the chip</p>
+              </li>
+            </ul>
+          </dd>
+        </dl>
+      </div>
+    </dd>
+    <dt class="hdlist1">Disk &amp; &lt;SSD&gt;</dt>
+    <dd>
+      <p>Storage.</p>
+      <p>More about disks.</p>
+    </dd>
+    <dt class="hdlist1">Other</dt>
+  </dl>
+</div>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn escape_titles_and_labels() -> Result<()> {
+  let content = r#"== Tom & Jerry <3
+
+.Title & <b>
+----
+code
+----
+
+[quote, A & B, <cite>]
+____
+Q
+____
+
+btn:[A & <b>]
+"#;
+  let reader = AsciidocReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<h2 id="_tom_jerry_3">Tom &amp; Jerry &lt;3</h2>
+<div class="listingblock">
+
+  <div class="title">Title &amp; &lt;b&gt;</div>
+  <pre>code</pre>
+</div>
+<div class="quoteblock">
+  <blockquote>
+    <p>Q</p>
+  </blockquote>
+  <div class="attribution">
+    &#8212; A &amp; B<br>
+    <cite>&lt;cite&gt;</cite>
+  </div>
+</div>
+<p><b class="button">A &amp; &lt;b&gt;</b></p>
+"#
+  );
+
+  Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use asciidoctrine::{self, *};
+use clap::Parser;
+use std::io::BufWriter;
+
+fn pages(output: &str) -> usize {
+  output.matches("/Type /Page\n").count()
+}
+
+#[test]
+fn text_and_outline() -> Result<()> {
+  let content = r#"= Manual
+
+== Getting Started
+
+Some *bold* text.
+
+=== Details
+
+More text.
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "pdf"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = PdfWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  // The content streams are not compressed, so the text can be searched
+  let output = String::from_utf8_lossy(&buf.into_inner()?).to_string();
+
+  assert!(output.starts_with("%PDF-"));
+  assert_eq!(pages(&output), 1);
+  assert!(output.contains("/BaseFont /Helvetica-Bold"));
+  assert!(output.contains("(Some ) Tj"));
+  assert!(output.contains("(bold) Tj"));
+  assert!(output.contains("/Outlines"));
+  assert!(output.contains("/Title (Getting Started)"));
+  assert!(output.contains("/Title (Details)"));
+  // The document title is not in the outline
+  assert!(!output.contains("/Title (Manual)"));
+
+  Ok(())
+}
+
+#[test]
+fn break_long_listings() -> Result<()> {
+  let lines: Vec<_> = (0..150).map(|line| format!("line {}", line)).collect();
+  let content = format!("----\n{}\n----\n", lines.join("\n"));
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "pdf"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(&content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = PdfWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8_lossy(&buf.into_inner()?).to_string();
+
+  assert!(pages(&output) > 1);
+  assert!(output.contains("/BaseFont /Courier"));
+  assert!(output.contains("(line 0) Tj"));
+  assert!(output.contains("(line 149) Tj"));
+  assert!(output.contains("(3 / 3) Tj"));
+
+  Ok(())
+}
+
+#[test]
+fn link_xrefs() -> Result<()> {
+  let content = r#"See <<target,the target>> and https://example.org[the web].
+
+[[target]]
+== Target
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "pdf"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = PdfWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8_lossy(&buf.into_inner()?).to_string();
+
+  assert_eq!(output.matches("/Subtype /Link").count(), 2);
+  assert!(output.contains("/S /GoTo"));
+  assert!(output.contains("/URI (https://example.org)"));
+
+  Ok(())
+}
+
+#[test]
+fn embed_images() -> Result<()> {
+  let png_path = std::env::temp_dir().join("asciidoctrine_pdf_test.png");
+  let mut encoder = png::Encoder::new(std::fs::File::create(&png_path)?, 2, 2);
+  encoder.set_color(png::ColorType::Rgba);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder
+    .write_header()?
+    .write_image_data(&[255, 0, 0, 128].repeat(4))?;
+  let svg_path = std::env::temp_dir().join("asciidoctrine_pdf_test.svg");
+  std::fs::write(
+    &svg_path,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20"><rect width="40" height="20" fill="blue"/></svg>"#,
+  )?;
+
+  let content = format!(
+    "image::{}[Red]\n\nimage::{}[Vector]\n\nimage::missing.png[Missing]\n",
+    png_path.display(),
+    svg_path.display()
+  );
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-w", "pdf"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(&content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = PdfWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8_lossy(&buf.into_inner()?).to_string();
+  std::fs::remove_file(&png_path)?;
+  std::fs::remove_file(&svg_path)?;
+
+  assert_eq!(output.matches("/Subtype /Image").count(), 2);
+  assert!(output.contains("/SMask"));
+  assert!(output.contains("/Im1 Do"));
+  // Svg images are drawn as forms
+  assert!(output.contains("/Subtype /Form"));
+  assert!(output.contains("/Im2 Do"));
+  // Images that can't be read are replaced by their alt text
+  assert!(output.contains("([Missing]) Tj"));
+  assert!(!output.contains("([Vector]) Tj"));
+
+  Ok(())
+}
@@ -0,0 +1,205 @@
+use anyhow::Result;
+use asciidoctrine::{self, *};
+use clap::Parser;
+use pretty_assertions::assert_eq;
+use std::io::BufWriter;
+
+#[test]
+fn markdown_blocks() -> Result<()> {
+  let content = r#"# Title
+
+Some *emphasis*, **strong** and `code` with a [link](http://example.org).
+
+Section
+-------
+
+- item one
+  continued
+- item two
+  - nested
+
+> quoted text
+"#;
+  let reader = MarkdownReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<h1>Title</h1>
+<p>Some <em>emphasis</em>, <strong>strong</strong> and <code>code</code> with a <a href="http://example.org">link</a>.</p>
+<h2 id="_section">Section</h2>
+<ul>
+  <li>
+    <p>item one
+continued</p>
+  </li>
+  <li>
+    <p>item two</p>
+    <ul>
+      <li>
+        <p>nested</p>
+      </li>
+    </ul>
+  </li>
+</ul>
+<div class="quoteblock">
+  <blockquote>
+    <p>quoted text</p>
+  </blockquote>
+</div>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn markdown_tables() -> Result<()> {
+  let content = r#"| Name | Value |
+|:-----|------:|
+| a    | `1`   |
+| b \| c |
+"#;
+  let reader = MarkdownReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    r#"<table class="tableblock frame-all grid-all stretch">
+  <colgroup>
+    <col style="width: 50%;">
+    <col style="width: 50%;">
+  </colgroup>
+  <thead>
+    <tr>
+      <th>Name</th>
+      <th class="halign-right valign-top">Value</th>
+    </tr>
+  </thead>
+  <tbody>
+    <tr>
+      <td><p>a</p></td>
+      <td class="halign-right valign-top"><p><code>1</code></p></td>
+    </tr>
+    <tr>
+      <td><p>b | c</p></td>
+      <td class="halign-right valign-top"></td>
+    </tr>
+  </tbody>
+</table>
+"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn markdown_positions() -> Result<()> {
+  let content = r#"Intro
+
+> - quoted *item*
+
+```rust save path=main.rs
+fn main() {}
+```
+"#;
+  let reader = MarkdownReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  // quote > list > item > paragraph > styled
+  let styled = &ast.elements[1].children[0].children[0].children[0].children[1];
+  assert_eq!(styled.element, Element::Styled);
+  assert_eq!(
+    (
+      styled.start_line,
+      styled.start_col,
+      styled.end_line,
+      styled.end_col
+    ),
+    (3, 12, 3, 18)
+  );
+  assert_eq!(&content[styled.start..styled.end], "*item*");
+
+  let listing = &ast.elements[2];
+  assert_eq!(
+    listing.positional_attributes,
+    vec![
+      AttributeValue::Ref("source"),
+      AttributeValue::Ref("rust"),
+      AttributeValue::Ref("save"),
+    ]
+  );
+  assert_eq!(listing.get_attribute("path"), Some("main.rs"));
+  assert_eq!(listing.get_attribute("content"), Some("fn main() {}"));
+
+  Ok(())
+}
+
+#[test]
+fn markdown_list_with_wide_spaces() -> Result<()> {
+  // The ideographic spaces are no indentation
+  let content = "- a\n\u{3000}\u{3000}x\n\n-\n\u{3000}\n";
+  let reader = MarkdownReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(
+    output,
+    "<ul>
+  <li>
+    <p>a
+\u{3000}\u{3000}x</p>
+  </li>
+  <li>
+    <p>\u{3000}</p>
+  </li>
+</ul>
+"
+  );
+
+  Ok(())
+}
+
+#[test]
+fn markdown_empty_headings() -> Result<()> {
+  let content = "#\n\n## \n";
+  let reader = MarkdownReader::new();
+  let mut opts = options::Opts::parse_from(vec!["--template", "-"].into_iter());
+  opts.template = Some("-".into());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut buf = BufWriter::new(Vec::new());
+  let mut writer = HtmlWriter::new();
+  writer.write(ast, &opts, &mut buf)?;
+
+  let output = String::from_utf8(buf.into_inner()?)?;
+  assert_eq!(output, "<h1></h1>\n<h2 id=\"_\"></h2>\n");
+
+  Ok(())
+}
@@ -99,12 +99,16 @@ fn substitude_params(
         let content = if snippet.raw {
           input
         } else {
-          let ast = CodeblockParser::parse(Rule::codeblock, &input).expect("couldn't parse input.");
+          match CodeblockParser::parse(Rule::codeblock, &input) {
+            Ok(ast) => {
+              snippet_params_history.push(snippet_params);
+              let snippet_params = extract_snippet_params(snippet_params_history, key);
 
-          snippet_params_history.push(snippet_params);
-          let snippet_params = extract_snippet_params(snippet_params_history, key);
-
-          merge_dependencies_inner(ast, snippets, snippet_params, key)
+              merge_dependencies_inner(ast, snippets, snippet_params, key)
+            }
+            // The error is already reported in the document
+            Err(_) => input,
+          }
         };
         output.push_str(&content);
       }
@@ -236,7 +240,10 @@ fn indent(content: &str, indentation: &str, output: &mut String) -> () {
 fn extract_snippet_params(snippet_params_history: SnippetParams, param: &str) -> SnippetParams {
   let mut snippet_params = snippet_params_history.clone().pop().unwrap_or_default();
   let mut new_params = HashMap::default();
-  let ast = CodeblockParser::parse(Rule::codeblock, &param).expect("couldn't parse input.");
+  let ast = match CodeblockParser::parse(Rule::codeblock, param) {
+    Ok(ast) => ast,
+    Err(_) => return snippet_params_history,
+  };
   let mut snippet_params_history = snippet_params_history;
 
   let ref_iter = ast.clone().filter(|element| match element.as_rule() {
@@ -316,11 +323,23 @@ fn extract_snippet_params(snippet_params_history: SnippetParams, param: &str) ->
   snippet_params_history
 }
 
+/// Checks if a snippet can be parsed. Snippets which can't
+/// be parsed are used as they are. The error holds the offset
+/// in the snippet and a description.
+pub fn check(input: &str) -> Result<(), (usize, String)> {
+  CodeblockParser::parse(Rule::codeblock, input)
+    .map(|_| ())
+    .map_err(|e| diagnostics::describe_parse_error(&e))
+}
+
 /// Extracts the ids of used snippets from a depending snippet
 pub fn get_dependencies(input: &str) -> Vec<String> {
   let mut depends_on_ids = Vec::new();
 
-  let ast = CodeblockParser::parse(Rule::codeblock, input).expect("couldn't parse input.");
+  let ast = match CodeblockParser::parse(Rule::codeblock, input) {
+    Ok(ast) => ast,
+    Err(_) => return depends_on_ids,
+  };
 
   for element in ast {
     match element.as_rule() {
@@ -355,7 +374,10 @@ pub fn get_dependencies(input: &str) -> Vec<String> {
 
 /// Merges the snippets into the depending snippet
 pub fn merge_dependencies(input: &str, snippets: &SnippetDB, key: &str) -> String {
-  let ast = CodeblockParser::parse(Rule::codeblock, input).expect("couldn't parse input.");
+  let ast = match CodeblockParser::parse(Rule::codeblock, input) {
+    Ok(ast) => ast,
+    Err(_) => return input.to_string(),
+  };
   let snippet_params = extract_snippet_params(Vec::from([HashMap::default()]), input);
 
   merge_dependencies_inner(ast, snippets, snippet_params, key)
@@ -10,6 +10,7 @@ use topological_sort::TopologicalSort;
 use core::cell::RefCell;
 use std::rc::Rc;
 use asciidoctrine::util::Environment;
+use asciidoctrine::visit::{self, Visit, Visitor, VisitorMut};
 #[macro_use]
 extern crate log;
 
@@ -176,12 +177,19 @@ impl LisaWrapper {
 pub enum Error {
   #[error("a nessessary attribute is missing")]
   Missing,
+  // Boxed to keep the results small
   #[error(transparent)]
-  Asciidoctrine(#[from] asciidoctrine::AsciidoctrineError),
+  Asciidoctrine(Box<asciidoctrine::AsciidoctrineError>),
   #[error("io problem")]
   Io(#[from] std::io::Error),
 }
 
+impl From<asciidoctrine::AsciidoctrineError> for Error {
+  fn from(error: asciidoctrine::AsciidoctrineError) -> Self {
+    Error::Asciidoctrine(Box::new(error))
+  }
+}
+
 pub struct Lisa {
   dependencies: TopologicalSort<String>,
   env: asciidoctrine::util::Env,
@@ -196,26 +204,29 @@ impl Lisa {
   }
 
   /// Gets recursively all snippets from an element
-  pub fn extract(&mut self, mut snippets: SnippetDB, input: &ElementSpan) -> Result<SnippetDB, Error> {
+  pub fn extract(&mut self, snippets: SnippetDB, input: &ElementSpan) -> Result<SnippetDB, Error> {
+    let mut elements = SnippetElements(Vec::new());
+    let _ = visit::walk_element(&mut elements, input);
+
+    elements
+      .0
+      .into_iter()
+      .try_fold(snippets, |snippets, element| self.extract_snippet(snippets, element))
+  }
+
+  /// Gets the snippet of a listing or an anchored text
+  fn extract_snippet(&mut self, mut snippets: SnippetDB, input: &ElementSpan) -> Result<SnippetDB, Error> {
     match &input.element {
       Element::TypedBlock {
         kind: BlockType::Listing,
       } => {
         let args = &mut input.positional_attributes.iter();
-        if !(args.next() == Some(&AttributeValue::Ref("source"))) {
+        // The values are compared as strings because an owned AST
+        // (e.g. read from json) has no references
+        if args.next().map(AttributeValue::as_str) != Some("source") {
           return Ok(snippets);
         }
-        let mut interpreter = None;
-        if let Some(value) = args.next()  {
-          match &value {
-            AttributeValue::Ref(value) => {
-              interpreter = Some(*value);
-            },
-            AttributeValue::String(value) => {
-              interpreter = Some(value.as_str());
-            }
-          }
-        }
+        let interpreter = args.next().map(AttributeValue::as_str);
 
         let title = input.get_attribute("title");
         let path = input.get_attribute("path").or(title);
@@ -230,19 +241,19 @@ impl Lisa {
         let mut kind = SnippetType::Plain;
 
         for argument in args {
-          match argument {
-            AttributeValue::Ref("save") => {
+          match argument.as_str() {
+            "save" => {
               let path = path.ok_or(Error::Missing)?;
               kind = SnippetType::Save(path.to_string());
             }
-            AttributeValue::Ref("eval") => {
+            "eval" => {
               let interpreter = interpreter.clone().ok_or(Error::Missing)?;
               kind = SnippetType::Eval(interpreter.to_string());
             }
-            AttributeValue::Ref("pipe") => {
+            "pipe" => {
               kind = SnippetType::Pipe;
             }
-            AttributeValue::Ref("lisa-raw") => {
+            "lisa-raw" => {
               raw = true;
             }
             _ => (),
@@ -257,7 +268,7 @@ impl Lisa {
 
         let content = input
           .get_attribute("content")
-          .unwrap_or(input.content);
+          .unwrap_or(&input.content);
         let mut dependencies = Vec::new();
         for dependency in codeblock_parser::get_dependencies(content).iter() {
           dependencies.push(dependency.to_string());
@@ -292,7 +303,7 @@ impl Lisa {
         }
         let content = input
           .get_attribute("content")
-          .unwrap_or(input.content);
+          .unwrap_or(&input.content);
         snippets.store(
           id.to_string(),
           Snippet {
@@ -308,16 +319,7 @@ impl Lisa {
 
         Ok(snippets)
       }
-      Element::IncludeElement(ast) => ast
-        .inner
-        .elements
-        .iter()
-        .try_fold(snippets, |snippets, element| {
-          self.extract(snippets, element)
-        }),
-      _ => input.children.iter().try_fold(snippets, |snippets, element| {
-        self.extract(snippets, element)
-      }),
+      _ => Ok(snippets),
     }
   }
 
@@ -404,12 +406,13 @@ impl Lisa {
 
   /// Gets all snippets from the ast
   pub fn extract_ast(&mut self, input: &AST) -> Result<SnippetDB, Error> {
-    let snippets = SnippetDB::new();
+    let mut elements = SnippetElements(Vec::new());
+    let _ = visit::walk_ast(&mut elements, input);
 
-    // extract snippets from all inner elements
-    input.elements.iter().try_fold(snippets, |snippets, element| {
-      self.extract(snippets, element)
-    })
+    elements
+      .0
+      .into_iter()
+      .try_fold(SnippetDB::new(), |snippets, element| self.extract_snippet(snippets, element))
   }
 
   /// Build all snippets (Runs the vm)
@@ -486,8 +489,55 @@ impl Lisa {
   }
 }
 
+/// Finds the elements which can hold a snippet
+struct SnippetElements<'v>(Vec<&'v ElementSpan<'v>>);
+
+impl<'v> Visitor<'v> for SnippetElements<'v> {
+  fn enter(&mut self, element: &'v ElementSpan<'v>) -> Visit {
+    match element.element {
+      Element::TypedBlock {
+        kind: BlockType::Listing,
+      }
+      | Element::Styled => {
+        self.0.push(element);
+        Visit::SkipChildren
+      }
+      _ => Visit::Continue,
+    }
+  }
+}
+
+/// Adds an error element to every snippet which can't be parsed
+struct InvalidSnippets;
+
+impl<'a> VisitorMut<'a> for InvalidSnippets {
+  fn enter(&mut self, element: &mut ElementSpan<'a>) -> Visit {
+    if let Element::TypedBlock {
+      kind: BlockType::Listing,
+    } = element.element
+    {
+      let content = element.get_attribute("content").unwrap_or(&element.content);
+      if let Err((offset, message)) = codeblock_parser::check(content) {
+        // The content is only found in the listing if no substitution
+        // changed it. Otherwise the position inside of it is unknown
+        // and the error is put at the start of the listing.
+        let mut positions = element.content.match_indices(content).map(|(start, _)| start);
+        let offset = match (positions.next(), positions.next()) {
+          (Some(start), None) => start + offset,
+          _ => 0,
+        };
+        let error = element.error_at(offset, format!("couldn't parse snippet ({})", message));
+        element.children.push(error);
+      }
+    }
+    Visit::Continue
+  }
+}
+
 impl Extension for Lisa {
-  fn transform<'a>(&mut self, input: AST<'a>) -> anyhow::Result<AST<'a>> {
+  fn transform<'a>(&mut self, mut input: AST<'a>) -> anyhow::Result<AST<'a>> {
+    let _ = visit::walk_ast_mut(&mut InvalidSnippets, &mut input);
+
     let snippets = self.extract_ast(&input)?;
 
     self.calculate_snippet_ordering(&snippets);
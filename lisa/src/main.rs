@@ -15,6 +15,7 @@ fn main() -> Result<()> {
   let reader: Box<dyn Reader> = match opts.readerfmt {
     options::Reader::Asciidoc => Box::new(AsciidocReader::new()),
     options::Reader::Json => Box::new(JsonReader::new()),
+    options::Reader::Markdown => Box::new(MarkdownReader::new()),
   };
 
   // read the input
@@ -60,6 +61,10 @@ fn main() -> Result<()> {
   // * Die zweiten sind (lua)-Scripte, die den AST als Struktur übergeben bekommen und wieder
   //   einen AST zurückgeben.
 
+  if let Some(format) = opts.diagnostics {
+    diagnostics::write(&diagnostics::collect(&ast), format, io::stderr())?;
+  }
+
   let output: Box<dyn Write> = match &opts.output {
     Some(output) => Box::new(fs::File::create(output).context("Could not open output file")?),
     None => Box::new(io::stdout()),
@@ -68,6 +73,9 @@ fn main() -> Result<()> {
   match opts.writerfmt {
     options::Writer::Html5 => HtmlWriter::new().write(ast, &opts, output)?,
     options::Writer::Json => JsonWriter::new().write(ast, &opts, output)?,
+    options::Writer::Docbook => DocbookWriter::new().write(ast, &opts, output)?,
+    options::Writer::Asciidoc => AsciidocWriter::new().write(ast, &opts, output)?,
+    options::Writer::Pdf => PdfWriter::new().write(ast, &opts, output)?,
     options::Writer::Docx => match &opts.output {
       Some(output) => {
         DocxWriter::new().write(
@@ -78,7 +86,6 @@ fn main() -> Result<()> {
       }
       None => bail!("docx cant only be written to file not to stdout"),
     },
-    _ => bail!("not yet supported"),
   };
 
   Ok(())
@@ -867,3 +867,237 @@ echo "you passed all checks"
   Ok(())
 }
 
+#[test]
+fn snippets_from_included_files() -> Result<()> {
+  use asciidoctrine::util::Environment;
+
+  let content = r#"
+The program is split across several documents.
+
+[source, python, save]
+.included.py
+----
+<<greeting>>
+----
+
+include::parts/greeting.adoc[]
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "main.adoc"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  env.write(
+    "parts/greeting.adoc",
+    r#"This snippet lives in its own file.
+
+[[greeting]]
+[source, python]
+----
+print("hello from an included file")
+----
+"#,
+  )?;
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut lisa = Lisa::from_env(env);
+  let _ast = lisa.transform(ast)?;
+
+  let mut outputs = lisa.into_cache().unwrap();
+
+  assert_eq!(
+    outputs.remove("included.py").unwrap(),
+    r#"print("hello from an included file")
+"#
+  );
+
+  assert!(outputs.is_empty());
+
+  Ok(())
+}
+
+#[test]
+fn snippets_for_target_platform() -> Result<()> {
+  let content = r#"
+The build script depends on the platform.
+
+[source, bash, save]
+.build.sh
+----
+ifdef::linux[]
+make all
+endif::linux[]
+ifeval::["{target}" == "windows"]
+nmake all
+endif::[]
+<<cleanup>>
+----
+
+ifeval::["{target}" == "linux"]
+[[cleanup]]
+[source, bash]
+----
+rm -rf build
+----
+endif::[]
+ifeval::["{target}" != "linux"]
+[[cleanup]]
+[source, bash]
+----
+rmdir /s build
+----
+endif::[]
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec!["", "-a", "target=linux", "-a", "linux"].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut lisa = Lisa::from_env(env);
+  let _ast = lisa.transform(ast)?;
+
+  let mut outputs = lisa.into_cache().unwrap();
+
+  assert_eq!(
+    outputs.remove("build.sh").unwrap(),
+    r#"make all
+rm -rf build
+"#
+  );
+
+  assert!(outputs.is_empty());
+
+  Ok(())
+}
+
+#[test]
+fn fenced_snippets() -> Result<()> {
+  let content = r#"
+```bash
+echo "not saved"
+```
+
+.build.sh
+[save]
+```bash
+make all
+```
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut lisa = Lisa::from_env(env);
+  let _ast = lisa.transform(ast)?;
+
+  let mut outputs = lisa.into_cache().unwrap();
+
+  assert_eq!(outputs.remove("build.sh").unwrap(), "make all\n");
+  assert!(outputs.is_empty());
+
+  Ok(())
+}
+
+#[test]
+fn markdown_snippets() -> Result<()> {
+  let content = r#"
+# Build
+
+```bash #cleanup
+rm -rf build
+```
+
+```bash save path=build.sh
+make all
+<<cleanup>>
+```
+"#;
+  let reader = MarkdownReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut lisa = Lisa::from_env(env);
+  let _ast = lisa.transform(ast)?;
+
+  let mut outputs = lisa.into_cache().unwrap();
+
+  assert_eq!(
+    outputs.remove("build.sh").unwrap(),
+    "make all\nrm -rf build\n"
+  );
+  assert!(outputs.is_empty());
+
+  Ok(())
+}
+
+#[test]
+fn keep_indentation_of_first_line() -> Result<()> {
+  let content = r#"
+[[body]]
+[source, python]
+----
+    return 1
+----
+
+[source, python, save]
+.indented.py
+----
+def one():
+<<body>>
+----
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut lisa = Lisa::from_env(env);
+  let _ast = lisa.transform(ast)?;
+
+  let mut outputs = lisa.into_cache().unwrap();
+
+  assert_eq!(
+    outputs.remove("indented.py").unwrap(),
+    "def one():\n    return 1\n"
+  );
+  assert!(outputs.is_empty());
+
+  Ok(())
+}
+
+#[test]
+fn snippets_from_owned_ast() -> Result<()> {
+  let content = r#"
+[[greeting]]
+[source, bash]
+----
+echo "hello"
+----
+
+[source, bash, save]
+.hello.sh
+----
+<<greeting>>
+----
+"#;
+  let reader = AsciidocReader::new();
+  let opts = options::Opts::parse_from(vec![""].into_iter());
+  let mut env = util::Env::Cache(util::Cache::new());
+  let ast = reader.parse(content, &opts, &mut env)?;
+
+  let mut json = Vec::new();
+  JsonWriter::new().write(ast.clone(), &opts, &mut json)?;
+  let json = String::from_utf8(json)?;
+
+  for ast in [ast.into_owned(), JsonReader::new().parse_owned(&json)?] {
+    let mut lisa = Lisa::from_env(util::Env::Cache(util::Cache::new()));
+    let _ast = lisa.transform(ast)?;
+
+    let mut outputs = lisa.into_cache().unwrap();
+
+    assert_eq!(outputs.remove("hello.sh").unwrap(), "echo \"hello\"\n");
+    assert!(outputs.is_empty());
+  }
+
+  Ok(())
+}